@@ -0,0 +1,154 @@
+use crate::bus::{Byte, Word};
+
+/// NTSC output rates in CPU cycles per bit
+const RATES: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+/// ### Delta modulation channel
+/// | Register | Content |
+/// |----------|---------|
+/// | `$4010` | `IL-- RRRR`: IRQ enable, loop, rate index |
+/// | `$4011` | `-DDD DDDD`: direct load of the output level |
+/// | `$4012` | Sample address, `$C000 + A * 64` |
+/// | `$4013` | Sample length, `L * 16 + 1` bytes |
+///
+/// The memory reader refills a one byte buffer through DMA whenever it runs dry
+/// and bytes remain; the output unit shifts the buffer out one bit per rate period,
+/// moving a 7 bit level up or down by 2. Addresses wrap from `$FFFF` to `$8000`.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Dmc {
+    irq_enabled: bool,
+    looped: bool,
+    rate: u16,
+    timer: u16,
+    level: Byte,
+    sample_address: Word,
+    sample_length: u16,
+    address: Word,
+    remaining: u16,
+    buffer: Option<Byte>,
+    /// A DMA read is on its way
+    fetching: bool,
+    shift: Byte,
+    bits: u8,
+    silence: bool,
+    pub irq: bool,
+}
+
+impl Dmc {
+    pub fn new() -> Self {
+        Self {
+            irq_enabled: false,
+            looped: false,
+            rate: RATES[0],
+            timer: RATES[0],
+            level: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            address: 0xC000,
+            remaining: 0,
+            buffer: None,
+            fetching: false,
+            shift: 0,
+            bits: 8,
+            silence: true,
+            irq: false,
+        }
+    }
+
+    pub fn write(&mut self, register: Word, value: Byte) {
+        match register {
+            0 => {
+                self.irq_enabled = value & 0x80 != 0;
+                self.looped = value & 0x40 != 0;
+                self.rate = RATES[(value & 0x0F) as usize];
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+            }
+            1 => self.level = value & 0x7F,
+            2 => self.sample_address = 0xC000 | (value as Word) << 6,
+            _ => self.sample_length = ((value as u16) << 4) + 1,
+        }
+    }
+
+    /// `$4015` bit 4: stops the sample, or restarts it if it had ended.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.remaining = 0;
+        } else if self.remaining == 0 {
+            self.restart();
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.remaining > 0
+    }
+
+    fn restart(&mut self) {
+        self.address = self.sample_address;
+        self.remaining = self.sample_length;
+    }
+
+    /// Address to fetch next, once per empty buffer.
+    pub fn fetch_request(&mut self) -> Option<Word> {
+        if self.buffer.is_some() || self.remaining == 0 || self.fetching {
+            return None;
+        }
+        self.fetching = true;
+        Some(self.address)
+    }
+
+    pub fn fill_buffer(&mut self, value: Byte) {
+        self.fetching = false;
+        if self.remaining == 0 {
+            return;
+        }
+        self.buffer = Some(value);
+        self.address = self.address.checked_add(1).unwrap_or(0x8000);
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            if self.looped {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    /// Clocked every CPU cycle, the rates are in CPU cycles.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.rate;
+        if !self.silence {
+            if self.shift & 0x01 != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+        self.shift >>= 1;
+        self.bits -= 1;
+        if self.bits == 0 {
+            self.bits = 8;
+            match self.buffer.take() {
+                Some(byte) => {
+                    self.silence = false;
+                    self.shift = byte;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> Byte {
+        self.level
+    }
+}
@@ -0,0 +1,53 @@
+use crate::bus::Byte;
+
+/// ### Envelope
+/// Either a constant volume or a sawtooth decaying from 15 to 0,
+/// one step every `volume + 1` quarter frames, restarting when looped.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Envelope {
+    start: bool,
+    looped: bool,
+    constant: bool,
+    /// Constant volume or divider period
+    volume: Byte,
+    divider: Byte,
+    decay: Byte,
+}
+
+impl Envelope {
+    /// `--LC VVVV`, the loop flag doubles as the length counter halt.
+    pub fn write(&mut self, value: Byte) {
+        self.looped = value & 0x20 != 0;
+        self.constant = value & 0x10 != 0;
+        self.volume = value & 0x0F;
+    }
+
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looped {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> Byte {
+        if self.constant {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
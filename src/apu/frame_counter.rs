@@ -0,0 +1,80 @@
+use crate::bus::Byte;
+
+/// CPU cycles of the quarter and half frame steps, NTSC
+const STEP_1: u32 = 7457;
+const STEP_2: u32 = 14913;
+const STEP_3: u32 = 22371;
+const STEP_4: u32 = 29829;
+const STEP_5: u32 = 37281;
+/// The IRQ flag is raised on the last 3 cycles of a 4-step sequence
+const FOUR_STEP_LENGTH: u32 = 29830;
+const FIVE_STEP_LENGTH: u32 = 37282;
+
+/// Units to clock on a given cycle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct FrameClocks {
+    /// Envelopes and the triangle's linear counter
+    pub quarter: bool,
+    /// Length counters and sweeps
+    pub half: bool,
+}
+
+/// ### Frame counter
+/// `$4017`: `MI-- ----`, mode (0: 4-step, 1: 5-step) and IRQ inhibit.
+///
+/// The write resets the sequence 3 or 4 CPU cycles later depending on the APU
+/// cycle parity, and selecting the 5-step mode clocks every unit right away.
+/// Only the 4-step mode raises the frame IRQ; setting the inhibit bit clears it.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct FrameCounter {
+    five_step: bool,
+    irq_inhibit: bool,
+    pub irq: bool,
+    cycle: u32,
+    reset_delay: Option<u8>,
+}
+
+impl FrameCounter {
+    pub fn write(&mut self, value: Byte, odd_cycle: bool) {
+        self.five_step = value & 0x80 != 0;
+        self.irq_inhibit = value & 0x40 != 0;
+        if self.irq_inhibit {
+            self.irq = false;
+        }
+        self.reset_delay = Some(if odd_cycle { 4 } else { 3 });
+    }
+
+    /// Advances by one CPU cycle.
+    pub fn clock(&mut self) -> FrameClocks {
+        if let Some(delay) = self.reset_delay {
+            if delay <= 1 {
+                self.reset_delay = None;
+                self.cycle = 0;
+                let all = self.five_step;
+                return FrameClocks {
+                    quarter: all,
+                    half: all,
+                };
+            }
+            self.reset_delay = Some(delay - 1);
+        }
+
+        self.cycle += 1;
+        let (length, last) = if self.five_step {
+            (FIVE_STEP_LENGTH, STEP_5)
+        } else {
+            (FOUR_STEP_LENGTH, STEP_4)
+        };
+        if !self.five_step && self.cycle >= FOUR_STEP_LENGTH - 2 && !self.irq_inhibit {
+            self.irq = true;
+        }
+        let clocks = FrameClocks {
+            quarter: matches!(self.cycle, STEP_1 | STEP_2 | STEP_3) || self.cycle == last,
+            half: self.cycle == STEP_2 || self.cycle == last,
+        };
+        if self.cycle == length {
+            self.cycle = 0;
+        }
+        clocks
+    }
+}
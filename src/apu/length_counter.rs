@@ -0,0 +1,43 @@
+use crate::bus::Byte;
+
+/// Lengths loaded by the upper 5 bits of a channel's last register
+const LENGTHS: [Byte; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/// ### Length counter
+/// Silences a channel once it counts down to 0, one step per half frame unless
+/// halted. A disabled channel (`$4015`) is held at 0 and ignores loads.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct LengthCounter {
+    counter: Byte,
+    pub halt: bool,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    /// Loads the length selected by `value >> 3`.
+    pub fn load(&mut self, value: Byte) {
+        if self.enabled {
+            self.counter = LENGTHS[(value >> 3) as usize];
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if self.counter > 0 && !self.halt {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.counter > 0
+    }
+}
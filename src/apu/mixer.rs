@@ -0,0 +1,19 @@
+use crate::bus::Byte;
+
+/// Output of the pulse half of the 2A03's DAC, `0.0-0.26` for two 4 bit levels.
+pub fn pulse_out(pulse_1: Byte, pulse_2: Byte) -> f32 {
+    let sum = pulse_1 as f32 + pulse_2 as f32;
+    if sum == 0.0 {
+        return 0.0;
+    }
+    95.88 / (8128.0 / sum + 100.0)
+}
+
+/// Output of the triangle, noise and DMC half of the DAC, `0.0-0.74`.
+pub fn tnd_out(triangle: Byte, noise: Byte, dmc: Byte) -> f32 {
+    let sum = triangle as f32 / 8227.0 + noise as f32 / 12241.0 + dmc as f32 / 22638.0;
+    if sum == 0.0 {
+        return 0.0;
+    }
+    159.79 / (1.0 / sum + 100.0)
+}
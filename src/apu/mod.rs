@@ -0,0 +1,220 @@
+use crate::{
+    apu::{
+        dmc::Dmc,
+        frame_counter::FrameCounter,
+        noise::Noise,
+        pulse::{Negate, Pulse},
+        triangle::Triangle,
+    },
+    audio::SampleSource,
+    bus::{
+        Bus, Byte, Word,
+        clock::{Clocked, Lines},
+    },
+    cartridge::mapper::SharedMapper,
+};
+
+mod dmc;
+mod envelope;
+mod frame_counter;
+mod length_counter;
+pub mod mixer;
+mod noise;
+mod pulse;
+mod triangle;
+
+pub const STATUS: Word = 0x4015;
+pub const FRAME_COUNTER: Word = 0x4017;
+
+/// ### 2A03 APU
+/// | Range | Content |
+/// |-------|---------|
+/// | `$4000-$4003` | Pulse 1 |
+/// | `$4004-$4007` | Pulse 2 |
+/// | `$4008-$400B` | Triangle |
+/// | `$400C-$400F` | Noise |
+/// | `$4010-$4013` | DMC |
+/// | `$4015` | Write: `---D NT21` channel enables. Read: `IF-D NT21` length counters and IRQ flags |
+/// | `$4017` | Frame counter |
+///
+/// Pulse and noise timers run on APU cycles, every other CPU cycle,
+/// the triangle's and DMC's on every CPU cycle. Reading `$4015` acknowledges the
+/// frame IRQ, writing it the DMC IRQ. The DMC fetches its samples through the
+/// CPU's DMA unit, see [`crate::cpu::dma::DmcDma`].
+/// Only `$4015` reads back, the other registers are write-only. The APU leaves
+/// the data lines it doesn't drive at 0, [`Apu::driven_bits`] tells the console
+/// bus which ones to fill with open bus.
+///
+/// The output goes through the nonlinear mixer, plus the expansion audio of the
+/// connected cartridge if it has any.
+pub struct Apu {
+    pulses: [Pulse; 2],
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+    frame_counter: FrameCounter,
+    /// Set on the second CPU cycle of every APU cycle
+    odd_cycle: bool,
+    cartridge: Option<SharedMapper>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    /// Bits the 2A03 drives when `addr` (`$4000-$4017`) is read, the others are
+    /// open bus. `$4015` leaves bit 5 floating and the controller ports `$4016`
+    /// and `$4017` only drive D0-D4.
+    pub fn driven_bits(addr: Word) -> Byte {
+        match addr {
+            STATUS => 0xDF,
+            0x4016 | FRAME_COUNTER => 0x1F,
+            _ => 0x00,
+        }
+    }
+
+    pub fn new() -> Self {
+        Self {
+            pulses: [
+                Pulse::new(Negate::OnesComplement),
+                Pulse::new(Negate::TwosComplement),
+            ],
+            triangle: Triangle::default(),
+            noise: Noise::new(),
+            dmc: Dmc::new(),
+            frame_counter: FrameCounter::default(),
+            odd_cycle: false,
+            cartridge: None,
+        }
+    }
+
+    /// Mixes the cartridge's expansion audio, if any, into the output.
+    pub fn connect_cartridge(&mut self, cartridge: SharedMapper) {
+        self.cartridge = Some(cartridge);
+    }
+
+    /// Output of the APU channels alone, without expansion audio.
+    pub fn mix(&self) -> f32 {
+        let pulse = mixer::pulse_out(self.pulses[0].output(), self.pulses[1].output());
+        let tnd = mixer::tnd_out(
+            self.triangle.output(),
+            self.noise.output(),
+            self.dmc.output(),
+        );
+        pulse + tnd
+    }
+
+    fn status(&self) -> Byte {
+        let mut status = 0;
+        for (bit, active) in [
+            self.pulses[0].length.active(),
+            self.pulses[1].length.active(),
+            self.triangle.length.active(),
+            self.noise.length.active(),
+            self.dmc.active(),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            status |= (active as Byte) << bit;
+        }
+        status | (self.frame_counter.irq as Byte) << 6 | (self.dmc.irq as Byte) << 7
+    }
+
+    fn set_enables(&mut self, value: Byte) {
+        self.pulses[0].length.set_enabled(value & 0x01 != 0);
+        self.pulses[1].length.set_enabled(value & 0x02 != 0);
+        self.triangle.length.set_enabled(value & 0x04 != 0);
+        self.noise.length.set_enabled(value & 0x08 != 0);
+        self.dmc.set_enabled(value & 0x10 != 0);
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulses[0].envelope.clock();
+        self.pulses[1].envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear_counter();
+    }
+
+    fn clock_half_frame(&mut self) {
+        for pulse in self.pulses.iter_mut() {
+            pulse.length.clock();
+            pulse.clock_sweep();
+        }
+        self.triangle.length.clock();
+        self.noise.length.clock();
+    }
+}
+
+impl Bus for Apu {
+    fn read(&mut self, addr: Word, read_only: bool) -> Byte {
+        if addr != STATUS {
+            return 0;
+        }
+        let status = self.status();
+        if !read_only {
+            self.frame_counter.irq = false;
+        }
+        status
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        let register = addr & 0x0003;
+        match addr {
+            0x4000..=0x4003 => self.pulses[0].write(register, value),
+            0x4004..=0x4007 => self.pulses[1].write(register, value),
+            0x4008..=0x400B => self.triangle.write(register, value),
+            0x400C..=0x400F => self.noise.write(register, value),
+            0x4010..=0x4013 => self.dmc.write(register, value),
+            STATUS => self.set_enables(value),
+            FRAME_COUNTER => self.frame_counter.write(value, self.odd_cycle),
+            _ => {}
+        }
+    }
+}
+
+impl Clocked for Apu {
+    fn tick(&mut self, lines: &mut Lines) {
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.odd_cycle {
+            self.pulses[0].clock_timer();
+            self.pulses[1].clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+
+        let clocks = self.frame_counter.clock();
+        if clocks.quarter {
+            self.clock_quarter_frame();
+        }
+        if clocks.half {
+            self.clock_half_frame();
+        }
+        if let Some(addr) = self.dmc.fetch_request() {
+            lines.request_dma(addr);
+        }
+        if self.frame_counter.irq || self.dmc.irq {
+            lines.assert_irq();
+        }
+    }
+
+    fn dma_data(&mut self, _addr: Word, value: Byte) {
+        self.dmc.fill_buffer(value);
+    }
+}
+
+impl SampleSource for Apu {
+    fn sample(&self) -> f32 {
+        let expansion = self.cartridge.as_ref().map_or(0.0, |cartridge| {
+            cartridge
+                .borrow()
+                .sample_source()
+                .map_or(0.0, |source| source.sample())
+        });
+        self.mix() + expansion
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{
+    apu::{envelope::Envelope, length_counter::LengthCounter},
+    bus::{Byte, Word},
+};
+
+/// NTSC timer periods in CPU cycles
+const PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+/// ### Noise channel
+/// | Register | Content |
+/// |----------|---------|
+/// | `$400C` | `--LC VVVV`: length halt / envelope loop, constant volume, volume |
+/// | `$400E` | `M--- PPPP`: mode, period index |
+/// | `$400F` | `LLLL L---`: length, restarts the envelope |
+///
+/// A 15 bit LFSR fed back from bits 0 and 1, or bits 0 and 6 in mode 1
+/// which gives a short 93 (or 31) step sequence.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Noise {
+    mode: bool,
+    period: u16,
+    timer: u16,
+    shift: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Self {
+            mode: false,
+            period: PERIODS[0],
+            timer: 0,
+            shift: 1,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+
+    pub fn write(&mut self, register: Word, value: Byte) {
+        match register {
+            0 => {
+                self.length.halt = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            1 => {}
+            2 => {
+                self.mode = value & 0x80 != 0;
+                self.period = PERIODS[(value & 0x0F) as usize];
+            }
+            _ => {
+                self.length.load(value);
+                self.envelope.restart();
+            }
+        }
+    }
+
+    /// Clocked every CPU cycle, the periods are in CPU cycles.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period - 1;
+        let tap = if self.mode { 6 } else { 1 };
+        let feedback = (self.shift ^ (self.shift >> tap)) & 0x01;
+        self.shift = (self.shift >> 1) | (feedback << 14);
+    }
+
+    pub fn output(&self) -> Byte {
+        if self.shift & 0x01 != 0 || !self.length.active() {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
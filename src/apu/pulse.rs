@@ -0,0 +1,150 @@
+use crate::{
+    apu::{envelope::Envelope, length_counter::LengthCounter},
+    bus::{Byte, Word},
+};
+
+const DUTY_CYCLES: [[Byte; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+/// Periods above this are out of the 11 bit timer's range
+const MAX_PERIOD: u16 = 0x07FF;
+/// Periods below this are silenced by the sweep unit
+const MIN_PERIOD: u16 = 8;
+
+/// How the sweep unit negates the period change, the only difference
+/// between the two pulse channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Negate {
+    /// Pulse 1 subtracts the change and 1 more
+    OnesComplement,
+    /// Pulse 2 subtracts the change
+    TwosComplement,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sweep {
+    enabled: bool,
+    period: Byte,
+    negate: bool,
+    shift: Byte,
+    reload: bool,
+    divider: Byte,
+}
+
+/// ### Pulse channel
+/// | Register | Content |
+/// |----------|---------|
+/// | `+0` | `DDLC VVVV`: duty, length halt / envelope loop, constant volume, volume |
+/// | `+1` | `EPPP NSSS`: sweep enable, period, negate, shift |
+/// | `+2` | Timer low |
+/// | `+3` | `LLLL LHHH`: length, timer high, restarts the sequence and envelope |
+///
+/// The sweep unit keeps computing its target period even while disabled,
+/// and mutes the channel whenever that target overflows 11 bits.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Pulse {
+    negate: Negate,
+    duty: Byte,
+    period: u16,
+    timer: u16,
+    step: usize,
+    sweep: Sweep,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+}
+
+impl Pulse {
+    pub fn new(negate: Negate) -> Self {
+        Self {
+            negate,
+            duty: 0,
+            period: 0,
+            timer: 0,
+            step: 0,
+            sweep: Sweep {
+                enabled: false,
+                period: 0,
+                negate: false,
+                shift: 0,
+                reload: false,
+                divider: 0,
+            },
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+
+    pub fn write(&mut self, register: Word, value: Byte) {
+        match register {
+            0 => {
+                self.duty = value >> 6;
+                self.length.halt = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            1 => {
+                self.sweep.enabled = value & 0x80 != 0;
+                self.sweep.period = (value >> 4) & 0x07;
+                self.sweep.negate = value & 0x08 != 0;
+                self.sweep.shift = value & 0x07;
+                self.sweep.reload = true;
+            }
+            2 => self.period = (self.period & 0x0700) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x07) as u16) << 8;
+                self.length.load(value);
+                self.step = 0;
+                self.envelope.restart();
+            }
+        }
+    }
+
+    /// Clocked every APU cycle, every other CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.step = (self.step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let sweep = self.sweep;
+        if sweep.divider == 0 && sweep.enabled && sweep.shift > 0 && !self.muted() {
+            self.period = self.target_period();
+        }
+        if sweep.divider == 0 || sweep.reload {
+            self.sweep.divider = sweep.period;
+            self.sweep.reload = false;
+        } else {
+            self.sweep.divider -= 1;
+        }
+    }
+
+    fn target_period(&self) -> u16 {
+        let change = self.period >> self.sweep.shift;
+        if !self.sweep.negate {
+            return self.period + change;
+        }
+        match self.negate {
+            Negate::OnesComplement => self.period.saturating_sub(change + 1),
+            Negate::TwosComplement => self.period.saturating_sub(change),
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.period < MIN_PERIOD || self.target_period() > MAX_PERIOD
+    }
+
+    pub fn output(&self) -> Byte {
+        if self.muted() || !self.length.active() || DUTY_CYCLES[self.duty as usize][self.step] == 0
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    apu::length_counter::LengthCounter,
+    bus::{Byte, Word},
+};
+
+const SEQUENCE: [Byte; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/// ### Triangle channel
+/// | Register | Content |
+/// |----------|---------|
+/// | `$4008` | `CRRR RRRR`: control (length halt), linear counter reload |
+/// | `$400A` | Timer low |
+/// | `$400B` | `LLLL LHHH`: length, timer high, sets the linear counter reload flag |
+///
+/// The sequencer only moves while both the length and the linear counter are
+/// non-zero, so a silenced triangle holds its last level instead of dropping to 0.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Triangle {
+    control: bool,
+    linear_reload_value: Byte,
+    linear_counter: Byte,
+    linear_reload: bool,
+    period: u16,
+    timer: u16,
+    step: usize,
+    pub length: LengthCounter,
+}
+
+impl Triangle {
+    pub fn write(&mut self, register: Word, value: Byte) {
+        match register {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.length.halt = self.control;
+                self.linear_reload_value = value & 0x7F;
+            }
+            1 => {}
+            2 => self.period = (self.period & 0x0700) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x07) as u16) << 8;
+                self.length.load(value);
+                self.linear_reload = true;
+            }
+        }
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.length.active() && self.linear_counter > 0 {
+                self.step = (self.step + 1) % SEQUENCE.len();
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    pub fn output(&self) -> Byte {
+        SEQUENCE[self.step]
+    }
+}
@@ -0,0 +1,133 @@
+use std::f64::consts::PI;
+
+/// Sub-sample positions the step kernel is computed for
+const PHASES: usize = 32;
+/// Output samples on each side of a step the kernel spreads over
+const HALF_WIDTH: usize = 8;
+const KERNEL_WIDTH: usize = 2 * HALF_WIDTH;
+/// Fraction of the output Nyquist frequency kept
+const CUTOFF: f64 = 0.9;
+
+/// ### Band-limited resampler
+/// Turns a level sampled every clock (1.79 MHz for the CPU) into audio at a host
+/// rate, blip-buffer style: the input is a step function, so only the changes
+/// are recorded, each as a windowed-sinc impulse at its exact sub-sample position,
+/// and reading integrates them back into band-limited steps.
+///
+/// Time is counted in clocks from the start of the current frame;
+/// [`Blip::end_frame`] makes the samples before that point readable.
+#[derive(Debug, Clone)]
+pub struct Blip {
+    /// Output samples per clock
+    factor: f64,
+    kernel: Vec<[f32; KERNEL_WIDTH]>,
+    /// Pending deltas, index 0 is the next sample to read
+    deltas: Vec<f32>,
+    /// Position of the frame start in output samples, past the readable ones
+    offset: f64,
+    available: usize,
+    integrator: f32,
+    /// Level seen by [`Blip::push`] and the clock it is at
+    level: f32,
+    clock: u64,
+}
+
+impl Blip {
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        Self {
+            factor: sample_rate as f64 / clock_rate,
+            kernel: kernel(),
+            deltas: vec![0.0; KERNEL_WIDTH],
+            offset: 0.0,
+            available: 0,
+            integrator: 0.0,
+            level: 0.0,
+            clock: 0,
+        }
+    }
+
+    /// Records a level change of `delta` at `clock` in the current frame.
+    pub fn add_delta(&mut self, clock: u64, delta: f32) {
+        let position = self.offset + clock as f64 * self.factor;
+        let index = position.floor() as usize;
+        let phase = ((position - index as f64) * PHASES as f64) as usize;
+        let end = self.available + index + KERNEL_WIDTH;
+        if self.deltas.len() < end {
+            self.deltas.resize(end, 0.0);
+        }
+        let start = self.available + index;
+        for (slot, weight) in self.deltas[start..end].iter_mut().zip(&self.kernel[phase]) {
+            *slot += delta * weight;
+        }
+    }
+
+    /// Feeds the level for the next clock of the frame, recording a step if it moved.
+    pub fn push(&mut self, level: f32) {
+        if level != self.level {
+            self.add_delta(self.clock, level - self.level);
+            self.level = level;
+        }
+        self.clock += 1;
+    }
+
+    /// Ends the frame `clocks` clocks in, or at the last [`Blip::push`] with `None`.
+    pub fn end_frame(&mut self, clocks: Option<u64>) {
+        let clocks = clocks.unwrap_or(self.clock);
+        let end = self.offset + clocks as f64 * self.factor;
+        let whole = end.floor();
+        self.available += whole as usize;
+        self.offset = end - whole;
+        self.clock = 0;
+        let needed = self.available + KERNEL_WIDTH;
+        if self.deltas.len() < needed {
+            self.deltas.resize(needed, 0.0);
+        }
+    }
+
+    pub fn samples_available(&self) -> usize {
+        self.available
+    }
+
+    /// Moves up to `out.len()` samples out, returns how many were written.
+    pub fn read_samples(&mut self, out: &mut [f32]) -> usize {
+        let count = out.len().min(self.available);
+        for (sample, delta) in out.iter_mut().zip(&self.deltas[..count]) {
+            self.integrator += delta;
+            *sample = self.integrator;
+        }
+        self.deltas.drain(..count);
+        self.deltas.resize(self.deltas.len() + count, 0.0);
+        self.available -= count;
+        count
+    }
+}
+
+/// Windowed sinc impulses, one per phase, each summing to 1 so steps keep their height.
+/// The step lands between taps `HALF_WIDTH - 1` and `HALF_WIDTH`, so a change is
+/// heard `HALF_WIDTH` samples late.
+fn kernel() -> Vec<[f32; KERNEL_WIDTH]> {
+    (0..PHASES)
+        .map(|phase| {
+            let fraction = phase as f64 / PHASES as f64;
+            let mut taps = [0.0; KERNEL_WIDTH];
+            for (i, tap) in taps.iter_mut().enumerate() {
+                let x = i as f64 - (HALF_WIDTH - 1) as f64 - fraction;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * CUTOFF * x).sin() / (PI * CUTOFF * x)
+                };
+                // Blackman window over the kernel's span
+                let w = (x + HALF_WIDTH as f64) / KERNEL_WIDTH as f64;
+                let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+                *tap = sinc * window.max(0.0);
+            }
+            let sum: f64 = taps.iter().sum();
+            let mut kernel = [0.0; KERNEL_WIDTH];
+            for (weight, tap) in kernel.iter_mut().zip(taps) {
+                *weight = (tap / sum) as f32;
+            }
+            kernel
+        })
+        .collect()
+}
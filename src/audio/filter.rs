@@ -0,0 +1,87 @@
+use std::f32::consts::PI;
+
+/// One sample in, one sample out, at a fixed sample rate.
+pub trait Filter {
+    fn process(&mut self, input: f32) -> f32;
+}
+
+/// First order RC high-pass.
+#[derive(Debug, Clone, Copy)]
+pub struct HighPass {
+    alpha: f32,
+    input: f32,
+    output: f32,
+}
+
+impl HighPass {
+    pub fn new(cutoff: f32, sample_rate: u32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        Self {
+            alpha: rc / (rc + dt),
+            input: 0.0,
+            output: 0.0,
+        }
+    }
+}
+
+impl Filter for HighPass {
+    fn process(&mut self, input: f32) -> f32 {
+        self.output = self.alpha * (self.output + input - self.input);
+        self.input = input;
+        self.output
+    }
+}
+
+/// First order RC low-pass.
+#[derive(Debug, Clone, Copy)]
+pub struct LowPass {
+    alpha: f32,
+    output: f32,
+}
+
+impl LowPass {
+    pub fn new(cutoff: f32, sample_rate: u32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        Self {
+            alpha: dt / (rc + dt),
+            output: 0.0,
+        }
+    }
+}
+
+impl Filter for LowPass {
+    fn process(&mut self, input: f32) -> f32 {
+        self.output += self.alpha * (input - self.output);
+        self.output
+    }
+}
+
+/// ### NES output filters
+/// The console's analog path: a 90 Hz and a 440 Hz high-pass, which take out the
+/// DC offset of the mixer, then a 14 kHz low-pass.
+#[derive(Debug, Clone, Copy)]
+pub struct NesFilters {
+    high_pass_90: HighPass,
+    high_pass_440: HighPass,
+    low_pass_14k: LowPass,
+}
+
+impl NesFilters {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            high_pass_90: HighPass::new(90.0, sample_rate),
+            high_pass_440: HighPass::new(440.0, sample_rate),
+            low_pass_14k: LowPass::new(14_000.0, sample_rate),
+        }
+    }
+}
+
+impl Filter for NesFilters {
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.high_pass_90.process(input);
+        let output = self.high_pass_440.process(output);
+        self.low_pass_14k.process(output)
+    }
+}
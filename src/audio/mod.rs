@@ -0,0 +1,63 @@
+use std::io;
+
+use crate::audio::{
+    blip::Blip,
+    filter::{Filter, NesFilters},
+};
+
+pub mod blip;
+pub mod filter;
+pub mod vgm;
+pub mod wav;
+
+/// NTSC CPU clock, the rate sample sources are sampled at
+pub const NTSC_CPU_CLOCK: f64 = 1_789_773.0;
+
+/// ### Sample source
+/// An analog audio output, sampled once per CPU cycle after the source was clocked.
+/// Levels use the scale of the APU mixer, where a full volume pulse channel
+/// is about `0.15`, so sources can simply be added together.
+pub trait SampleSource {
+    fn sample(&self) -> f32;
+}
+
+/// Where finished host rate audio goes.
+pub trait AudioSink {
+    fn write_samples(&mut self, samples: &[f32]) -> io::Result<()>;
+}
+
+/// ### Audio output
+/// Takes one level per CPU cycle and hands out host rate samples, resampled by a
+/// [`Blip`] then run through the console's [`NesFilters`].
+#[derive(Debug, Clone)]
+pub struct AudioOutput {
+    blip: Blip,
+    filters: NesFilters,
+    samples: Vec<f32>,
+}
+
+impl AudioOutput {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            blip: Blip::new(NTSC_CPU_CLOCK, sample_rate),
+            filters: NesFilters::new(sample_rate),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Feeds the level of the next CPU cycle.
+    pub fn push(&mut self, level: f32) {
+        self.blip.push(level);
+    }
+
+    /// Returns the samples for the cycles pushed since the last call.
+    pub fn end_frame(&mut self) -> &[f32] {
+        self.blip.end_frame(None);
+        self.samples.resize(self.blip.samples_available(), 0.0);
+        let count = self.blip.read_samples(&mut self.samples);
+        for sample in self.samples[..count].iter_mut() {
+            *sample = self.filters.process(*sample);
+        }
+        &self.samples[..count]
+    }
+}
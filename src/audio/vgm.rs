@@ -0,0 +1,198 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    rc::Rc,
+};
+
+use crate::{
+    audio::NTSC_CPU_CLOCK,
+    bus::{Byte, Word},
+    cpu::{Access, CPU, dma::OAM_DMA, observer::BusEvent},
+};
+
+/// VGM timestamps count samples at this rate
+pub const VGM_SAMPLE_RATE: u64 = 44_100;
+const VERSION: u32 = 0x161;
+const HEADER_SIZE: usize = 0xC0;
+/// Header fields, as offsets into the file
+const EOF_OFFSET: usize = 0x04;
+const VERSION_OFFSET: usize = 0x08;
+const TOTAL_SAMPLES: usize = 0x18;
+const DATA_OFFSET: usize = 0x34;
+const NES_APU_CLOCK: usize = 0x84;
+
+const CMD_NES_APU: Byte = 0xB4;
+const CMD_WAIT: Byte = 0x61;
+const CMD_WAIT_NTSC: Byte = 0x62;
+const CMD_WAIT_PAL: Byte = 0x63;
+const CMD_WAIT_SHORT: Byte = 0x70;
+const CMD_END: Byte = 0x66;
+const CMD_DATA_BLOCK: Byte = 0x67;
+/// Data block loaded in the NES APU's memory, after a 2 byte start address
+const BLOCK_NES_RAM: Byte = 0xC2;
+
+/// A write to an APU register, `cycle` counted from when logging started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApuWrite {
+    pub cycle: u64,
+    pub addr: Word,
+    pub value: Byte,
+}
+
+/// ### VGM logger
+/// Watches the CPU bus for writes to the APU registers at `$4000-$4017`
+/// and for the bytes the DMC fetches, then exports them as a VGM 1.61 file
+/// for the NES APU.
+///
+/// VGM players hold DPCM samples in memory uploaded before the music starts, so
+/// every fetched byte goes in data blocks at the start of the stream. Samples
+/// swapped in at the same address through bankswitching keep the last bytes
+/// fetched there.
+#[derive(Debug, Clone, Default)]
+pub struct VgmLogger {
+    start_cycle: u64,
+    writes: Vec<ApuWrite>,
+    samples: BTreeMap<Word, Byte>,
+    /// Page of the last OAM DMA, whose reads aren't samples
+    oam_page: Option<Byte>,
+}
+
+impl VgmLogger {
+    pub fn new(start_cycle: u64) -> Self {
+        Self {
+            start_cycle,
+            ..Self::default()
+        }
+    }
+
+    /// Starts logging everything `cpu` does from now on.
+    pub fn attach(cpu: &mut CPU) -> Rc<RefCell<Self>> {
+        let logger = Rc::new(RefCell::new(Self::new(cpu.general_cycles)));
+        let observer = logger.clone();
+        cpu.add_observer(move |event| observer.borrow_mut().observe(event));
+        logger
+    }
+
+    pub fn observe(&mut self, event: &BusEvent) {
+        match (event.access, event.addr) {
+            (Access::Write, OAM_DMA) => self.oam_page = Some(event.value),
+            // `$4016` belongs to the controllers. The dummy write of a
+            // read-modify-write reaches the APU like any other
+            (Access::Write | Access::DummyWrite, 0x4000..=0x4015 | 0x4017) => {
+                self.writes.push(ApuWrite {
+                    cycle: event.cycle.saturating_sub(self.start_cycle),
+                    addr: event.addr,
+                    value: event.value,
+                })
+            }
+            (Access::DmaRead, addr) if self.oam_page != Some((addr >> 8) as Byte) => {
+                self.samples.insert(addr, event.value);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn writes(&self) -> &[ApuWrite] {
+        &self.writes
+    }
+
+    /// DPCM bytes fetched so far, by address.
+    pub fn samples(&self) -> &BTreeMap<Word, Byte> {
+        &self.samples
+    }
+
+    /// Builds the VGM file, the music lasting until `end_cycle`
+    /// (counted like [`ApuWrite::cycle`]) or the last write if later.
+    pub fn to_vgm(&self, end_cycle: u64) -> Vec<Byte> {
+        let mut vgm = vec![0; HEADER_SIZE];
+        vgm[0..4].copy_from_slice(b"Vgm ");
+        put_u32(&mut vgm, VERSION_OFFSET, VERSION);
+        put_u32(&mut vgm, DATA_OFFSET, (HEADER_SIZE - DATA_OFFSET) as u32);
+        put_u32(&mut vgm, NES_APU_CLOCK, NTSC_CPU_CLOCK as u32);
+
+        for (start, data) in self.sample_blocks() {
+            vgm.extend([CMD_DATA_BLOCK, CMD_END, BLOCK_NES_RAM]);
+            vgm.extend((data.len() as u32 + 2).to_le_bytes());
+            vgm.extend(start.to_le_bytes());
+            vgm.extend(data);
+        }
+
+        let mut position = 0;
+        for write in &self.writes {
+            let at = to_samples(write.cycle);
+            push_wait(&mut vgm, at - position);
+            position = at;
+            vgm.extend([CMD_NES_APU, (write.addr - 0x4000) as Byte, write.value]);
+        }
+        let end = to_samples(end_cycle).max(position);
+        push_wait(&mut vgm, end - position);
+        vgm.push(CMD_END);
+
+        let eof = vgm.len() as u32 - EOF_OFFSET as u32;
+        put_u32(&mut vgm, EOF_OFFSET, eof);
+        put_u32(&mut vgm, TOTAL_SAMPLES, end as u32);
+        vgm
+    }
+
+    pub fn write_vgm(&self, writer: &mut impl Write, end_cycle: u64) -> io::Result<()> {
+        writer.write_all(&self.to_vgm(end_cycle))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, end_cycle: u64) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_vgm(&mut writer, end_cycle)?;
+        writer.flush()
+    }
+
+    /// Runs of consecutive sample addresses
+    fn sample_blocks(&self) -> Vec<(Word, Vec<Byte>)> {
+        let mut blocks: Vec<(Word, Vec<Byte>)> = Vec::new();
+        for (&addr, &value) in &self.samples {
+            match blocks.last_mut() {
+                Some((start, data)) if *start as usize + data.len() == addr as usize => {
+                    data.push(value)
+                }
+                _ => blocks.push((addr, vec![value])),
+            }
+        }
+        blocks
+    }
+}
+
+fn to_samples(cycle: u64) -> u64 {
+    (cycle as f64 * VGM_SAMPLE_RATE as f64 / NTSC_CPU_CLOCK) as u64
+}
+
+fn put_u32(vgm: &mut [Byte], offset: usize, value: u32) {
+    vgm[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Emits the shortest wait commands covering `samples`
+fn push_wait(vgm: &mut Vec<Byte>, mut samples: u64) {
+    while samples > 0 {
+        let step = match samples {
+            1..=16 => {
+                vgm.push(CMD_WAIT_SHORT + samples as Byte - 1);
+                samples
+            }
+            735 => {
+                vgm.push(CMD_WAIT_NTSC);
+                735
+            }
+            882 => {
+                vgm.push(CMD_WAIT_PAL);
+                882
+            }
+            _ => {
+                let step = samples.min(u16::MAX as u64);
+                vgm.push(CMD_WAIT);
+                vgm.extend((step as u16).to_le_bytes());
+                step
+            }
+        };
+        samples -= step;
+    }
+}
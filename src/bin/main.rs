@@ -202,7 +202,7 @@ impl DemoCPU {
             );
             for i in 0..16 {
                 d.draw_text(
-                    format!("{:02X}", self.cpu.read_byte(addr + i)).as_str(),
+                    format!("{:02X}", self.cpu.peek(addr + i)).as_str(),
                     origin_x + 70 + (25 * i as i32),
                     origin_y,
                     18,
@@ -0,0 +1,67 @@
+use std::{env, process};
+
+use cpu_6502::{
+    audio::wav::WavWriter,
+    bus::Byte,
+    nsf::{Nsf, player::NsfPlayer},
+};
+
+const SAMPLE_RATE: u32 = 44_100;
+const DEFAULT_SECONDS: f64 = 120.0;
+
+fn usage() -> ! {
+    eprintln!("usage: nsf2wav <input.nsf|input.nsfe> <output.wav> [track] [seconds]");
+    eprintln!("  track is counted from 1, defaults to the tune's starting song");
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 || args.len() > 4 {
+        usage();
+    }
+    let nsf = Nsf::from_file(&args[0]).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", args[0]);
+        process::exit(1);
+    });
+    let song = match args.get(2) {
+        Some(track) => match track.parse::<Byte>() {
+            Ok(track) if (1..=nsf.song_count).contains(&track) => track - 1,
+            _ => {
+                eprintln!("track must be between 1 and {}", nsf.song_count);
+                process::exit(2);
+            }
+        },
+        None => nsf.starting_song,
+    };
+    let seconds = match args.get(3) {
+        Some(seconds) => seconds.parse::<f64>().unwrap_or_else(|_| usage()),
+        None => nsf
+            .track_lengths
+            .get(song as usize)
+            .filter(|&&ms| ms >= 0)
+            .map_or(DEFAULT_SECONDS, |&ms| ms as f64 / 1000.0),
+    };
+
+    println!(
+        "{} - {} (track {}/{}, {seconds:.1}s)",
+        nsf.artist,
+        nsf.title,
+        song + 1,
+        nsf.song_count
+    );
+    if !nsf.chips.is_empty() {
+        println!("expansion audio {:?} is not emulated", nsf.chips);
+    }
+
+    let result = WavWriter::create(&args[1], SAMPLE_RATE).and_then(|mut wav| {
+        let mut player = NsfPlayer::new(nsf, SAMPLE_RATE);
+        player.start_song(song);
+        player.render(seconds, &mut wav)?;
+        wav.finish().map(|_| ())
+    });
+    if let Err(err) = result {
+        eprintln!("{}: {err}", args[1]);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,131 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::bus::{Byte, Word};
+
+/// ### Control lines
+/// What the devices drive back into the CPU during one cycle.
+/// A fresh, released set is handed to every device on every cycle,
+/// so asserting a line means holding it for that cycle only.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lines {
+    /// Level triggered, wired-OR between devices
+    pub irq: bool,
+    /// Edge triggered, the CPU reacts to the rising edge
+    pub nmi: bool,
+    /// Cycles the CPU must be held through RDY before its next instruction
+    pub stall: u32,
+    /// Address the device wants the CPU's DMA unit to read for it
+    pub dma: Option<Word>,
+}
+
+impl Lines {
+    pub fn assert_irq(&mut self) {
+        self.irq = true;
+    }
+
+    pub fn assert_nmi(&mut self) {
+        self.nmi = true;
+    }
+
+    /// Pulls RDY low for `cycles` more cycles, e.g. while a DMA owns the bus.
+    pub fn stall(&mut self, cycles: u32) {
+        self.stall += cycles;
+    }
+
+    /// Asks for a one byte DMA read, delivered through [`Clocked::dma_data`].
+    pub fn request_dma(&mut self, addr: Word) {
+        self.dma = Some(addr);
+    }
+}
+
+/// ### Clocked device
+/// A peripheral with a notion of time. It is advanced by one CPU cycle per call,
+/// after the CPU has finished the bus accesses of that cycle's instruction.
+pub trait Clocked {
+    fn tick(&mut self, lines: &mut Lines);
+
+    /// Receives the byte read for the device's last [`Lines::request_dma`].
+    fn dma_data(&mut self, _addr: Word, _value: Byte) {}
+}
+
+impl<T: Clocked + ?Sized> Clocked for Rc<RefCell<T>> {
+    fn tick(&mut self, lines: &mut Lines) {
+        self.borrow_mut().tick(lines);
+    }
+
+    fn dma_data(&mut self, addr: Word, value: Byte) {
+        self.borrow_mut().dma_data(addr, value);
+    }
+}
+
+/// ### Scheduler
+/// Keeps the devices attached to a CPU in step with it
+/// and folds their lines into the CPU's interrupt inputs.
+#[derive(Default)]
+pub struct Scheduler {
+    devices: Vec<Box<dyn Clocked>>,
+    irq: bool,
+    nmi: bool,
+    nmi_edge: bool,
+    stall: u32,
+    /// Pending DMA read and the index of the device that asked for it
+    dma: Option<(usize, Word)>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attach(&mut self, device: Box<dyn Clocked>) {
+        self.devices.push(device);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Advances every device by one cycle.
+    pub fn tick(&mut self) {
+        let mut lines = Lines::default();
+        for (index, device) in self.devices.iter_mut().enumerate() {
+            device.tick(&mut lines);
+            if let Some(addr) = lines.dma.take() {
+                self.dma = Some((index, addr));
+            }
+        }
+        if lines.nmi && !self.nmi {
+            self.nmi_edge = true;
+        }
+        self.nmi = lines.nmi;
+        self.irq = lines.irq;
+        self.stall += lines.stall;
+    }
+
+    /// State of the IRQ line after the last cycle.
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    /// Returns `true` once per rising edge seen on the NMI line.
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_edge)
+    }
+
+    /// Returns and clears the stall cycles requested so far.
+    pub fn take_stall(&mut self) -> u32 {
+        std::mem::take(&mut self.stall)
+    }
+
+    /// Address of the pending DMA read, if any.
+    pub fn dma_request(&self) -> Option<Word> {
+        self.dma.map(|(_, addr)| addr)
+    }
+
+    /// Hands the byte read for the pending DMA to the device that asked for it.
+    pub fn complete_dma(&mut self, value: Byte) {
+        if let Some((index, addr)) = self.dma.take() {
+            self.devices[index].dma_data(addr, value);
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::{fmt, ops::RangeInclusive};
+
+use crate::bus::{Bus, Byte, Word, open_bus::OpenBus};
+
+/// Size of a single entry in the page table.
+pub const PAGE_SIZE: usize = 0x100;
+const PAGE_COUNT: usize = 0x10000 / PAGE_SIZE;
+
+enum Backing {
+    Ram(Vec<Byte>),
+    Rom(Vec<Byte>),
+    Device(Box<dyn Bus>),
+}
+
+struct Region {
+    start: Word,
+    end: Word,
+    /// ### Mirror period
+    /// every address in the region is folded into `start..start + period`
+    period: usize,
+    /// bits actually driven by the region, the others come from open bus
+    driven: Byte,
+    backing: Backing,
+}
+
+impl Region {
+    fn contains(&self, addr: Word) -> bool {
+        (self.start..=self.end).contains(&addr)
+    }
+
+    fn offset(&self, addr: Word) -> usize {
+        (addr - self.start) as usize % self.period
+    }
+}
+
+#[derive(Clone)]
+enum Page {
+    Unmapped,
+    /// the whole page belongs to one region
+    Single(usize),
+    /// several regions share the page, they are searched in order
+    Split(Vec<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapError {
+    /// Two regions claim the same address
+    Overlap {
+        first: RangeInclusive<Word>,
+        second: RangeInclusive<Word>,
+    },
+    /// A region was given no backing bytes or a mirror period of zero
+    EmptyRegion(RangeInclusive<Word>),
+    /// The mirror period (or backing size) is bigger than the region itself
+    MirrorTooLarge {
+        range: RangeInclusive<Word>,
+        period: usize,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Overlap { first, second } => write!(
+                f,
+                "region ${:04X}-${:04X} overlaps ${:04X}-${:04X}",
+                second.start(),
+                second.end(),
+                first.start(),
+                first.end()
+            ),
+            MapError::EmptyRegion(range) => {
+                write!(
+                    f,
+                    "region ${:04X}-${:04X} is empty",
+                    range.start(),
+                    range.end()
+                )
+            }
+            MapError::MirrorTooLarge { range, period } => write!(
+                f,
+                "mirror period {period:#X} does not fit in region ${:04X}-${:04X}",
+                range.start(),
+                range.end()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+/// ### Memory map builder
+/// Describes which address ranges are backed by RAM, ROM or a device.
+/// Overlapping regions are reported by [`MemoryMapBuilder::build`].
+#[derive(Default)]
+pub struct MemoryMapBuilder {
+    regions: Vec<Region>,
+    open_bus: Option<OpenBus>,
+}
+
+impl MemoryMapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps RAM covering the whole `range`.
+    pub fn ram(self, range: RangeInclusive<Word>) -> Self {
+        let size = range_len(&range);
+        self.mirrored_ram(range, size)
+    }
+
+    /// Maps `size` bytes of RAM repeated across `range`.
+    pub fn mirrored_ram(self, range: RangeInclusive<Word>, size: usize) -> Self {
+        self.push(range, size, 0xFF, Backing::Ram(vec![0; size]))
+    }
+
+    /// Maps write-protected `data` repeated across `range`.
+    pub fn rom(self, range: RangeInclusive<Word>, data: Vec<Byte>) -> Self {
+        let size = data.len();
+        self.push(range, size, 0xFF, Backing::Rom(data))
+    }
+
+    /// Hands the whole `range` to `device`, which receives the CPU address untouched.
+    pub fn device(self, range: RangeInclusive<Word>, device: Box<dyn Bus>) -> Self {
+        let size = range_len(&range);
+        self.mirrored_device(range, size, device)
+    }
+
+    /// Hands `range` to `device`, folding addresses every `period` bytes
+    /// so the device only ever sees `start..start + period`.
+    pub fn mirrored_device(
+        self,
+        range: RangeInclusive<Word>,
+        period: usize,
+        device: Box<dyn Bus>,
+    ) -> Self {
+        self.push(range, period, 0xFF, Backing::Device(device))
+    }
+
+    /// Hands `range` to a device that only drives the bits in `mask`,
+    /// the remaining bits read back from open bus.
+    pub fn partial_device(
+        self,
+        range: RangeInclusive<Word>,
+        mask: Byte,
+        device: Box<dyn Bus>,
+    ) -> Self {
+        let size = range_len(&range);
+        self.push(range, size, mask, Backing::Device(device))
+    }
+
+    /// Makes unmapped reads return the last value seen on the bus instead of `0`.
+    pub fn open_bus(mut self, open_bus: OpenBus) -> Self {
+        self.open_bus = Some(open_bus);
+        self
+    }
+
+    fn push(
+        mut self,
+        range: RangeInclusive<Word>,
+        period: usize,
+        driven: Byte,
+        backing: Backing,
+    ) -> Self {
+        self.regions.push(Region {
+            start: *range.start(),
+            end: *range.end(),
+            period,
+            driven,
+            backing,
+        });
+        self
+    }
+
+    pub fn build(self) -> Result<MemoryMap, MapError> {
+        for region in &self.regions {
+            let range = region.start..=region.end;
+            if region.period == 0 || region.start > region.end {
+                return Err(MapError::EmptyRegion(range));
+            }
+            if region.period > range_len(&range) {
+                return Err(MapError::MirrorTooLarge {
+                    range,
+                    period: region.period,
+                });
+            }
+        }
+
+        let mut order: Vec<usize> = (0..self.regions.len()).collect();
+        order.sort_by_key(|&i| self.regions[i].start);
+        for pair in order.windows(2) {
+            let (first, second) = (&self.regions[pair[0]], &self.regions[pair[1]]);
+            if second.start <= first.end {
+                return Err(MapError::Overlap {
+                    first: first.start..=first.end,
+                    second: second.start..=second.end,
+                });
+            }
+        }
+
+        let mut pages = vec![Page::Unmapped; PAGE_COUNT];
+        for (index, region) in self.regions.iter().enumerate() {
+            let first_page = region.start as usize / PAGE_SIZE;
+            let last_page = region.end as usize / PAGE_SIZE;
+            for (page_index, page) in pages
+                .iter_mut()
+                .enumerate()
+                .take(last_page + 1)
+                .skip(first_page)
+            {
+                let page_start = page_index * PAGE_SIZE;
+                let page_end = page_start + PAGE_SIZE - 1;
+                let covers_page =
+                    region.start as usize <= page_start && region.end as usize >= page_end;
+                *page = match std::mem::replace(page, Page::Unmapped) {
+                    Page::Unmapped if covers_page => Page::Single(index),
+                    Page::Unmapped => Page::Split(vec![index]),
+                    Page::Single(other) => Page::Split(vec![other, index]),
+                    Page::Split(mut others) => {
+                        others.push(index);
+                        Page::Split(others)
+                    }
+                };
+            }
+        }
+
+        Ok(MemoryMap {
+            regions: self.regions,
+            pages,
+            open_bus: self.open_bus,
+        })
+    }
+}
+
+/// ### Composable bus
+/// A [`Bus`] assembled from RAM, ROM and device regions,
+/// resolved through a 256 entry page table.
+/// Reads from unmapped addresses return `0`, or the open bus value when one
+/// is configured, and writes to them are dropped.
+pub struct MemoryMap {
+    regions: Vec<Region>,
+    pages: Vec<Page>,
+    open_bus: Option<OpenBus>,
+}
+
+impl MemoryMap {
+    pub fn builder() -> MemoryMapBuilder {
+        MemoryMapBuilder::new()
+    }
+
+    fn region_index(&self, addr: Word) -> Option<usize> {
+        match &self.pages[addr as usize / PAGE_SIZE] {
+            Page::Unmapped => None,
+            Page::Single(index) => Some(*index),
+            Page::Split(indices) => indices
+                .iter()
+                .copied()
+                .find(|&i| self.regions[i].contains(addr)),
+        }
+    }
+
+    /// Returns `true` if some region answers at `addr`.
+    pub fn is_mapped(&self, addr: Word) -> bool {
+        self.region_index(addr).is_some()
+    }
+
+    pub fn open_bus(&self) -> Option<&OpenBus> {
+        self.open_bus.as_ref()
+    }
+
+    pub fn open_bus_mut(&mut self) -> Option<&mut OpenBus> {
+        self.open_bus.as_mut()
+    }
+
+    fn open_bus_value(&self) -> Byte {
+        self.open_bus.map_or(0, |open_bus| open_bus.value())
+    }
+
+    fn latch(&mut self, value: Byte) {
+        if let Some(ref mut open_bus) = self.open_bus {
+            open_bus.latch(value);
+        }
+    }
+}
+
+impl Bus for MemoryMap {
+    fn read(&mut self, addr: Word, read_only: bool) -> Byte {
+        let open = self.open_bus_value();
+        let value = match self.region_index(addr) {
+            None => open,
+            Some(index) => {
+                let region = &mut self.regions[index];
+                let offset = region.offset(addr);
+                let (data, driven) = match region.backing {
+                    Backing::Ram(ref data) | Backing::Rom(ref data) => {
+                        (data[offset], region.driven)
+                    }
+                    Backing::Device(ref mut device) => {
+                        let addr = region.start.wrapping_add(offset as Word);
+                        let driven = region.driven & device.driven_bits(addr);
+                        (device.read(addr, read_only), driven)
+                    }
+                };
+                (data & driven) | (open & !driven)
+            }
+        };
+        if !read_only {
+            self.latch(value);
+        }
+        value
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        self.latch(value);
+        let Some(index) = self.region_index(addr) else {
+            return;
+        };
+        let region = &mut self.regions[index];
+        let offset = region.offset(addr);
+        match region.backing {
+            Backing::Ram(ref mut data) => data[offset] = value,
+            Backing::Rom(_) => {}
+            Backing::Device(ref mut device) => {
+                device.write(region.start.wrapping_add(offset as Word), value)
+            }
+        }
+    }
+}
+
+fn range_len(range: &RangeInclusive<Word>) -> usize {
+    (*range.end() as usize + 1).saturating_sub(*range.start() as usize)
+}
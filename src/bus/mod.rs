@@ -1,9 +1,53 @@
+use std::{cell::RefCell, rc::Rc};
+
+pub mod clock;
+pub mod memory_map;
+pub mod open_bus;
 pub mod simple_bus;
 
 pub type Byte = u8;
 pub type Word = u16;
 
+/// ### Bus
+/// Anything the CPU can be connected to.
+///
+/// ### Contract for `read_only`
+/// When `read_only` is `true` the read comes from a tool (debugger, disassembler,
+/// memory viewer) and not from the CPU. Implementors must return the value the CPU
+/// would see without changing any state: latches are not cleared, buffers are not
+/// refilled, counters are not advanced. Devices that cannot answer without a side
+/// effect should return their best guess instead.
 pub trait Bus {
     fn read(&mut self, addr: Word, read_only: bool) -> Byte;
     fn write(&mut self, addr: Word, value: Byte);
+
+    /// Side-effect-free read, see the `read_only` contract above.
+    fn peek(&mut self, addr: Word) -> Byte {
+        self.read(addr, true)
+    }
+
+    /// Bits the device drives when `addr` is read. The bus it is connected to
+    /// fills the others with open bus, whatever [`Bus::read`] returned for them.
+    fn driven_bits(&self, _addr: Word) -> Byte {
+        0xFF
+    }
+}
+
+/// Lets a device be shared between the bus and the scheduler.
+impl<T: Bus + ?Sized> Bus for Rc<RefCell<T>> {
+    fn read(&mut self, addr: Word, read_only: bool) -> Byte {
+        self.borrow_mut().read(addr, read_only)
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        self.borrow_mut().write(addr, value);
+    }
+
+    fn peek(&mut self, addr: Word) -> Byte {
+        self.borrow_mut().peek(addr)
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        self.borrow().driven_bits(addr)
+    }
 }
@@ -0,0 +1,68 @@
+use crate::bus::Byte;
+
+/// ### Open bus
+/// Models the capacitance of an undriven data bus: reading an address nobody
+/// answers returns whatever was last transferred.
+///
+/// With decay enabled every bit remembers when it was last driven and reads
+/// back as `0` once it has not been refreshed for `decay` cycles, like the
+/// NES PPU I/O latch. Time only moves through [`OpenBus::tick`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpenBus {
+    value: Byte,
+    decay: Option<u64>,
+    refreshed: [u64; 8],
+    now: u64,
+}
+
+impl OpenBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open bus whose bits fade to `0` after `cycles` cycles without being driven.
+    pub fn with_decay(cycles: u64) -> Self {
+        Self {
+            decay: Some(cycles),
+            ..Self::default()
+        }
+    }
+
+    /// Records a full byte transfer.
+    pub fn latch(&mut self, value: Byte) {
+        self.drive(0xFF, value);
+    }
+
+    /// Records a transfer where only the bits in `mask` were driven.
+    pub fn drive(&mut self, mask: Byte, value: Byte) {
+        self.value = (self.value & !mask) | (value & mask);
+        for (bit, refreshed) in self.refreshed.iter_mut().enumerate() {
+            if mask & (1 << bit) != 0 {
+                *refreshed = self.now;
+            }
+        }
+    }
+
+    /// The value currently floating on the bus.
+    pub fn value(&self) -> Byte {
+        let Some(decay) = self.decay else {
+            return self.value;
+        };
+        let alive = self
+            .refreshed
+            .iter()
+            .enumerate()
+            .filter(|(_, refreshed)| self.now - **refreshed < decay)
+            .fold(0, |mask, (bit, _)| mask | (1 << bit));
+        self.value & alive
+    }
+
+    /// Combines the bits a device drives (`mask`) with open bus for the rest.
+    pub fn merge(&self, mask: Byte, driven: Byte) -> Byte {
+        (driven & mask) | (self.value() & !mask)
+    }
+
+    pub fn tick(&mut self, cycles: u64) {
+        self.now += cycles;
+    }
+}
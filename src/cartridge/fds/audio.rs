@@ -0,0 +1,244 @@
+use crate::{audio::SampleSource, bus::Byte};
+
+const WAVE_SIZE: usize = 64;
+const MOD_TABLE_SIZE: usize = 64;
+/// Gain beyond which the volume envelope no longer gets louder
+const MAX_OUTPUT_GAIN: Byte = 32;
+/// Full volume is about 2.4 times a full volume APU pulse.
+const LEVEL_STEP: f32 = 2.4 * 0.1494 / (63.0 * 32.0);
+/// Modulation table steps, `None` resets the counter
+const MOD_STEPS: [Option<i8>; 8] = [
+    Some(0),
+    Some(1),
+    Some(2),
+    Some(4),
+    None,
+    Some(-4),
+    Some(-2),
+    Some(-1),
+];
+/// Master volume as a fraction of 30: 2/2, 2/3, 2/4 and 2/5
+const MASTER_VOLUME: [u32; 4] = [30, 20, 15, 12];
+
+/// Envelope shared by the volume and modulation units.
+#[derive(Debug, Clone, Copy, Default)]
+struct Envelope {
+    speed: Byte,
+    gain: Byte,
+    increase: bool,
+    disabled: bool,
+    timer: u32,
+}
+
+impl Envelope {
+    fn write(&mut self, value: Byte) {
+        self.disabled = value & 0x80 != 0;
+        self.increase = value & 0x40 != 0;
+        self.speed = value & 0x3F;
+        if self.disabled {
+            self.gain = self.speed;
+        }
+        self.timer = 0;
+    }
+
+    fn clock(&mut self, master_speed: Byte) {
+        if self.disabled || master_speed == 0 {
+            return;
+        }
+        self.timer += 1;
+        if self.timer < 8 * (self.speed as u32 + 1) * master_speed as u32 {
+            return;
+        }
+        self.timer = 0;
+        if self.increase && self.gain < MAX_OUTPUT_GAIN {
+            self.gain += 1;
+        } else if !self.increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+    }
+}
+
+/// ### FDS wavetable channel
+/// A 64 step, 6 bit wavetable read at a 12 bit frequency, with a volume envelope
+/// and a modulation unit that bends the pitch by a table of 3 bit steps.
+///
+/// | Register | Content |
+/// |----------|---------|
+/// | `$4040-$407F` | Wavetable, writable while `$4089` bit 7 is set |
+/// | `$4080` | Volume envelope |
+/// | `$4082-$4083` | Frequency, `$4083` bit 7 halts the wave and bit 6 the envelopes |
+/// | `$4084` | Modulation envelope |
+/// | `$4085` | Modulation counter |
+/// | `$4086-$4087` | Modulation frequency, `$4087` bit 7 halts modulation |
+/// | `$4088` | Modulation table, two entries per write while halted |
+/// | `$4089` | Wavetable write enable, master volume |
+/// | `$408A` | Envelope speed multiplier |
+#[derive(Debug, Clone)]
+pub struct FdsAudio {
+    wave: [Byte; WAVE_SIZE],
+    wave_frequency: u16,
+    wave_halted: bool,
+    wave_writable: bool,
+    wave_accumulator: u32,
+    wave_position: usize,
+    envelopes_halted: bool,
+    master_volume: Byte,
+    master_speed: Byte,
+    volume: Envelope,
+    modulation: Envelope,
+    mod_table: [Byte; MOD_TABLE_SIZE],
+    mod_frequency: u16,
+    mod_halted: bool,
+    mod_accumulator: u32,
+    mod_position: usize,
+    mod_counter: i8,
+    output: Byte,
+}
+
+impl Default for FdsAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FdsAudio {
+    pub fn new() -> Self {
+        Self {
+            wave: [0; WAVE_SIZE],
+            wave_frequency: 0,
+            wave_halted: true,
+            wave_writable: false,
+            wave_accumulator: 0,
+            wave_position: 0,
+            envelopes_halted: false,
+            master_volume: 0,
+            master_speed: 0xE8,
+            volume: Envelope::default(),
+            modulation: Envelope::default(),
+            mod_table: [0; MOD_TABLE_SIZE],
+            mod_frequency: 0,
+            mod_halted: true,
+            mod_accumulator: 0,
+            mod_position: 0,
+            mod_counter: 0,
+            output: 0,
+        }
+    }
+
+    /// Reads `$4040-$4092`.
+    pub fn read(&self, addr: u16) -> Option<Byte> {
+        match addr {
+            0x4040..=0x407F => Some(self.wave[addr as usize - 0x4040]),
+            0x4090 => Some(self.volume.gain),
+            0x4092 => Some(self.modulation.gain),
+            _ => None,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: Byte) {
+        match addr {
+            0x4040..=0x407F if self.wave_writable => {
+                self.wave[addr as usize - 0x4040] = value & 0x3F
+            }
+            0x4080 => self.volume.write(value),
+            0x4082 => self.wave_frequency = (self.wave_frequency & 0x0F00) | value as u16,
+            0x4083 => {
+                self.wave_frequency = (self.wave_frequency & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.wave_halted = value & 0x80 != 0;
+                self.envelopes_halted = value & 0x40 != 0;
+                if self.wave_halted {
+                    self.wave_accumulator = 0;
+                    self.wave_position = 0;
+                }
+            }
+            0x4084 => self.modulation.write(value),
+            0x4085 => self.mod_counter = ((value << 1) as i8) >> 1,
+            0x4086 => self.mod_frequency = (self.mod_frequency & 0x0F00) | value as u16,
+            0x4087 => {
+                self.mod_frequency = (self.mod_frequency & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.mod_halted = value & 0x80 != 0;
+                if self.mod_halted {
+                    self.mod_accumulator = 0;
+                }
+            }
+            0x4088 if self.mod_halted => {
+                self.mod_table[self.mod_position] = value & 0x07;
+                self.mod_table[(self.mod_position + 1) % MOD_TABLE_SIZE] = value & 0x07;
+                self.mod_position = (self.mod_position + 2) % MOD_TABLE_SIZE;
+            }
+            0x4089 => {
+                self.wave_writable = value & 0x80 != 0;
+                self.master_volume = value & 0x03;
+            }
+            0x408A => self.master_speed = value,
+            _ => {}
+        }
+    }
+
+    /// Advances the channel by one CPU cycle.
+    pub fn clock(&mut self) {
+        if !self.envelopes_halted && !self.wave_halted {
+            self.volume.clock(self.master_speed);
+            self.modulation.clock(self.master_speed);
+        }
+        if !self.mod_halted && self.mod_frequency != 0 {
+            self.mod_accumulator += self.mod_frequency as u32;
+            if self.mod_accumulator >= 0x10000 {
+                self.mod_accumulator -= 0x10000;
+                self.step_modulation();
+            }
+        }
+        if !self.wave_halted && !self.wave_writable {
+            self.wave_accumulator += self.pitch();
+            while self.wave_accumulator >= 0x10000 {
+                self.wave_accumulator -= 0x10000;
+                self.wave_position = (self.wave_position + 1) % WAVE_SIZE;
+            }
+            self.output = self.wave[self.wave_position];
+        }
+    }
+
+    fn step_modulation(&mut self) {
+        let step = MOD_STEPS[self.mod_table[self.mod_position] as usize];
+        self.mod_counter = match step {
+            // 7 bit signed counter
+            Some(step) => (self.mod_counter + step).wrapping_shl(1) >> 1,
+            None => 0,
+        };
+        self.mod_position = (self.mod_position + 1) % MOD_TABLE_SIZE;
+    }
+
+    /// Wave frequency bent by the modulation unit.
+    fn pitch(&self) -> u32 {
+        let frequency = self.wave_frequency as i32;
+        if self.mod_halted {
+            return frequency as u32;
+        }
+        let mut temp = self.mod_counter as i32 * self.modulation.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if self.mod_counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+        let mut bend = frequency * temp;
+        let remainder = bend & 0x3F;
+        bend >>= 6;
+        if remainder >= 32 {
+            bend += 1;
+        }
+        (frequency + bend).max(0) as u32
+    }
+}
+
+impl SampleSource for FdsAudio {
+    fn sample(&self) -> f32 {
+        let gain = self.volume.gain.min(MAX_OUTPUT_GAIN) as u32;
+        let level = self.output as u32 * gain * MASTER_VOLUME[self.master_volume as usize] / 30;
+        level as f32 * LEVEL_STEP
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{bus::Byte, cartridge::CartridgeError};
+
+/// Bytes per disk side in an `.fds` image
+pub const SIDE_SIZE: usize = 65500;
+const FWNES_MAGIC: [Byte; 4] = *b"FDS\x1A";
+const FWNES_HEADER_SIZE: usize = 16;
+const DISK_INFO: &[Byte] = b"\x01*NINTENDO-HVC*";
+/// Zero bytes before the first block, about 28300 bits of gap
+const LEADING_GAP: usize = 28300 / 8;
+/// Zero bytes between blocks, about 976 bits of gap
+const BLOCK_GAP: usize = 976 / 8;
+/// Marks the end of a gap, right before each block
+const BLOCK_START: Byte = 0x80;
+const CRC_SIZE: usize = 2;
+const IPS_MAGIC: &[Byte] = b"PATCH";
+const IPS_EOF: &[Byte] = b"EOF";
+/// IPS records can't be longer than a 16 bit size
+const IPS_MAX_RECORD: usize = 0xFFFF;
+
+/// ### FDS disk image
+/// The sides of an `.fds` file, with or without the 16 byte fwNES header.
+/// Images store the blocks of each side back to back without the gaps and CRCs
+/// the drive sees, [`DiskImage::raw_side`] puts them back.
+///
+/// The image as loaded is kept so changes can be saved as an IPS patch
+/// next to the file instead of modifying it, see [`DiskImage::save_diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskImage {
+    pub sides: Vec<Vec<Byte>>,
+    original: Vec<Vec<Byte>>,
+    fwnes_header: bool,
+}
+
+impl DiskImage {
+    pub fn from_bytes(bytes: &[Byte]) -> Result<Self, CartridgeError> {
+        let (fwnes_header, data) = if bytes.starts_with(&FWNES_MAGIC) {
+            if bytes.len() < FWNES_HEADER_SIZE {
+                return Err(CartridgeError::Truncated {
+                    expected: FWNES_HEADER_SIZE,
+                    actual: bytes.len(),
+                });
+            }
+            (true, &bytes[FWNES_HEADER_SIZE..])
+        } else {
+            (false, bytes)
+        };
+        if !data.starts_with(DISK_INFO) {
+            return Err(CartridgeError::BadMagic);
+        }
+        // fwNES side counts are unreliable, the data length decides
+        let count = data.len().div_ceil(SIDE_SIZE);
+        if data.len() % SIDE_SIZE != 0 {
+            let offset = if fwnes_header { FWNES_HEADER_SIZE } else { 0 };
+            return Err(CartridgeError::Truncated {
+                expected: offset + count * SIDE_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        let sides: Vec<Vec<Byte>> = data.chunks(SIDE_SIZE).map(<[Byte]>::to_vec).collect();
+        Ok(Self {
+            original: sides.clone(),
+            sides,
+            fwnes_header,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CartridgeError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// The image in the format it was loaded from, with the current contents.
+    pub fn to_bytes(&self) -> Vec<Byte> {
+        Self::serialize(&self.sides, self.fwnes_header)
+    }
+
+    fn serialize(sides: &[Vec<Byte>], fwnes_header: bool) -> Vec<Byte> {
+        let mut bytes = Vec::new();
+        if fwnes_header {
+            bytes.extend(FWNES_MAGIC);
+            bytes.push(sides.len() as Byte);
+            bytes.resize(FWNES_HEADER_SIZE, 0);
+        }
+        for side in sides {
+            bytes.extend(side);
+        }
+        bytes
+    }
+
+    /// Whether any side differs from the image as loaded.
+    pub fn is_modified(&self) -> bool {
+        self.sides != self.original
+    }
+
+    /// The bitstream of side `side` as the drive reads it: a leading gap,
+    /// then every block preceded by a gap end mark and followed by its CRC.
+    /// The unused space of the side is kept as trailing gap so files can be added.
+    pub fn raw_side(&self, side: usize) -> Vec<Byte> {
+        let data = &self.sides[side];
+        let mut raw = vec![0; LEADING_GAP];
+        let mut position = 0;
+        let mut file_size = 0;
+        while let Some(size) = block_size(data, position, file_size) {
+            let block = &data[position..position + size];
+            if block[0] == 3 {
+                file_size = u16::from_le_bytes([block[13], block[14]]) as usize;
+            }
+            raw.push(BLOCK_START);
+            raw.extend(block);
+            raw.extend(crc(block).to_le_bytes());
+            raw.resize(raw.len() + BLOCK_GAP, 0);
+            position += size;
+        }
+        raw.resize(raw.len() + SIDE_SIZE - position, 0);
+        raw
+    }
+
+    /// Stores side `side` back from a bitstream written by the drive.
+    pub fn store_raw_side(&mut self, side: usize, raw: &[Byte]) {
+        let mut data = Vec::with_capacity(SIDE_SIZE);
+        let mut position = 0;
+        let mut file_size = 0;
+        while let Some(start) = raw[position..].iter().position(|&b| b != 0) {
+            position += start;
+            if raw[position] != BLOCK_START {
+                break;
+            }
+            let Some(size) = block_size(raw, position + 1, file_size) else {
+                break;
+            };
+            let block = &raw[position + 1..position + 1 + size];
+            if block[0] == 3 {
+                file_size = u16::from_le_bytes([block[13], block[14]]) as usize;
+            }
+            data.extend(block);
+            position += 1 + size + CRC_SIZE;
+            if position >= raw.len() {
+                break;
+            }
+        }
+        data.resize(SIDE_SIZE, 0);
+        self.sides[side] = data;
+    }
+
+    /// Changes since loading, as an IPS patch over the loaded file.
+    pub fn diff(&self) -> Vec<Byte> {
+        let old = Self::serialize(&self.original, self.fwnes_header);
+        let new = self.to_bytes();
+        let mut patch = IPS_MAGIC.to_vec();
+        let mut offset = 0;
+        while offset < new.len() {
+            if old.get(offset) == Some(&new[offset]) {
+                offset += 1;
+                continue;
+            }
+            let mut end = offset;
+            while end < new.len()
+                && end - offset < IPS_MAX_RECORD
+                && old.get(end) != Some(&new[end])
+            {
+                end += 1;
+            }
+            patch.extend(&(offset as u32).to_be_bytes()[1..]);
+            patch.extend(((end - offset) as u16).to_be_bytes());
+            patch.extend(&new[offset..end]);
+            offset = end;
+        }
+        patch.extend(IPS_EOF);
+        patch
+    }
+
+    /// Applies an IPS patch over the image as loaded.
+    pub fn apply_diff(&mut self, patch: &[Byte]) -> Result<(), CartridgeError> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed IPS patch");
+        let mut bytes = Self::serialize(&self.original, self.fwnes_header);
+        let mut rest = patch.strip_prefix(IPS_MAGIC).ok_or_else(invalid)?;
+        while rest != IPS_EOF {
+            let [a, b, c, d, e, data @ ..] = rest else {
+                return Err(invalid().into());
+            };
+            let offset = u32::from_be_bytes([0, *a, *b, *c]) as usize;
+            let size = u16::from_be_bytes([*d, *e]) as usize;
+            // RLE records, which this module never writes
+            let (record, next) = if size == 0 {
+                let [f, g, value, next @ ..] = data else {
+                    return Err(invalid().into());
+                };
+                (vec![*value; u16::from_be_bytes([*f, *g]) as usize], next)
+            } else if data.len() >= size {
+                (data[..size].to_vec(), &data[size..])
+            } else {
+                return Err(invalid().into());
+            };
+            if offset + record.len() > bytes.len() {
+                return Err(invalid().into());
+            }
+            bytes[offset..offset + record.len()].copy_from_slice(&record);
+            rest = next;
+        }
+        let start = if self.fwnes_header {
+            FWNES_HEADER_SIZE
+        } else {
+            0
+        };
+        self.sides = bytes[start..]
+            .chunks(SIDE_SIZE)
+            .map(<[Byte]>::to_vec)
+            .collect();
+        Ok(())
+    }
+
+    /// Where the diff of the image at `path` is kept: the same name with an `.ips` extension.
+    pub fn diff_path(path: impl AsRef<Path>) -> PathBuf {
+        path.as_ref().with_extension("ips")
+    }
+
+    pub fn save_diff(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.diff())
+    }
+
+    /// Applies the diff at `path`, a missing file meaning there are no changes.
+    pub fn load_diff(&mut self, path: impl AsRef<Path>) -> Result<(), CartridgeError> {
+        match fs::read(path) {
+            Ok(patch) => self.apply_diff(&patch),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Size of the block starting at `position`, `None` past the last one.
+/// File data blocks take their size from the preceding file header block.
+fn block_size(data: &[Byte], position: usize, file_size: usize) -> Option<usize> {
+    let size = match data.get(position)? {
+        1 => 56,
+        2 => 2,
+        3 => 16,
+        4 => 1 + file_size,
+        _ => return None,
+    };
+    (position + size <= data.len()).then_some(size)
+}
+
+/// CRC-16 the drive appends to blocks, including the gap end mark.
+pub(crate) fn crc(block: &[Byte]) -> u16 {
+    let mut crc = 0x8000;
+    for &byte in std::iter::once(&BLOCK_START).chain(block).chain(&[0, 0]) {
+        crc = update_crc(crc, byte);
+    }
+    crc
+}
+
+pub(crate) fn update_crc(mut crc: u16, value: Byte) -> u16 {
+    for bit in 0..8 {
+        let carry = crc & 0x01 != 0;
+        crc >>= 1;
+        if carry {
+            crc ^= 0x8408;
+        }
+        if value & (1 << bit) != 0 {
+            crc ^= 0x8000;
+        }
+    }
+    crc
+}
@@ -0,0 +1,416 @@
+use crate::{
+    audio::SampleSource,
+    bus::{Bus, Byte, Word, clock::Lines},
+    cartridge::{
+        CartridgeError, Mirroring,
+        fds::{
+            audio::FdsAudio,
+            disk::{DiskImage, update_crc},
+        },
+        mapper::Mapper,
+    },
+};
+
+pub mod audio;
+pub mod disk;
+
+pub const BIOS_SIZE: usize = 8 * 1024;
+const PRG_RAM_SIZE: usize = 32 * 1024;
+const CHR_RAM_SIZE: usize = 8 * 1024;
+/// CPU cycles per byte at the drive's 96.4 kbit/s
+const BYTE_CYCLES: u32 = 150;
+/// CPU cycles the head takes to get back to the start of the disk
+const REWIND_CYCLES: u32 = 50_000;
+/// How long [`Fds::swap_side`] leaves the drive empty, about half a second
+const SWAP_CYCLES: u32 = 900_000;
+const CRC_INIT: u16 = 0x8000;
+
+/// ### Famicom Disk System
+/// The RAM adapter: 32 KiB of PRG-RAM at `$6000-$DFFF`, the BIOS at
+/// `$E000-$FFFF`, 8 KiB of CHR-RAM, the disk drive and the wavetable channel.
+///
+/// | Register | Content |
+/// |----------|---------|
+/// | `$4020-$4021` | Timer IRQ reload value |
+/// | `$4022` | Timer IRQ control: repeat, enable |
+/// | `$4023` | Enables the disk and sound registers |
+/// | `$4024` | Byte to write to the disk |
+/// | `$4025` | Drive control: motor, transfer reset, read mode, mirroring, CRC, transfer, IRQ |
+/// | `$4030` | Status: timer IRQ, byte transferred, end of disk (read) |
+/// | `$4031` | Byte read from the disk (read) |
+/// | `$4032` | Drive status: no disk, not ready, write protected (read) |
+/// | `$4040-$408A` | Wavetable channel, see [`FdsAudio`] |
+///
+/// The drive moves one byte every 150 CPU cycles over the bitstream of the
+/// inserted side, from which writes are folded back into the [`DiskImage`].
+/// The board must be attached to the CPU to run the drive, the timer and the audio.
+pub struct Fds {
+    bios: Vec<Byte>,
+    prg_ram: Vec<Byte>,
+    chr_ram: Vec<Byte>,
+    disk: DiskImage,
+    raw_sides: Vec<Vec<Byte>>,
+    dirty_sides: Vec<bool>,
+    side: Option<usize>,
+    pending_side: Option<(usize, u32)>,
+    audio: FdsAudio,
+    disk_registers: bool,
+    sound_registers: bool,
+    timer_reload: u16,
+    timer_counter: u16,
+    timer_repeat: bool,
+    timer_enabled: bool,
+    timer_irq: bool,
+    motor_on: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    mirroring: Mirroring,
+    crc_control: bool,
+    transfer_enabled: bool,
+    byte_irq_enabled: bool,
+    disk_irq: bool,
+    transfer_complete: bool,
+    read_data: Byte,
+    write_data: Byte,
+    position: usize,
+    delay: u32,
+    end_of_head: bool,
+    scanning: bool,
+    gap_ended: bool,
+    crc: u16,
+    previous_crc_control: bool,
+}
+
+impl Fds {
+    /// Powers on with side A of `disk` inserted.
+    pub fn new(bios: Vec<Byte>, disk: DiskImage) -> Result<Self, CartridgeError> {
+        if bios.len() != BIOS_SIZE {
+            return Err(CartridgeError::BadBios(bios.len()));
+        }
+        let raw_sides: Vec<Vec<Byte>> = (0..disk.sides.len())
+            .map(|side| disk.raw_side(side))
+            .collect();
+        Ok(Self {
+            bios,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr_ram: vec![0; CHR_RAM_SIZE],
+            dirty_sides: vec![false; raw_sides.len()],
+            raw_sides,
+            disk,
+            side: Some(0),
+            pending_side: None,
+            audio: FdsAudio::new(),
+            disk_registers: false,
+            sound_registers: false,
+            timer_reload: 0,
+            timer_counter: 0,
+            timer_repeat: false,
+            timer_enabled: false,
+            timer_irq: false,
+            motor_on: false,
+            reset_transfer: false,
+            read_mode: true,
+            mirroring: Mirroring::Vertical,
+            crc_control: false,
+            transfer_enabled: false,
+            byte_irq_enabled: false,
+            disk_irq: false,
+            transfer_complete: false,
+            read_data: 0,
+            write_data: 0,
+            position: 0,
+            delay: 0,
+            end_of_head: true,
+            scanning: false,
+            gap_ended: false,
+            crc: CRC_INIT,
+            previous_crc_control: false,
+        })
+    }
+
+    pub fn side_count(&self) -> usize {
+        self.raw_sides.len()
+    }
+
+    /// The side in the drive, `None` when it is empty.
+    pub fn inserted_side(&self) -> Option<usize> {
+        self.side
+    }
+
+    /// Puts side `side` in the drive, replacing whatever was in it.
+    ///
+    /// # Panics
+    /// If `side` is not below [`Fds::side_count`].
+    pub fn insert_disk(&mut self, side: usize) {
+        assert!(side < self.side_count(), "disk has no side {side}");
+        self.side = Some(side);
+        self.pending_side = None;
+        self.rewind_head();
+    }
+
+    pub fn eject_disk(&mut self) {
+        self.side = None;
+        self.pending_side = None;
+        self.rewind_head();
+    }
+
+    /// Sides differ in length, so a new side is always read from its start
+    fn rewind_head(&mut self) {
+        self.end_of_head = true;
+        self.scanning = false;
+    }
+
+    /// Ejects the disk and inserts `side` half a second later, long enough
+    /// for games waiting for the disk to be flipped to notice.
+    ///
+    /// # Panics
+    /// If `side` is not below [`Fds::side_count`].
+    pub fn swap_side(&mut self, side: usize) {
+        assert!(side < self.side_count(), "disk has no side {side}");
+        self.eject_disk();
+        self.pending_side = Some((side, SWAP_CYCLES));
+    }
+
+    /// The disk with everything the game wrote so far.
+    pub fn disk(&mut self) -> &DiskImage {
+        for (side, dirty) in self.dirty_sides.iter_mut().enumerate() {
+            if *dirty {
+                self.disk.store_raw_side(side, &self.raw_sides[side]);
+                *dirty = false;
+            }
+        }
+        &self.disk
+    }
+
+    pub fn audio(&self) -> &FdsAudio {
+        &self.audio
+    }
+
+    fn write_register(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x4023 => {
+                self.disk_registers = value & 0x01 != 0;
+                self.sound_registers = value & 0x02 != 0;
+                if !self.disk_registers {
+                    self.timer_enabled = false;
+                    self.timer_irq = false;
+                    self.disk_irq = false;
+                }
+            }
+            0x4020..=0x4026 if !self.disk_registers => {}
+            0x4020 => self.timer_reload = (self.timer_reload & 0xFF00) | value as u16,
+            0x4021 => self.timer_reload = (self.timer_reload & 0x00FF) | (value as u16) << 8,
+            0x4022 => {
+                self.timer_repeat = value & 0x01 != 0;
+                self.timer_enabled = value & 0x02 != 0;
+                if self.timer_enabled {
+                    self.timer_counter = self.timer_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4024 => {
+                self.write_data = value;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            0x4025 => {
+                self.motor_on = value & 0x01 != 0;
+                self.reset_transfer = value & 0x02 != 0;
+                self.read_mode = value & 0x04 != 0;
+                self.mirroring = if value & 0x08 != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+                self.crc_control = value & 0x10 != 0;
+                self.transfer_enabled = value & 0x40 != 0;
+                self.byte_irq_enabled = value & 0x80 != 0;
+                self.disk_irq = false;
+            }
+            0x4040..=0x408A if self.sound_registers => self.audio.write(addr, value),
+            _ => {}
+        }
+    }
+
+    fn read_register(&mut self, addr: Word, read_only: bool) -> Byte {
+        match addr {
+            0x4030 if self.disk_registers => {
+                let status = self.timer_irq as Byte
+                    | (self.transfer_complete as Byte) << 1
+                    | (self.end_of_head as Byte) << 6;
+                if !read_only {
+                    self.timer_irq = false;
+                    self.disk_irq = false;
+                    self.transfer_complete = false;
+                }
+                status
+            }
+            0x4031 if self.disk_registers => {
+                if !read_only {
+                    self.transfer_complete = false;
+                    self.disk_irq = false;
+                }
+                self.read_data
+            }
+            0x4032 if self.disk_registers => {
+                let empty = self.side.is_none();
+                empty as Byte | ((empty || !self.scanning) as Byte) << 1 | (empty as Byte) << 2
+            }
+            // Battery good on the expansion port
+            0x4033 if self.disk_registers => 0x80,
+            0x4040..=0x409F if self.sound_registers => self.audio.read(addr).unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if !self.timer_enabled {
+            return;
+        }
+        if self.timer_counter == 0 {
+            self.timer_irq = true;
+            self.timer_counter = self.timer_reload;
+            if !self.timer_repeat {
+                self.timer_enabled = false;
+            }
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    /// Moves the head by one byte every [`BYTE_CYCLES`], rewinding when the motor
+    /// starts again after reaching the end of the disk.
+    fn clock_drive(&mut self) {
+        let Some(side) = self.side.filter(|_| self.motor_on) else {
+            self.end_of_head = true;
+            self.scanning = false;
+            return;
+        };
+        if self.reset_transfer && !self.scanning {
+            return;
+        }
+        if self.end_of_head {
+            self.delay = REWIND_CYCLES;
+            self.end_of_head = false;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+        self.scanning = true;
+        let raw = &mut self.raw_sides[side];
+        if self.read_mode {
+            let data = raw[self.position];
+            let mut irq = self.byte_irq_enabled;
+            if !self.transfer_enabled {
+                self.gap_ended = false;
+            } else if data != 0 && !self.gap_ended {
+                // The gap end mark itself is swallowed
+                self.gap_ended = true;
+                irq = false;
+            }
+            if self.gap_ended {
+                self.transfer_complete = true;
+                self.read_data = data;
+                self.disk_irq |= irq;
+            }
+        } else {
+            let mut data = 0;
+            if !self.crc_control {
+                self.transfer_complete = true;
+                self.disk_irq |= self.byte_irq_enabled;
+                data = self.write_data;
+            }
+            if !self.transfer_enabled {
+                data = 0;
+                self.crc = CRC_INIT;
+            } else if !self.crc_control {
+                self.crc = update_crc(self.crc, data);
+            } else {
+                if !self.previous_crc_control {
+                    self.crc = update_crc(update_crc(self.crc, 0), 0);
+                }
+                data = self.crc as Byte;
+                self.crc >>= 8;
+            }
+            raw[self.position] = data;
+            self.dirty_sides[side] = true;
+            self.gap_ended = false;
+        }
+        self.previous_crc_control = self.crc_control;
+        self.position += 1;
+        if self.position >= self.raw_sides[side].len() {
+            self.motor_on = false;
+        } else {
+            self.delay = BYTE_CYCLES - 1;
+        }
+    }
+}
+
+impl Bus for Fds {
+    fn read(&mut self, addr: Word, read_only: bool) -> Byte {
+        match addr {
+            0x6000..=0xDFFF => self.prg_ram[addr as usize - 0x6000],
+            0xE000..=0xFFFF => self.bios[addr as usize - 0xE000],
+            _ => self.read_register(addr, read_only),
+        }
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x6000..=0xDFFF => self.prg_ram[addr as usize - 0x6000] = value,
+            0xE000..=0xFFFF => {}
+            _ => self.write_register(addr, value),
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        match addr {
+            0x6000..=0xFFFF => 0xFF,
+            0x4030 | 0x4031 | 0x4033 if self.disk_registers => 0xFF,
+            0x4032 if self.disk_registers => 0x07,
+            // the wavetable and the gains are 6 bits wide
+            0x4040..=0x407F | 0x4090 | 0x4092 if self.sound_registers => 0x3F,
+            _ => 0x00,
+        }
+    }
+}
+
+impl Mapper for Fds {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr_ram[addr as usize & 0x1FFF]
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr_ram[addr as usize & 0x1FFF] = value;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn clock(&mut self, lines: &mut Lines) {
+        if let Some((side, cycles)) = self.pending_side {
+            self.pending_side = if cycles > 1 {
+                Some((side, cycles - 1))
+            } else {
+                self.insert_disk(side);
+                None
+            };
+        }
+        self.clock_timer();
+        self.clock_drive();
+        self.audio.clock();
+        if self.timer_irq || self.disk_irq {
+            lines.assert_irq();
+        }
+    }
+
+    fn sample_source(&self) -> Option<&dyn SampleSource> {
+        Some(&self.audio)
+    }
+}
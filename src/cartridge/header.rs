@@ -0,0 +1,194 @@
+use crate::{
+    bus::Byte,
+    cartridge::{CartridgeError, Mirroring},
+};
+
+pub const HEADER_SIZE: usize = 16;
+pub const TRAINER_SIZE: usize = 512;
+pub const PRG_BANK_SIZE: usize = 16 * 1024;
+pub const CHR_BANK_SIZE: usize = 8 * 1024;
+const MAGIC: [Byte; 4] = *b"NES\x1A";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Original iNES header
+    INes,
+    /// NES 2.0 header (flags 7 bits 2-3 == `0b10`)
+    Nes2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    Playchoice10,
+    /// NES 2.0 extended console type from byte 13
+    Extended(Byte),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    MultiRegion,
+    Dendy,
+}
+
+/// ### iNES / NES 2.0 header
+/// Sizes are in bytes, `0` means the memory is absent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub format: Format,
+    pub mapper: u16,
+    pub submapper: Byte,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub mirroring: Mirroring,
+    /// Flags 6 bit 0 as stored. Some boards give it their own meaning
+    /// when bit 3 is set, e.g. UNROM-512 uses it to tell one-screen from four-screen.
+    pub mirroring_bit: bool,
+    pub battery: bool,
+    pub trainer: bool,
+    pub console: ConsoleType,
+    pub region: Region,
+    /// NES 2.0 only, number of miscellaneous ROMs after CHR
+    pub misc_roms: Byte,
+}
+
+impl Header {
+    pub fn parse(bytes: &[Byte]) -> Result<Self, CartridgeError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(CartridgeError::Truncated {
+                expected: HEADER_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[0..4] != MAGIC {
+            return Err(CartridgeError::BadMagic);
+        }
+        let flags6 = bytes[6];
+        let flags7 = bytes[7];
+        let format = if flags7 & 0x0C == 0x08 {
+            Format::Nes2
+        } else {
+            Format::INes
+        };
+
+        let mirroring = if flags6 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let console = match flags7 & 0x03 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(bytes[13] & 0x0F),
+        };
+
+        let mut header = Header {
+            format,
+            mapper: (flags6 >> 4) as u16,
+            submapper: 0,
+            prg_rom_size: 0,
+            chr_rom_size: 0,
+            prg_ram_size: 0,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            mirroring,
+            mirroring_bit: flags6 & 0x01 != 0,
+            battery: flags6 & 0x02 != 0,
+            trainer: flags6 & 0x04 != 0,
+            console,
+            region: Region::Ntsc,
+            misc_roms: 0,
+        };
+
+        match format {
+            Format::Nes2 => header.parse_nes2(bytes)?,
+            Format::INes => header.parse_ines(bytes),
+        }
+        if header.prg_rom_size == 0 {
+            return Err(CartridgeError::NoPrgRom);
+        }
+        Ok(header)
+    }
+
+    fn parse_ines(&mut self, bytes: &[Byte]) {
+        // Old dumping tools wrote their name ("DiskDude!") over bytes 7-15,
+        // in that case the upper mapper nibble is garbage.
+        if bytes[12..16].iter().all(|&b| b == 0) {
+            self.mapper |= (bytes[7] & 0xF0) as u16;
+        } else {
+            self.console = ConsoleType::Nes;
+        }
+        self.prg_rom_size = bytes[4] as usize * PRG_BANK_SIZE;
+        self.chr_rom_size = bytes[5] as usize * CHR_BANK_SIZE;
+        // iNES can't tell RAM from NVRAM, a battery means it is saved
+        let prg_ram = bytes[8].max(1) as usize * 8 * 1024;
+        if self.battery {
+            self.prg_nvram_size = prg_ram;
+        } else {
+            self.prg_ram_size = prg_ram;
+        }
+        if self.chr_rom_size == 0 {
+            self.chr_ram_size = CHR_BANK_SIZE;
+        }
+        self.region = if bytes[9] & 0x01 != 0 {
+            Region::Pal
+        } else {
+            Region::Ntsc
+        };
+    }
+
+    fn parse_nes2(&mut self, bytes: &[Byte]) -> Result<(), CartridgeError> {
+        self.mapper |= (bytes[7] & 0xF0) as u16 | ((bytes[8] & 0x0F) as u16) << 8;
+        self.submapper = bytes[8] >> 4;
+        self.prg_rom_size = rom_size(bytes[4], bytes[9] & 0x0F, PRG_BANK_SIZE)?;
+        self.chr_rom_size = rom_size(bytes[5], bytes[9] >> 4, CHR_BANK_SIZE)?;
+        self.prg_ram_size = ram_size(bytes[10] & 0x0F);
+        self.prg_nvram_size = ram_size(bytes[10] >> 4);
+        self.chr_ram_size = ram_size(bytes[11] & 0x0F);
+        self.chr_nvram_size = ram_size(bytes[11] >> 4);
+        self.region = match bytes[12] & 0x03 {
+            0 => Region::Ntsc,
+            1 => Region::Pal,
+            2 => Region::MultiRegion,
+            _ => Region::Dendy,
+        };
+        self.misc_roms = bytes[14] & 0x03;
+        Ok(())
+    }
+
+    /// Whether the cartridge has CHR-RAM instead of (or on top of) CHR-ROM.
+    pub fn has_chr_ram(&self) -> bool {
+        self.chr_ram_size + self.chr_nvram_size != 0
+    }
+}
+
+/// NES 2.0 ROM sizes: a 12 bit bank count, or `2^E * (MM * 2 + 1)` bytes
+/// when the MSB nibble is `0xF`.
+fn rom_size(lsb: Byte, msb: Byte, bank_size: usize) -> Result<usize, CartridgeError> {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        return 1usize
+            .checked_shl(exponent)
+            .filter(|_| exponent < usize::BITS - 2)
+            .map(|size| size * multiplier)
+            .ok_or(CartridgeError::SizeOverflow);
+    }
+    Ok((((msb as usize) << 8) | lsb as usize) * bank_size)
+}
+
+/// NES 2.0 RAM sizes are `64 << shift` bytes, a shift of `0` means none.
+fn ram_size(shift: Byte) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
+}
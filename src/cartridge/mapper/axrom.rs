@@ -0,0 +1,70 @@
+use crate::{
+    bus::{Bus, Byte, Word},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, has_bus_conflicts},
+    },
+};
+
+/// ### AxROM (mapper 7)
+/// Writes to `$8000-$FFFF` select the 32 KiB PRG bank (bits 0-2) and which
+/// 1 KiB of CIRAM every nametable shows (bit 4). CHR is 8 KiB of RAM.
+/// ANROM has no bus conflicts, AMROM (NES 2.0 submapper 2) does.
+pub struct Axrom {
+    prg_rom: BankedMemory,
+    chr: BankedMemory,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+}
+
+impl Axrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self {
+            chr: BankedMemory::chr(&cartridge, 0x2000, 1),
+            mirroring: Mirroring::SingleScreenLower,
+            bus_conflicts: has_bus_conflicts(&cartridge, false),
+            prg_rom: BankedMemory::rom(cartridge.prg_rom, 0x8000, 1),
+        }
+    }
+}
+
+impl Bus for Axrom {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, mut value: Byte) {
+        if addr >= 0x8000 {
+            if self.bus_conflicts {
+                value &= self.prg_rom.read(addr as usize - 0x8000);
+            }
+            self.prg_rom.map(0, 1, (value & 0x07) as usize);
+            self.mirroring = if value & 0x10 != 0 {
+                Mirroring::SingleScreenUpper
+            } else {
+                Mirroring::SingleScreenLower
+            };
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        if addr >= 0x8000 { 0xFF } else { 0x00 }
+    }
+}
+
+impl Mapper for Axrom {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{bus::Byte, cartridge::Cartridge};
+
+/// ### Banked memory
+/// A ROM or RAM seen through `slots` windows of `window` bytes each.
+/// Bank numbers wrap around the size of the memory, the way unconnected
+/// high bank lines behave on a real board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BankedMemory {
+    pub data: Vec<Byte>,
+    pub writable: bool,
+    window: usize,
+    offsets: Vec<usize>,
+}
+
+impl BankedMemory {
+    pub fn rom(data: Vec<Byte>, window: usize, slots: usize) -> Self {
+        let mut memory = Self {
+            data,
+            writable: false,
+            window,
+            offsets: vec![0; slots],
+        };
+        memory.map(0, slots, 0);
+        memory
+    }
+
+    pub fn ram(size: usize, window: usize, slots: usize) -> Self {
+        Self {
+            writable: true,
+            ..Self::rom(vec![0; size], window, slots)
+        }
+    }
+
+    /// CHR-ROM of the cartridge, or CHR-RAM when it has none.
+    pub fn chr(cartridge: &Cartridge, window: usize, slots: usize) -> Self {
+        if cartridge.chr_rom.is_empty() {
+            let size = cartridge.header.chr_ram_size + cartridge.header.chr_nvram_size;
+            Self::ram(size.max(8 * 1024), window, slots)
+        } else {
+            Self::rom(cartridge.chr_rom.clone(), window, slots)
+        }
+    }
+
+    /// Number of banks of `count` windows.
+    pub fn bank_count(&self, count: usize) -> usize {
+        self.data.len().div_ceil(self.window * count).max(1)
+    }
+
+    /// Points `count` slots starting at `slot` to `bank`, counted in units of `count` windows.
+    pub fn map(&mut self, slot: usize, count: usize, bank: usize) {
+        if self.data.is_empty() {
+            return;
+        }
+        for i in 0..count {
+            self.offsets[slot + i] = ((bank * count + i) * self.window) % self.data.len();
+        }
+    }
+
+    fn index(&self, addr: usize) -> usize {
+        let slot = (addr / self.window) % self.offsets.len();
+        (self.offsets[slot] + addr % self.window) % self.data.len()
+    }
+
+    /// Reads `addr`, relative to the start of the first slot.
+    pub fn read(&self, addr: usize) -> Byte {
+        if self.data.is_empty() {
+            return 0;
+        }
+        self.data[self.index(addr)]
+    }
+
+    /// Writes `addr`, ignored unless the memory is RAM.
+    pub fn write(&mut self, addr: usize, value: Byte) {
+        if self.writable && !self.data.is_empty() {
+            let index = self.index(addr);
+            self.data[index] = value;
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{
+    bus::{Bus, Byte, Word},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, has_bus_conflicts},
+    },
+};
+
+/// ### CNROM (mapper 3)
+/// NROM PRG layout, any write to `$8000-$FFFF` selects the 8 KiB CHR-ROM bank.
+pub struct Cnrom {
+    prg_rom: BankedMemory,
+    chr: BankedMemory,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+}
+
+impl Cnrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self {
+            chr: BankedMemory::chr(&cartridge, 0x2000, 1),
+            mirroring: cartridge.header.mirroring,
+            bus_conflicts: has_bus_conflicts(&cartridge, true),
+            prg_rom: BankedMemory::rom(cartridge.prg_rom, 0x4000, 2),
+        }
+    }
+}
+
+impl Bus for Cnrom {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, mut value: Byte) {
+        if addr >= 0x8000 {
+            if self.bus_conflicts {
+                value &= self.prg_rom.read(addr as usize - 0x8000);
+            }
+            self.chr.map(0, 1, value as usize);
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        if addr >= 0x8000 { 0xFF } else { 0x00 }
+    }
+}
+
+impl Mapper for Cnrom {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{
+    bus::{Bus, Byte, Word},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, has_bus_conflicts},
+    },
+};
+
+/// ### GxROM (mapper 66)
+/// Writes to `$8000-$FFFF` select the 32 KiB PRG bank (bits 4-5)
+/// and the 8 KiB CHR bank (bits 0-1).
+pub struct Gxrom {
+    prg_rom: BankedMemory,
+    chr: BankedMemory,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+}
+
+impl Gxrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self {
+            chr: BankedMemory::chr(&cartridge, 0x2000, 1),
+            mirroring: cartridge.header.mirroring,
+            bus_conflicts: has_bus_conflicts(&cartridge, true),
+            prg_rom: BankedMemory::rom(cartridge.prg_rom, 0x8000, 1),
+        }
+    }
+}
+
+impl Bus for Gxrom {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, mut value: Byte) {
+        if addr >= 0x8000 {
+            if self.bus_conflicts {
+                value &= self.prg_rom.read(addr as usize - 0x8000);
+            }
+            self.prg_rom.map(0, 1, ((value >> 4) & 0x03) as usize);
+            self.chr.map(0, 1, (value & 0x03) as usize);
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        if addr >= 0x8000 { 0xFF } else { 0x00 }
+    }
+}
+
+impl Mapper for Gxrom {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
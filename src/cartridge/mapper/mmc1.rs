@@ -0,0 +1,189 @@
+use crate::{
+    bus::{Bus, Byte, Word, clock::Lines},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, prg_ram_size},
+    },
+};
+
+const OUTER_PRG_SIZE: usize = 256 * 1024;
+
+/// ### MMC1 (mapper 1)
+/// Registers are loaded serially: five writes to `$8000-$FFFF` shift in bit 0
+/// of the value, LSB first, and the fifth write lands in the register picked by
+/// address bits 13-14. Writing a value with bit 7 set resets the shift register.
+///
+/// | Register | Range | Content |
+/// |----------|-------|---------|
+/// | Control | `$8000-$9FFF` | `CPPMM`: CHR mode, PRG mode, mirroring |
+/// | CHR bank 0 | `$A000-$BFFF` | 4 KiB bank at `$0000` (8 KiB in 8 KiB mode) |
+/// | CHR bank 1 | `$C000-$DFFF` | 4 KiB bank at `$1000` |
+/// | PRG bank | `$E000-$FFFF` | `RPPPP`: PRG-RAM disable, 16 KiB bank |
+///
+/// SUROM/SXROM boards reuse CHR bank 0 bits on CHR-RAM carts: bit 4 selects the
+/// 256 KiB half of a 512 KiB PRG-ROM and bits 2-3 (SXROM) or bit 3 (SOROM)
+/// select the 8 KiB PRG-RAM bank.
+///
+/// The chip ignores a write on the cycle right after another one, which makes
+/// the dummy write of read-modify-write instructions harmless. The board
+/// counts cycles through [`Mapper::clock`], and takes every write until it
+/// has been clocked, so boards that aren't attached to the CPU still work.
+pub struct Mmc1 {
+    prg_rom: BankedMemory,
+    prg_ram: BankedMemory,
+    chr: BankedMemory,
+    battery: bool,
+    shift: Byte,
+    shift_count: Byte,
+    control: Byte,
+    chr_bank_0: Byte,
+    chr_bank_1: Byte,
+    prg_bank: Byte,
+    cycle: u64,
+    last_write: Option<u64>,
+}
+
+impl Mmc1 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let ram_size = prg_ram_size(&cartridge);
+        let mut mmc1 = Self {
+            prg_ram: BankedMemory::ram(ram_size, 0x2000, 1),
+            chr: BankedMemory::chr(&cartridge, 0x1000, 2),
+            battery: cartridge.header.battery,
+            prg_rom: BankedMemory::rom(cartridge.prg_rom, 0x4000, 2),
+            shift: 0,
+            shift_count: 0,
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cycle: 0,
+            last_write: None,
+        };
+        mmc1.update_banks();
+        mmc1
+    }
+
+    fn write_register(&mut self, addr: Word, value: Byte) {
+        if value & 0x80 != 0 {
+            self.shift = 0;
+            self.shift_count = 0;
+            self.control |= 0x0C;
+            self.update_banks();
+            return;
+        }
+        self.shift |= (value & 0x01) << self.shift_count;
+        self.shift_count += 1;
+        if self.shift_count < 5 {
+            return;
+        }
+        match addr {
+            0x8000..=0x9FFF => self.control = self.shift,
+            0xA000..=0xBFFF => self.chr_bank_0 = self.shift,
+            0xC000..=0xDFFF => self.chr_bank_1 = self.shift,
+            _ => self.prg_bank = self.shift,
+        }
+        self.shift = 0;
+        self.shift_count = 0;
+        self.update_banks();
+    }
+
+    fn update_banks(&mut self) {
+        let outer = if self.prg_rom.data.len() > OUTER_PRG_SIZE {
+            (self.chr_bank_0 & 0x10) as usize
+        } else {
+            0
+        };
+        let bank = (self.prg_bank & 0x0F) as usize | outer;
+        match (self.control >> 2) & 0x03 {
+            0 | 1 => self.prg_rom.map(0, 2, bank >> 1),
+            2 => {
+                self.prg_rom.map(0, 1, outer);
+                self.prg_rom.map(1, 1, bank);
+            }
+            _ => {
+                self.prg_rom.map(0, 1, bank);
+                self.prg_rom.map(1, 1, outer | 0x0F);
+            }
+        }
+
+        if self.control & 0x10 != 0 {
+            self.chr.map(0, 1, self.chr_bank_0 as usize);
+            self.chr.map(1, 1, self.chr_bank_1 as usize);
+        } else {
+            self.chr.map(0, 2, (self.chr_bank_0 >> 1) as usize);
+        }
+
+        let ram_bank = match self.prg_ram.data.len() {
+            0x8000.. => (self.chr_bank_0 >> 2) & 0x03,
+            0x4000.. => (self.chr_bank_0 >> 3) & 0x01,
+            _ => 0,
+        };
+        self.prg_ram.map(0, 1, ram_bank as usize);
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0 && !self.prg_ram.data.is_empty()
+    }
+}
+
+impl Bus for Mmc1 {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.read(addr as usize - 0x6000),
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                self.prg_ram.write(addr as usize - 0x6000, value)
+            }
+            0x8000..=0xFFFF => {
+                let consecutive = self.cycle > 0 && self.last_write == Some(self.cycle);
+                self.last_write = Some(self.cycle);
+                if !consecutive {
+                    self.write_register(addr, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => 0xFF,
+            0x8000..=0xFFFF => 0xFF,
+            _ => 0x00,
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn clock(&mut self, _: &mut Lines) {
+        self.cycle += 1;
+    }
+
+    fn save_ram(&self) -> Option<&[Byte]> {
+        (self.battery && !self.prg_ram.data.is_empty()).then_some(self.prg_ram.data.as_slice())
+    }
+}
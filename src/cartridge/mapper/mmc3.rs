@@ -0,0 +1,273 @@
+use crate::{
+    bus::{Bus, Byte, Word, clock::Lines},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, prg_ram_size},
+    },
+};
+
+/// CPU cycles A12 has to stay low before a rising edge clocks the counter again.
+/// Filters out the short dips between pattern fetches, the longest being the
+/// 9 dots (3 cycles) from the last prefetch of a scanline to its first tile.
+const A12_FILTER_CYCLES: u64 = 4;
+
+/// ### IRQ counter revision
+/// The chips disagree on what happens when the counter is reloaded with `0`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IrqBehavior {
+    /// MMC3B/C (Sharp): fires on every clock that leaves the counter at `0`,
+    /// so a latch of `0` fires on every scanline.
+    #[default]
+    Sharp,
+    /// MMC3A (NEC): only fires when the counter decrements to `0`
+    /// or was explicitly reloaded through `$C001`.
+    Nec,
+}
+
+/// ### MMC3 (mapper 4)
+/// | Register | Even address | Odd address |
+/// |----------|--------------|-------------|
+/// | `$8000-$9FFF` | Bank select: `CP...RRR` | Bank data for `R` |
+/// | `$A000-$BFFF` | Mirroring | PRG-RAM protect: `EW......` |
+/// | `$C000-$DFFF` | IRQ latch | IRQ reload |
+/// | `$E000-$FFFF` | IRQ disable and acknowledge | IRQ enable |
+///
+/// PRG is banked in 8 KiB windows: R6 at `$8000` (or `$C000` when `P` is set),
+/// R7 at `$A000`, the second to last bank in the other of `$8000`/`$C000` and the
+/// last bank at `$E000`. CHR uses two 2 KiB banks (R0, R1) and four 1 KiB banks
+/// (R2-R5), the halves swapped when `C` is set.
+///
+/// The IRQ counter is clocked by rising edges of PPU A12, which the board sees
+/// through every PPU access hook of [`Mapper`].
+/// It must be attached to the CPU so it can measure time and drive the IRQ line.
+pub struct Mmc3 {
+    prg_rom: BankedMemory,
+    prg_ram: BankedMemory,
+    chr: BankedMemory,
+    battery: bool,
+    four_screen: bool,
+    bank_select: Byte,
+    registers: [Byte; 8],
+    mirroring: Mirroring,
+    ram_control: Byte,
+    irq_behavior: IrqBehavior,
+    irq_latch: Byte,
+    irq_counter: Byte,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    cycle: u64,
+    a12_low_since: Option<u64>,
+}
+
+impl Mmc3 {
+    /// NES 2.0 submapper 4 selects the MMC3A IRQ behavior, anything else the Sharp one.
+    pub fn new(cartridge: Cartridge) -> Self {
+        let ram_size = prg_ram_size(&cartridge);
+        let four_screen = cartridge.header.mirroring == Mirroring::FourScreen;
+        let mut mmc3 = Self {
+            prg_ram: BankedMemory::ram(ram_size, 0x2000, 1),
+            chr: BankedMemory::chr(&cartridge, 0x0400, 8),
+            battery: cartridge.header.battery,
+            four_screen,
+            mirroring: cartridge.header.mirroring,
+            irq_behavior: if cartridge.header.submapper == 4 {
+                IrqBehavior::Nec
+            } else {
+                IrqBehavior::Sharp
+            },
+            prg_rom: BankedMemory::rom(cartridge.prg_rom, 0x2000, 4),
+            bank_select: 0,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            ram_control: 0x80,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            cycle: 0,
+            a12_low_since: Some(0),
+        };
+        mmc3.update_banks();
+        mmc3
+    }
+
+    pub fn with_irq_behavior(mut self, behavior: IrqBehavior) -> Self {
+        self.irq_behavior = behavior;
+        self
+    }
+
+    /// Whether the IRQ line is currently held low.
+    pub fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn write_register(&mut self, addr: Word, value: Byte) {
+        match (addr & 0xE000, addr & 0x01) {
+            (0x8000, 0) => {
+                self.bank_select = value;
+                self.update_banks();
+            }
+            (0x8000, _) => {
+                self.registers[(self.bank_select & 0x07) as usize] = value;
+                self.update_banks();
+            }
+            (0xA000, 0) => {
+                if !self.four_screen {
+                    self.mirroring = if value & 0x01 != 0 {
+                        Mirroring::Horizontal
+                    } else {
+                        Mirroring::Vertical
+                    };
+                }
+            }
+            (0xA000, _) => self.ram_control = value,
+            (0xC000, 0) => self.irq_latch = value,
+            (0xC000, _) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (_, 0) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            _ => self.irq_enabled = true,
+        }
+    }
+
+    fn update_banks(&mut self) {
+        let last = self.prg_rom.bank_count(1) - 1;
+        let (r6_slot, fixed_slot) = if self.bank_select & 0x40 != 0 {
+            (2, 0)
+        } else {
+            (0, 2)
+        };
+        self.prg_rom.map(r6_slot, 1, self.registers[6] as usize);
+        self.prg_rom.map(1, 1, self.registers[7] as usize);
+        self.prg_rom.map(fixed_slot, 1, last.saturating_sub(1));
+        self.prg_rom.map(3, 1, last);
+
+        let invert = if self.bank_select & 0x80 != 0 { 4 } else { 0 };
+        self.chr.map(invert, 1, (self.registers[0] & 0xFE) as usize);
+        self.chr
+            .map(invert + 1, 1, (self.registers[0] | 0x01) as usize);
+        self.chr
+            .map(invert + 2, 1, (self.registers[1] & 0xFE) as usize);
+        self.chr
+            .map(invert + 3, 1, (self.registers[1] | 0x01) as usize);
+        for i in 0..4 {
+            self.chr
+                .map((4 - invert) + i, 1, self.registers[2 + i] as usize);
+        }
+    }
+
+    /// Tracks PPU A12 and clocks the IRQ counter on filtered rising edges.
+    fn watch_a12(&mut self, addr: Word) {
+        if addr & 0x1000 == 0 {
+            self.a12_low_since.get_or_insert(self.cycle);
+        } else if let Some(since) = self.a12_low_since.take()
+            && self.cycle - since >= A12_FILTER_CYCLES
+        {
+            self.clock_irq_counter();
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let previous = self.irq_counter;
+        let reloaded = self.irq_reload;
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        let fire = match self.irq_behavior {
+            IrqBehavior::Sharp => self.irq_counter == 0,
+            IrqBehavior::Nec => self.irq_counter == 0 && (previous != 0 || reloaded),
+        };
+        if fire && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.ram_control & 0x80 != 0 && !self.prg_ram.data.is_empty()
+    }
+}
+
+impl Bus for Mmc3 {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.read(addr as usize - 0x6000),
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() && self.ram_control & 0x40 == 0 => {
+                self.prg_ram.write(addr as usize - 0x6000, value)
+            }
+            0x8000..=0xFFFF => self.write_register(addr, value),
+            _ => {}
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => 0xFF,
+            0x8000..=0xFFFF => 0xFF,
+            _ => 0x00,
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.watch_a12(addr);
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.watch_a12(addr);
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn ppu_peek(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_address(&mut self, addr: Word) {
+        self.watch_a12(addr);
+    }
+
+    fn nametable_read(&mut self, addr: Word) -> Option<Byte> {
+        self.watch_a12(addr);
+        None
+    }
+
+    fn nametable_peek(&mut self, _: Word) -> Option<Byte> {
+        None
+    }
+
+    fn nametable_write(&mut self, addr: Word, _: Byte) -> bool {
+        self.watch_a12(addr);
+        false
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn clock(&mut self, lines: &mut Lines) {
+        self.cycle += 1;
+        if self.irq_pending {
+            lines.assert_irq();
+        }
+    }
+
+    fn save_ram(&self) -> Option<&[Byte]> {
+        (self.battery && !self.prg_ram.data.is_empty()).then_some(self.prg_ram.data.as_slice())
+    }
+}
@@ -0,0 +1,502 @@
+use std::ops::Range;
+
+use crate::{
+    bus::{Bus, Byte, Word, clock::Lines},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, prg_ram_size},
+    },
+};
+
+const EXRAM_SIZE: usize = 0x400;
+/// PPU reads of a scanline, counted from its first background fetch, that belong to sprites
+const SPRITE_FETCHES: Range<usize> = 128..160;
+/// Reads fetching the first two tiles of the next scanline
+const PREFETCHES: Range<usize> = 160..168;
+/// CPU cycles without a PPU read after which the chip considers rendering stopped
+const IDLE_CYCLES: u32 = 3;
+const VISIBLE_SCANLINES: usize = 240;
+
+/// ### MMC5 (mapper 5)
+/// | Register | Content |
+/// |----------|---------|
+/// | `$5100` | PRG mode: 32, 16, 16+8+8 or 8 KiB banks |
+/// | `$5101` | CHR mode: 8, 4, 2 or 1 KiB banks |
+/// | `$5102-$5103` | PRG-RAM write protect, writable when `$02` and `$01` |
+/// | `$5104` | ExRAM mode: nametable, extended attributes, RAM, ROM |
+/// | `$5105` | Nametable sources, 2 bits each: CIRAM 0/1, ExRAM, fill |
+/// | `$5106-$5107` | Fill mode tile and palette |
+/// | `$5113-$5117` | PRG banks, bit 7 picks ROM over RAM (`$5117` is always ROM) |
+/// | `$5120-$512B` | CHR banks, `$5130` supplies the upper bits |
+/// | `$5200-$5202` | Vertical split control, scroll and CHR bank |
+/// | `$5203-$5204` | Scanline IRQ compare, enable (write) and status (read) |
+/// | `$5205-$5206` | 8x8 bit unsigned multiplier |
+/// | `$5C00-$5FFF` | 1 KiB ExRAM |
+///
+/// The chip sees the whole CPU bus and snoops PPU writes to `$2000` (8x16 sprites)
+/// and `$2001` (rendering enable). Everything else it learns from the PPU's reads:
+/// three reads of the same nametable address in a row mark the start of a scanline,
+/// from which it counts fetches to tell sprite pattern fetches from background ones.
+/// That needs a PPU doing the real fetch pattern through [`Mapper::nametable_read`]
+/// and [`Mapper::ppu_read`], and the board attached to the CPU.
+///
+/// With 8x16 sprites, sprites use CHR banks `$5120-$5127` and the background
+/// `$5128-$512B`, otherwise the last written set is used for everything.
+/// Expansion audio is not emulated.
+pub struct Mmc5 {
+    prg_rom: BankedMemory,
+    /// Slot 0 is `$6000`, slots 1-4 mirror the ROM slots
+    prg_ram: BankedMemory,
+    ram_slots: [bool; 4],
+    /// Slots 0-7 are the sprite set, slots 8-15 the background set
+    chr: BankedMemory,
+    exram: [Byte; EXRAM_SIZE],
+    battery: bool,
+    prg_mode: Byte,
+    chr_mode: Byte,
+    ram_protect: [Byte; 2],
+    exram_mode: Byte,
+    nametables: Byte,
+    fill_tile: Byte,
+    fill_color: Byte,
+    prg_registers: [Byte; 5],
+    chr_registers: [usize; 12],
+    chr_upper: Byte,
+    last_chr_set_b: bool,
+    split_control: Byte,
+    split_scroll: Byte,
+    split_bank: Byte,
+    irq_compare: Byte,
+    irq_enabled: bool,
+    irq_pending: bool,
+    multiplicand: Byte,
+    multiplier: Byte,
+    sprite_8x16: bool,
+    in_frame: bool,
+    scanline: Byte,
+    last_read: Option<Word>,
+    repeats: u8,
+    fetch: usize,
+    idle_cycles: u32,
+    tile_split: bool,
+    tile_attributes: Byte,
+}
+
+impl Mmc5 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mut mmc5 = Self {
+            prg_ram: BankedMemory::ram(prg_ram_size(&cartridge), 0x2000, 5),
+            chr: BankedMemory::chr(&cartridge, 0x0400, 16),
+            battery: cartridge.header.battery,
+            prg_rom: BankedMemory::rom(cartridge.prg_rom, 0x2000, 4),
+            ram_slots: [false; 4],
+            exram: [0; EXRAM_SIZE],
+            prg_mode: 3,
+            chr_mode: 0,
+            ram_protect: [0; 2],
+            exram_mode: 0,
+            nametables: 0,
+            fill_tile: 0,
+            fill_color: 0,
+            prg_registers: [0, 0, 0, 0, 0xFF],
+            chr_registers: [0; 12],
+            chr_upper: 0,
+            last_chr_set_b: false,
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            irq_compare: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            sprite_8x16: false,
+            in_frame: false,
+            scanline: 0,
+            last_read: None,
+            repeats: 0,
+            fetch: usize::MAX,
+            idle_cycles: 0,
+            tile_split: false,
+            tile_attributes: 0,
+        };
+        mmc5.update_prg();
+        mmc5.update_chr();
+        mmc5
+    }
+
+    fn update_prg(&mut self) {
+        let r = self.prg_registers;
+        self.prg_ram.map(0, 1, (r[0] & 0x0F) as usize);
+        // (register, 8 KiB bank) for each slot of $8000-$FFFF
+        let bank = |register: usize, mask: Byte, offset: Byte| {
+            (register, ((r[register] & mask) + offset) as usize)
+        };
+        let slots = match self.prg_mode & 0x03 {
+            0 => [0, 1, 2, 3].map(|i| bank(4, 0x7C, i)),
+            1 => [
+                bank(2, 0x7E, 0),
+                bank(2, 0x7E, 1),
+                bank(4, 0x7E, 0),
+                bank(4, 0x7E, 1),
+            ],
+            2 => [
+                bank(2, 0x7E, 0),
+                bank(2, 0x7E, 1),
+                bank(3, 0x7F, 0),
+                bank(4, 0x7F, 0),
+            ],
+            _ => [
+                bank(1, 0x7F, 0),
+                bank(2, 0x7F, 0),
+                bank(3, 0x7F, 0),
+                bank(4, 0x7F, 0),
+            ],
+        };
+        for (slot, (register, bank)) in slots.into_iter().enumerate() {
+            self.ram_slots[slot] = register != 4 && r[register] & 0x80 == 0;
+            self.prg_rom.map(slot, 1, bank);
+            self.prg_ram.map(slot + 1, 1, bank & 0x0F);
+        }
+    }
+
+    fn update_chr(&mut self) {
+        let a = &self.chr_registers[..8];
+        let b = &self.chr_registers[8..];
+        match self.chr_mode & 0x03 {
+            0 => {
+                self.chr.map(0, 8, a[7]);
+                self.chr.map(8, 8, b[3]);
+            }
+            1 => {
+                self.chr.map(0, 4, a[3]);
+                self.chr.map(4, 4, a[7]);
+                self.chr.map(8, 4, b[3]);
+                self.chr.map(12, 4, b[3]);
+            }
+            2 => {
+                for i in 0..4 {
+                    self.chr.map(i * 2, 2, a[i * 2 + 1]);
+                    self.chr.map(8 + i * 2, 2, b[(i % 2) * 2 + 1]);
+                }
+            }
+            _ => {
+                for i in 0..8 {
+                    self.chr.map(i, 1, a[i]);
+                    self.chr.map(8 + i, 1, b[i % 4]);
+                }
+            }
+        }
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.ram_protect == [0x02, 0x01] && !self.prg_ram.data.is_empty()
+    }
+
+    /// Follows the PPU's reads to find scanline starts and the index of the current fetch.
+    fn observe_read(&mut self, addr: Word) {
+        self.idle_cycles = 0;
+        if self.last_read == Some(addr) {
+            self.repeats += 1;
+        } else {
+            self.last_read = Some(addr);
+            self.repeats = 0;
+        }
+        if self.repeats == 2 && (0x2000..0x3000).contains(&addr) {
+            self.fetch = 0;
+            self.start_scanline();
+        } else {
+            self.fetch = self.fetch.saturating_add(1);
+        }
+    }
+
+    fn start_scanline(&mut self) {
+        if self.in_frame {
+            self.scanline = self.scanline.wrapping_add(1);
+            if self.scanline == self.irq_compare {
+                self.irq_pending = true;
+            }
+        } else {
+            // A new frame drops an IRQ left unacknowledged from the last one
+            self.in_frame = true;
+            self.scanline = 0;
+            self.irq_pending = false;
+        }
+    }
+
+    fn leave_frame(&mut self) {
+        self.in_frame = false;
+        self.last_read = None;
+        self.fetch = usize::MAX;
+    }
+
+    fn background_fetch(&self) -> bool {
+        self.in_frame && (self.fetch < SPRITE_FETCHES.start || PREFETCHES.contains(&self.fetch))
+    }
+
+    fn sprite_fetch(&self) -> bool {
+        self.in_frame && SPRITE_FETCHES.contains(&self.fetch)
+    }
+
+    /// Tile column of the current background fetch, the first two tiles of a
+    /// scanline being prefetched at the end of the previous one.
+    fn tile_column(&self) -> usize {
+        if self.fetch >= PREFETCHES.start {
+            (self.fetch - PREFETCHES.start) / 4
+        } else {
+            self.fetch / 4 + 2
+        }
+    }
+
+    /// Row of the split region the current background fetch is for.
+    fn split_y(&self) -> usize {
+        let next_line = usize::from(self.fetch >= PREFETCHES.start);
+        (self.split_scroll as usize + self.scanline as usize + next_line) % VISIBLE_SCANLINES
+    }
+
+    fn in_split(&self, column: usize) -> bool {
+        let edge = (self.split_control & 0x1F) as usize;
+        self.split_control & 0x80 != 0
+            && self.exram_mode <= 1
+            && if self.split_control & 0x40 != 0 {
+                column >= edge
+            } else {
+                column < edge
+            }
+    }
+
+    /// Nametable byte `$5105` maps at `addr`, `None` for CIRAM.
+    fn mapped_nametable(&self, addr: Word) -> Option<Byte> {
+        let offset = addr as usize & 0x03FF;
+        match (self.nametables >> (((addr >> 10) & 0x03) * 2)) & 0x03 {
+            0 | 1 => None,
+            2 => Some(if self.exram_mode <= 1 {
+                self.exram[offset]
+            } else {
+                0
+            }),
+            _ if offset >= 0x3C0 => Some(self.fill_color * 0x55),
+            _ => Some(self.fill_tile),
+        }
+    }
+
+    fn chr_4k(&self, bank: usize, addr: Word) -> Byte {
+        let data = &self.chr.data;
+        if data.is_empty() {
+            return 0;
+        }
+        data[(bank * 0x1000 + (addr as usize & 0x0FFF)) % data.len()]
+    }
+
+    fn chr_read(&self, addr: Word) -> Byte {
+        if self.background_fetch() && self.tile_split {
+            let fine_y = (self.split_y() & 0x07) as Word;
+            return self.chr_4k(self.split_bank as usize, (addr & 0x0FF8) | fine_y);
+        }
+        if self.background_fetch() && self.exram_mode == 1 {
+            let bank = (self.tile_attributes & 0x3F) as usize | (self.chr_upper as usize) << 6;
+            return self.chr_4k(bank, addr);
+        }
+        let set_b = if self.sprite_8x16 && self.in_frame {
+            !self.sprite_fetch()
+        } else {
+            self.last_chr_set_b
+        };
+        let base = if set_b { 0x2000 } else { 0 };
+        self.chr.read(base + (addr as usize & 0x1FFF))
+    }
+
+    fn read_register(&mut self, addr: Word, read_only: bool) -> Byte {
+        match addr {
+            0x5204 => {
+                let status = (self.irq_pending as Byte) << 7 | (self.in_frame as Byte) << 6;
+                if !read_only {
+                    self.irq_pending = false;
+                }
+                status
+            }
+            0x5205 => (self.multiplicand as u16 * self.multiplier as u16) as Byte,
+            0x5206 => ((self.multiplicand as u16 * self.multiplier as u16) >> 8) as Byte,
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => self.exram[addr as usize - 0x5C00],
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x2000 => self.sprite_8x16 = value & 0x20 != 0,
+            0x2001 if value & 0x18 == 0 => self.leave_frame(),
+            0x5100 => {
+                self.prg_mode = value & 0x03;
+                self.update_prg();
+            }
+            0x5101 => {
+                self.chr_mode = value & 0x03;
+                self.update_chr();
+            }
+            0x5102 | 0x5103 => self.ram_protect[addr as usize - 0x5102] = value & 0x03,
+            0x5104 => self.exram_mode = value & 0x03,
+            0x5105 => self.nametables = value,
+            0x5106 => self.fill_tile = value,
+            0x5107 => self.fill_color = value & 0x03,
+            0x5113..=0x5117 => {
+                self.prg_registers[addr as usize - 0x5113] = value;
+                self.update_prg();
+            }
+            0x5120..=0x512B => {
+                self.chr_registers[addr as usize - 0x5120] =
+                    value as usize | (self.chr_upper as usize) << 8;
+                self.last_chr_set_b = addr >= 0x5128;
+                self.update_chr();
+            }
+            0x5130 => self.chr_upper = value & 0x03,
+            0x5200 => self.split_control = value,
+            0x5201 => self.split_scroll = value,
+            0x5202 => self.split_bank = value,
+            0x5203 => self.irq_compare = value,
+            0x5204 => self.irq_enabled = value & 0x80 != 0,
+            0x5205 => self.multiplicand = value,
+            0x5206 => self.multiplier = value,
+            // Nametable and attribute modes only accept writes while rendering
+            0x5C00..=0x5FFF => match self.exram_mode {
+                0 | 1 => self.exram[addr as usize - 0x5C00] = if self.in_frame { value } else { 0 },
+                2 => self.exram[addr as usize - 0x5C00] = value,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Bus for Mmc5 {
+    fn read(&mut self, addr: Word, read_only: bool) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if !self.prg_ram.data.is_empty() => {
+                self.prg_ram.read(addr as usize - 0x6000)
+            }
+            0x8000..=0xFFFF => {
+                let slot = (addr as usize - 0x8000) / 0x2000;
+                if self.ram_slots[slot] && !self.prg_ram.data.is_empty() {
+                    self.prg_ram.read(addr as usize - 0x6000)
+                } else {
+                    self.prg_rom.read(addr as usize - 0x8000)
+                }
+            }
+            _ => self.read_register(addr, read_only),
+        }
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_writable() => {
+                self.prg_ram.write(addr as usize - 0x6000, value)
+            }
+            0x8000..=0xDFFF if self.prg_ram_writable() => {
+                if self.ram_slots[(addr as usize - 0x8000) / 0x2000] {
+                    self.prg_ram.write(addr as usize - 0x6000, value);
+                }
+            }
+            0x6000..=0xFFFF => {}
+            _ => self.write_register(addr, value),
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram.data.is_empty() => 0x00,
+            0x5204..=0x5206 | 0x6000..=0xFFFF => 0xFF,
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => 0xFF,
+            _ => 0x00,
+        }
+    }
+}
+
+impl Mapper for Mmc5 {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.observe_read(addr);
+        self.chr_read(addr)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        let base = if self.last_chr_set_b { 0x2000 } else { 0 };
+        self.chr.write(base + (addr as usize & 0x1FFF), value);
+    }
+
+    fn ppu_peek(&mut self, addr: Word) -> Byte {
+        self.chr_read(addr)
+    }
+
+    fn nametable_read(&mut self, addr: Word) -> Option<Byte> {
+        self.observe_read(addr);
+        let offset = addr as usize & 0x03FF;
+        let attribute = offset >= 0x3C0;
+        if self.background_fetch() {
+            let column = self.tile_column();
+            if !attribute {
+                self.tile_split = self.in_split(column);
+                self.tile_attributes = self.exram[offset];
+            }
+            if self.tile_split {
+                let y = self.split_y();
+                let column = column & 0x1F;
+                if !attribute {
+                    return Some(self.exram[y / 8 * 32 + column]);
+                }
+                let shift = ((y / 16) & 0x01) * 4 + ((column / 2) & 0x01) * 2;
+                let palette = (self.exram[0x3C0 + y / 32 * 8 + column / 4] >> shift) & 0x03;
+                return Some(palette * 0x55);
+            }
+            if attribute && self.exram_mode == 1 {
+                return Some((self.tile_attributes >> 6) * 0x55);
+            }
+        }
+        self.mapped_nametable(addr)
+    }
+
+    /// What `$5105` maps at `addr`, without the split and extended attributes
+    /// that depend on where the PPU is in its fetches.
+    fn nametable_peek(&mut self, addr: Word) -> Option<Byte> {
+        self.mapped_nametable(addr)
+    }
+
+    fn nametable_write(&mut self, addr: Word, value: Byte) -> bool {
+        match (self.nametables >> (((addr >> 10) & 0x03) * 2)) & 0x03 {
+            0 | 1 => false,
+            2 => {
+                if self.exram_mode <= 1 {
+                    self.exram[addr as usize & 0x03FF] = value;
+                }
+                true
+            }
+            _ => true,
+        }
+    }
+
+    fn ciram_page(&self, table: usize) -> usize {
+        (self.nametables >> (table * 2)) as usize & 0x01
+    }
+
+    /// Closest fixed layout to `$5105`, [`Mapper::ciram_page`] is what the PPU uses.
+    fn mirroring(&self) -> Mirroring {
+        match self.nametables {
+            0x00 => Mirroring::SingleScreenLower,
+            0x55 => Mirroring::SingleScreenUpper,
+            0x50 => Mirroring::Horizontal,
+            _ => Mirroring::Vertical,
+        }
+    }
+
+    fn clock(&mut self, lines: &mut Lines) {
+        self.idle_cycles += 1;
+        if self.in_frame && self.idle_cycles >= IDLE_CYCLES {
+            self.leave_frame();
+        }
+        if self.irq_pending && self.irq_enabled {
+            lines.assert_irq();
+        }
+    }
+
+    fn save_ram(&self) -> Option<&[Byte]> {
+        (self.battery && !self.prg_ram.data.is_empty()).then_some(self.prg_ram.data.as_slice())
+    }
+}
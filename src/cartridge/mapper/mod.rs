@@ -0,0 +1,152 @@
+use std::{cell::RefCell, io, path::Path, rc::Rc};
+
+use crate::{
+    audio::SampleSource,
+    bus::{
+        Bus, Byte, Word,
+        clock::{Clocked, Lines},
+    },
+    cartridge::{
+        Cartridge, CartridgeError, Mirroring,
+        mapper::{
+            axrom::Axrom,
+            cnrom::Cnrom,
+            color_dreams::ColorDreams,
+            gxrom::Gxrom,
+            mmc1::Mmc1,
+            mmc3::Mmc3,
+            mmc5::Mmc5,
+            nrom::Nrom,
+            unrom512::Unrom512,
+            uxrom::Uxrom,
+            vrc6::{Vrc6, Vrc6Variant},
+        },
+    },
+};
+
+pub mod axrom;
+pub mod banked;
+pub mod cnrom;
+pub mod color_dreams;
+pub mod gxrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod mmc5;
+pub mod nrom;
+pub mod unrom512;
+pub mod uxrom;
+pub mod vrc6;
+
+/// ### Cartridge board
+/// The CPU side is the [`Bus`] implementation, which receives CPU addresses
+/// `$4020-$FFFF`. The board doesn't drive addresses it doesn't decode, see
+/// [`Bus::driven_bits`], and the console reads open bus there.
+///
+/// The PPU side covers the pattern tables at `$0000-$1FFF`. Nametables live in
+/// the console's CIRAM, arranged by [`Mapper::ciram_page`], unless the board
+/// answers [`Mapper::nametable_read`] and [`Mapper::nametable_write`] itself.
+pub trait Mapper: Bus {
+    fn ppu_read(&mut self, addr: Word) -> Byte;
+    fn ppu_write(&mut self, addr: Word, value: Byte);
+
+    /// Side-effect-free PPU read for debuggers, see [`Bus`]'s `read_only` contract.
+    fn ppu_peek(&mut self, addr: Word) -> Byte {
+        self.ppu_read(addr)
+    }
+
+    /// Sees addresses the PPU puts on its bus without reading or writing,
+    /// such as the `$2006` address, for boards that snoop A12.
+    fn ppu_address(&mut self, _addr: Word) {}
+
+    /// Nametable read (`$2000-$2FFF`) the board answers from its own memory,
+    /// `None` lets the PPU read CIRAM. Called for every nametable and attribute fetch.
+    fn nametable_read(&mut self, _addr: Word) -> Option<Byte> {
+        None
+    }
+
+    /// Side-effect-free [`Mapper::nametable_read`] for debuggers.
+    fn nametable_peek(&mut self, addr: Word) -> Option<Byte> {
+        self.nametable_read(addr)
+    }
+
+    /// Nametable write the board takes itself, `false` lets the PPU write CIRAM.
+    fn nametable_write(&mut self, _addr: Word, _value: Byte) -> bool {
+        false
+    }
+
+    fn mirroring(&self) -> Mirroring;
+
+    /// CIRAM page backing nametable `table` (0-3).
+    fn ciram_page(&self, table: usize) -> usize {
+        self.mirroring().ciram_page(table)
+    }
+
+    /// Advances the board by one CPU cycle, letting it drive the IRQ line.
+    fn clock(&mut self, _lines: &mut Lines) {}
+
+    /// Expansion audio generated on the cartridge, mixed with the APU by the console.
+    fn sample_source(&self) -> Option<&dyn SampleSource> {
+        None
+    }
+
+    /// Battery backed RAM that should be persisted, if any.
+    fn save_ram(&self) -> Option<&[Byte]> {
+        None
+    }
+
+    /// Writes what the game saved outside of battery backed RAM, such as
+    /// flashed PRG, to a sidecar file at `path`. Boards without any do nothing.
+    fn save_sidecar(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Restores a sidecar written by [`Mapper::save_sidecar`].
+    fn load_sidecar(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Clocked for dyn Mapper {
+    fn tick(&mut self, lines: &mut Lines) {
+        self.clock(lines);
+    }
+}
+
+/// A board shared between the CPU bus, the PPU and the scheduler.
+pub type SharedMapper = Rc<RefCell<dyn Mapper>>;
+
+/// Builds the board matching the iNES mapper number of `cartridge`.
+pub fn from_cartridge(cartridge: Cartridge) -> Result<SharedMapper, CartridgeError> {
+    let mapper: SharedMapper = match cartridge.header.mapper {
+        0 => Rc::new(RefCell::new(Nrom::new(cartridge))),
+        1 => Rc::new(RefCell::new(Mmc1::new(cartridge))),
+        2 => Rc::new(RefCell::new(Uxrom::new(cartridge))),
+        3 => Rc::new(RefCell::new(Cnrom::new(cartridge))),
+        4 => Rc::new(RefCell::new(Mmc3::new(cartridge))),
+        5 => Rc::new(RefCell::new(Mmc5::new(cartridge))),
+        7 => Rc::new(RefCell::new(Axrom::new(cartridge))),
+        11 => Rc::new(RefCell::new(ColorDreams::new(cartridge))),
+        24 => Rc::new(RefCell::new(Vrc6::new(cartridge, Vrc6Variant::A))),
+        26 => Rc::new(RefCell::new(Vrc6::new(cartridge, Vrc6Variant::B))),
+        30 => Rc::new(RefCell::new(Unrom512::new(cartridge))),
+        66 => Rc::new(RefCell::new(Gxrom::new(cartridge))),
+        mapper => return Err(CartridgeError::UnsupportedMapper(mapper)),
+    };
+    Ok(mapper)
+}
+
+/// Size of the PRG-RAM declared by the header, volatile or not.
+pub(crate) fn prg_ram_size(cartridge: &Cartridge) -> usize {
+    cartridge.header.prg_ram_size + cartridge.header.prg_nvram_size
+}
+
+/// Whether writes to ROM get ANDed with the byte the ROM drives at the same time.
+/// NES 2.0 submapper 1 means no conflicts and 2 means conflicts,
+/// otherwise the usual board for the mapper number decides.
+pub(crate) fn has_bus_conflicts(cartridge: &Cartridge, default: bool) -> bool {
+    match cartridge.header.submapper {
+        1 => false,
+        2 => true,
+        _ => default,
+    }
+}
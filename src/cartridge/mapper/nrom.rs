@@ -0,0 +1,83 @@
+use crate::{
+    bus::{Bus, Byte, Word},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, prg_ram_size},
+    },
+};
+
+/// ### NROM (mapper 0)
+/// | Range | Content |
+/// |-------|---------|
+/// | `$6000-$7FFF` | PRG-RAM (Family BASIC), when the header declares some |
+/// | `$8000-$BFFF` | First 16 KiB of PRG-ROM |
+/// | `$C000-$FFFF` | Last 16 KiB of PRG-ROM, a mirror of the first on NROM-128 |
+///
+/// The PPU sees 8 KiB of CHR-ROM, or CHR-RAM when the header has no CHR,
+/// and the mirroring is soldered on the board.
+pub struct Nrom {
+    prg_rom: BankedMemory,
+    prg_ram: Vec<Byte>,
+    chr: BankedMemory,
+    mirroring: Mirroring,
+    battery: bool,
+}
+
+impl Nrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self {
+            prg_ram: vec![0; prg_ram_size(&cartridge)],
+            chr: BankedMemory::chr(&cartridge, 0x2000, 1),
+            mirroring: cartridge.header.mirroring,
+            battery: cartridge.header.battery,
+            prg_rom: BankedMemory::rom(cartridge.prg_rom, 0x4000, 2),
+        }
+    }
+}
+
+impl Bus for Nrom {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if !self.prg_ram.is_empty() => {
+                self.prg_ram[(addr as usize - 0x6000) % self.prg_ram.len()]
+            }
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        if let 0x6000..=0x7FFF = addr
+            && !self.prg_ram.is_empty()
+        {
+            let len = self.prg_ram.len();
+            self.prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if !self.prg_ram.is_empty() => 0xFF,
+            0x8000..=0xFFFF => 0xFF,
+            _ => 0x00,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_ram(&self) -> Option<&[Byte]> {
+        (self.battery && !self.prg_ram.is_empty()).then_some(self.prg_ram.as_slice())
+    }
+}
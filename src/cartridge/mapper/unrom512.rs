@@ -0,0 +1,250 @@
+use std::{fs, io, path::Path};
+
+use crate::{
+    bus::{Bus, Byte, Word},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, has_bus_conflicts},
+    },
+};
+
+pub const FLASH_SECTOR_SIZE: usize = 4 * 1024;
+const MIN_CHR_RAM_SIZE: usize = 32 * 1024;
+/// SST manufacturer and SST39SF040 device IDs
+const FLASH_ID: [Byte; 2] = [0xBF, 0xB7];
+
+/// Progress through the SST39SF040 command sequences.
+/// Every command starts with `$AA` to `$5555` then `$55` to `$2AAA`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlashState {
+    Ready,
+    Unlock1,
+    Unlock2,
+    /// After `$A0`, the next write programs a byte
+    Program,
+    /// After `$80`, an erase needs its own unlock sequence
+    Erase,
+    EraseUnlock1,
+    EraseUnlock2,
+}
+
+/// ### UNROM-512 (mapper 30)
+/// Writes to the register select `MCCPPPPP`: the 16 KiB PRG bank at `$8000`,
+/// the 8 KiB CHR-RAM bank and, on one-screen boards, the CIRAM page.
+/// `$C000-$FFFF` is fixed to the last bank.
+///
+/// Flashable boards (battery bit set) decode the register at `$C000-$FFFF` only,
+/// writes to `$8000-$BFFF` go to the SST39SF040 holding PRG, which lets games save
+/// into their own ROM. The flash address is the selected bank and `A0-A13`, so the
+/// `$5555`/`$2AAA` command addresses are reached through `$9555` in bank 1 and
+/// `$AAAA` in bank 0. Programming and erasing complete instantly.
+///
+/// Flashed sectors are tracked so they can be kept in a sidecar file next to the
+/// ROM, see [`Mapper::save_sidecar`].
+pub struct Unrom512 {
+    prg_rom: BankedMemory,
+    chr: BankedMemory,
+    /// `None` on one-screen boards, where the register picks the page
+    mirroring: Option<Mirroring>,
+    bank: Byte,
+    flashable: bool,
+    bus_conflicts: bool,
+    flash_state: FlashState,
+    software_id: bool,
+    dirty_sectors: Vec<bool>,
+}
+
+impl Unrom512 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let header = cartridge.header;
+        let mirroring = match (header.mirroring, header.mirroring_bit) {
+            (Mirroring::FourScreen, false) => None,
+            (mirroring, _) => Some(mirroring),
+        };
+        let bus_conflicts = has_bus_conflicts(&cartridge, !header.battery);
+        let chr = if cartridge.chr_rom.is_empty() {
+            let size = header.chr_ram_size + header.chr_nvram_size;
+            BankedMemory::ram(size.max(MIN_CHR_RAM_SIZE), 0x2000, 1)
+        } else {
+            BankedMemory::chr(&cartridge, 0x2000, 1)
+        };
+        let mut prg_rom = BankedMemory::rom(cartridge.prg_rom, 0x4000, 2);
+        let last = prg_rom.bank_count(1) - 1;
+        prg_rom.map(1, 1, last);
+        Self {
+            dirty_sectors: vec![false; prg_rom.data.len().div_ceil(FLASH_SECTOR_SIZE)],
+            prg_rom,
+            chr,
+            mirroring,
+            bank: 0,
+            flashable: header.battery,
+            bus_conflicts,
+            flash_state: FlashState::Ready,
+            software_id: false,
+        }
+    }
+
+    /// Whether any sector has been erased or programmed since power on or the last load.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_sectors.contains(&true)
+    }
+
+    fn select(&mut self, value: Byte) {
+        self.bank = value;
+        self.prg_rom.map(0, 1, (value & 0x1F) as usize);
+        self.chr.map(0, 1, ((value >> 5) & 0x03) as usize);
+    }
+
+    fn flash_address(&self, addr: Word) -> usize {
+        ((self.bank & 0x1F) as usize) << 14 | (addr as usize & 0x3FFF)
+    }
+
+    fn write_flash(&mut self, addr: Word, value: Byte) {
+        let address = self.flash_address(addr);
+        let command = address & 0x7FFF;
+        self.flash_state = match (self.flash_state, command, value) {
+            (FlashState::Program, _, _) => {
+                self.program(address, value);
+                FlashState::Ready
+            }
+            (_, _, 0xF0) => {
+                self.software_id = false;
+                FlashState::Ready
+            }
+            (FlashState::Ready, 0x5555, 0xAA) => FlashState::Unlock1,
+            (FlashState::Unlock1, 0x2AAA, 0x55) => FlashState::Unlock2,
+            (FlashState::Unlock2, 0x5555, 0xA0) => FlashState::Program,
+            (FlashState::Unlock2, 0x5555, 0x80) => FlashState::Erase,
+            (FlashState::Unlock2, 0x5555, 0x90) => {
+                self.software_id = true;
+                FlashState::Ready
+            }
+            (FlashState::Erase, 0x5555, 0xAA) => FlashState::EraseUnlock1,
+            (FlashState::EraseUnlock1, 0x2AAA, 0x55) => FlashState::EraseUnlock2,
+            (FlashState::EraseUnlock2, _, 0x30) => {
+                self.erase(
+                    address / FLASH_SECTOR_SIZE * FLASH_SECTOR_SIZE,
+                    FLASH_SECTOR_SIZE,
+                );
+                FlashState::Ready
+            }
+            (FlashState::EraseUnlock2, 0x5555, 0x10) => {
+                self.erase(0, self.prg_rom.data.len());
+                FlashState::Ready
+            }
+            _ => FlashState::Ready,
+        };
+    }
+
+    /// Programming can only clear bits, erased bytes read `$FF`.
+    fn program(&mut self, address: usize, value: Byte) {
+        let Some(byte) = self.prg_rom.data.get_mut(address) else {
+            return;
+        };
+        *byte &= value;
+        self.dirty_sectors[address / FLASH_SECTOR_SIZE] = true;
+    }
+
+    fn erase(&mut self, start: usize, size: usize) {
+        let end = (start + size).min(self.prg_rom.data.len());
+        if start >= end {
+            return;
+        }
+        self.prg_rom.data[start..end].fill(0xFF);
+        for dirty in
+            &mut self.dirty_sectors[start / FLASH_SECTOR_SIZE..end.div_ceil(FLASH_SECTOR_SIZE)]
+        {
+            *dirty = true;
+        }
+    }
+}
+
+impl Bus for Unrom512 {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x8000..=0xFFFF if self.software_id => FLASH_ID[addr as usize & 0x01],
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, mut value: Byte) {
+        match addr {
+            0x8000..=0xBFFF if self.flashable => self.write_flash(addr, value),
+            0x8000..=0xFFFF => {
+                if self.bus_conflicts {
+                    value &= self.prg_rom.read(addr as usize - 0x8000);
+                }
+                self.select(value);
+            }
+            _ => {}
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        if addr >= 0x8000 { 0xFF } else { 0x00 }
+    }
+}
+
+impl Mapper for Unrom512 {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            Some(mirroring) => mirroring,
+            None if self.bank & 0x80 != 0 => Mirroring::SingleScreenUpper,
+            None => Mirroring::SingleScreenLower,
+        }
+    }
+
+    /// Writes every flashed sector to `path`, as a 2 byte little endian sector
+    /// number followed by the sector's 4 KiB.
+    fn save_sidecar(&self, path: &Path) -> io::Result<()> {
+        let mut out = Vec::new();
+        for (sector, data) in self.prg_rom.data.chunks(FLASH_SECTOR_SIZE).enumerate() {
+            if self.dirty_sectors[sector] {
+                out.extend((sector as u16).to_le_bytes());
+                out.extend(data);
+                out.resize(out.len() + FLASH_SECTOR_SIZE - data.len(), 0xFF);
+            }
+        }
+        fs::write(path, out)
+    }
+
+    /// Applies a sidecar written by [`Mapper::save_sidecar`] over PRG.
+    /// A missing file is not an error, the game simply has no save yet.
+    fn load_sidecar(&mut self, path: &Path) -> io::Result<()> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let record_size = 2 + FLASH_SECTOR_SIZE;
+        if bytes.len() % record_size != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "flash sidecar is not made of whole sectors",
+            ));
+        }
+        for record in bytes.chunks(record_size) {
+            let sector = u16::from_le_bytes([record[0], record[1]]) as usize;
+            let start = sector * FLASH_SECTOR_SIZE;
+            if sector >= self.dirty_sectors.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("flash sidecar sector {sector} is past the end of PRG"),
+                ));
+            }
+            let end = (start + FLASH_SECTOR_SIZE).min(self.prg_rom.data.len());
+            self.prg_rom.data[start..end].copy_from_slice(&record[2..2 + end - start]);
+            self.dirty_sectors[sector] = true;
+        }
+        Ok(())
+    }
+}
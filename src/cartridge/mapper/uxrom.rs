@@ -0,0 +1,70 @@
+use crate::{
+    bus::{Bus, Byte, Word},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, has_bus_conflicts},
+    },
+};
+
+/// ### UxROM (mapper 2)
+/// Any write to `$8000-$FFFF` selects the 16 KiB bank at `$8000`,
+/// the last bank is fixed at `$C000`. CHR is 8 KiB of RAM on most boards.
+pub struct Uxrom {
+    prg_rom: BankedMemory,
+    chr: BankedMemory,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+}
+
+impl Uxrom {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let chr = BankedMemory::chr(&cartridge, 0x2000, 1);
+        let bus_conflicts = has_bus_conflicts(&cartridge, true);
+        let mirroring = cartridge.header.mirroring;
+        let mut prg_rom = BankedMemory::rom(cartridge.prg_rom, 0x4000, 2);
+        let last = prg_rom.bank_count(1) - 1;
+        prg_rom.map(1, 1, last);
+        Self {
+            prg_rom,
+            chr,
+            mirroring,
+            bus_conflicts,
+        }
+    }
+}
+
+impl Bus for Uxrom {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, mut value: Byte) {
+        if addr >= 0x8000 {
+            if self.bus_conflicts {
+                value &= self.prg_rom.read(addr as usize - 0x8000);
+            }
+            self.prg_rom.map(0, 1, value as usize);
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        if addr >= 0x8000 { 0xFF } else { 0x00 }
+    }
+}
+
+impl Mapper for Uxrom {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
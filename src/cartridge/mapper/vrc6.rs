@@ -0,0 +1,367 @@
+use crate::{
+    audio::SampleSource,
+    bus::{Bus, Byte, Word, clock::Lines},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, banked::BankedMemory, prg_ram_size},
+    },
+};
+
+/// PPU dots per scanline, the IRQ prescaler counts them down 3 per CPU cycle.
+const PRESCALER_PERIOD: i16 = 341;
+/// Level of one VRC6 volume step, so a full volume pulse matches an APU pulse.
+const LEVEL_STEP: f32 = 0.1494 / 15.0;
+
+/// Which CPU address lines reach the chip's A0 and A1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vrc6Variant {
+    /// Mapper 24, A0 and A1 wired straight
+    A,
+    /// Mapper 26, A0 and A1 swapped
+    B,
+}
+
+/// Frequency control shared by the three channels (`$9003`).
+#[derive(Debug, Clone, Copy, Default)]
+struct FrequencyControl {
+    halt: bool,
+    shift: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pulse {
+    volume: Byte,
+    duty: Byte,
+    constant: bool,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: Byte,
+}
+
+impl Pulse {
+    fn new() -> Self {
+        Self {
+            volume: 0,
+            duty: 0,
+            constant: false,
+            period: 0,
+            enabled: false,
+            timer: 0,
+            step: 15,
+        }
+    }
+
+    fn write(&mut self, register: Word, value: Byte) {
+        match register {
+            0 => {
+                self.constant = value & 0x80 != 0;
+                self.duty = (value >> 4) & 0x07;
+                self.volume = value & 0x0F;
+            }
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 15;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, control: FrequencyControl) {
+        if !self.enabled || control.halt {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> control.shift;
+            self.step = self.step.wrapping_sub(1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> Byte {
+        if self.enabled && (self.constant || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sawtooth {
+    rate: Byte,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: Byte,
+    accumulator: Byte,
+}
+
+impl Sawtooth {
+    fn new() -> Self {
+        Self {
+            rate: 0,
+            period: 0,
+            enabled: false,
+            timer: 0,
+            step: 0,
+            accumulator: 0,
+        }
+    }
+
+    fn write(&mut self, register: Word, value: Byte) {
+        match register {
+            0 => self.rate = value & 0x3F,
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    /// The accumulator grows on every other timer clock and resets on the 14th.
+    fn clock(&mut self, control: FrequencyControl) {
+        if !self.enabled || control.halt {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period >> control.shift;
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step & 0x01 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    fn output(&self) -> Byte {
+        self.accumulator >> 3
+    }
+}
+
+/// ### Konami VRC6 (mappers 24 and 26)
+/// | Range | Content |
+/// |-------|---------|
+/// | `$8000-$8003` | 16 KiB PRG bank at `$8000` |
+/// | `$9000-$9003` | Pulse 1, `$9003` frequency control |
+/// | `$A000-$A002` | Pulse 2 |
+/// | `$B000-$B002` | Sawtooth |
+/// | `$B003` | `R...MM..`: PRG-RAM enable, mirroring |
+/// | `$C000-$C003` | 8 KiB PRG bank at `$C000` |
+/// | `$D000-$E003` | 1 KiB CHR banks |
+/// | `$F000-$F002` | IRQ latch, control, acknowledge |
+///
+/// `$E000` is fixed to the last 8 KiB bank. Only the PPU banking mode licensed
+/// games use is implemented: eight 1 KiB CHR banks and CIRAM nametables.
+///
+/// The IRQ counter counts up to `$FF` and reloads from the latch, clocked either
+/// every CPU cycle or once per scanline through a 341 dot prescaler. The board has
+/// to be attached to the CPU, which also clocks the expansion audio: two pulse
+/// channels with 8 duty cycles and a sawtooth, exposed as a [`SampleSource`].
+pub struct Vrc6 {
+    variant: Vrc6Variant,
+    prg_rom: BankedMemory,
+    prg_ram: BankedMemory,
+    chr: BankedMemory,
+    battery: bool,
+    control: Byte,
+    irq_latch: Byte,
+    irq_counter: Byte,
+    irq_prescaler: i16,
+    irq_enabled: bool,
+    irq_enable_after_ack: bool,
+    irq_cycle_mode: bool,
+    irq_pending: bool,
+    frequency: FrequencyControl,
+    pulses: [Pulse; 2],
+    sawtooth: Sawtooth,
+}
+
+impl Vrc6 {
+    pub fn new(cartridge: Cartridge, variant: Vrc6Variant) -> Self {
+        let prg_ram = BankedMemory::ram(prg_ram_size(&cartridge), 0x2000, 1);
+        let chr = BankedMemory::chr(&cartridge, 0x0400, 8);
+        let battery = cartridge.header.battery;
+        let mut prg_rom = BankedMemory::rom(cartridge.prg_rom, 0x2000, 4);
+        let last = prg_rom.bank_count(1) - 1;
+        prg_rom.map(3, 1, last);
+        Self {
+            variant,
+            prg_rom,
+            prg_ram,
+            chr,
+            battery,
+            control: 0,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_prescaler: PRESCALER_PERIOD,
+            irq_enabled: false,
+            irq_enable_after_ack: false,
+            irq_cycle_mode: false,
+            irq_pending: false,
+            frequency: FrequencyControl::default(),
+            pulses: [Pulse::new(); 2],
+            sawtooth: Sawtooth::new(),
+        }
+    }
+
+    /// The register an address decodes to, `A1` and `A0` in the low bits.
+    fn register(&self, addr: Word) -> Word {
+        let addr = addr & 0xF003;
+        match self.variant {
+            Vrc6Variant::A => addr,
+            Vrc6Variant::B => (addr & 0xF000) | (addr & 0x01) << 1 | (addr & 0x02) >> 1,
+        }
+    }
+
+    fn write_register(&mut self, register: Word, value: Byte) {
+        let low = register & 0x03;
+        match register & 0xF000 {
+            0x8000 => self.prg_rom.map(0, 2, (value & 0x0F) as usize),
+            0x9000 if low == 3 => {
+                self.frequency = FrequencyControl {
+                    halt: value & 0x01 != 0,
+                    shift: match value & 0x06 {
+                        0 => 0,
+                        0x02 => 4,
+                        _ => 8,
+                    },
+                }
+            }
+            0x9000 => self.pulses[0].write(low, value),
+            0xA000 => self.pulses[1].write(low, value),
+            0xB000 if low == 3 => self.control = value,
+            0xB000 => self.sawtooth.write(low, value),
+            0xC000 => self.prg_rom.map(2, 1, (value & 0x1F) as usize),
+            0xD000 => self.chr.map(low as usize, 1, value as usize),
+            0xE000 => self.chr.map(4 + low as usize, 1, value as usize),
+            _ => match low {
+                0 => self.irq_latch = value,
+                1 => {
+                    self.irq_enable_after_ack = value & 0x01 != 0;
+                    self.irq_enabled = value & 0x02 != 0;
+                    self.irq_cycle_mode = value & 0x04 != 0;
+                    if self.irq_enabled {
+                        self.irq_counter = self.irq_latch;
+                        self.irq_prescaler = PRESCALER_PERIOD;
+                    }
+                    self.irq_pending = false;
+                }
+                2 => {
+                    self.irq_pending = false;
+                    self.irq_enabled = self.irq_enable_after_ack;
+                }
+                _ => {}
+            },
+        }
+    }
+
+    fn clock_irq(&mut self) {
+        if !self.irq_enabled {
+            return;
+        }
+        if !self.irq_cycle_mode {
+            self.irq_prescaler -= 3;
+            if self.irq_prescaler > 0 {
+                return;
+            }
+            self.irq_prescaler += PRESCALER_PERIOD;
+        }
+        if self.irq_counter == 0xFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_pending = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.control & 0x80 != 0 && !self.prg_ram.data.is_empty()
+    }
+}
+
+impl Bus for Vrc6 {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram.read(addr as usize - 0x6000),
+            0x8000..=0xFFFF => self.prg_rom.read(addr as usize - 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                self.prg_ram.write(addr as usize - 0x6000, value)
+            }
+            0x8000..=0xFFFF => self.write_register(self.register(addr), value),
+            _ => {}
+        }
+    }
+
+    fn driven_bits(&self, addr: Word) -> Byte {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => 0xFF,
+            0x8000..=0xFFFF => 0xFF,
+            _ => 0x00,
+        }
+    }
+}
+
+impl Mapper for Vrc6 {
+    fn ppu_read(&mut self, addr: Word) -> Byte {
+        self.chr.read(addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: Word, value: Byte) {
+        self.chr.write(addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match (self.control >> 2) & 0x03 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn clock(&mut self, lines: &mut Lines) {
+        self.clock_irq();
+        if self.irq_pending {
+            lines.assert_irq();
+        }
+        for pulse in &mut self.pulses {
+            pulse.clock(self.frequency);
+        }
+        self.sawtooth.clock(self.frequency);
+    }
+
+    fn save_ram(&self) -> Option<&[Byte]> {
+        (self.battery && !self.prg_ram.data.is_empty()).then_some(self.prg_ram.data.as_slice())
+    }
+
+    fn sample_source(&self) -> Option<&dyn SampleSource> {
+        Some(self)
+    }
+}
+
+impl SampleSource for Vrc6 {
+    fn sample(&self) -> f32 {
+        let level = self.pulses[0].output() + self.pulses[1].output() + self.sawtooth.output();
+        level as f32 * LEVEL_STEP
+    }
+}
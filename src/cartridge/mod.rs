@@ -0,0 +1,182 @@
+use std::{fmt, fs, io, path::Path};
+
+use crate::{
+    bus::Byte,
+    cartridge::header::{CHR_BANK_SIZE, HEADER_SIZE, Header, PRG_BANK_SIZE, TRAINER_SIZE},
+};
+
+pub mod fds;
+pub mod header;
+pub mod mapper;
+
+/// ### Nametable mirroring
+/// How the PPU's four logical nametables map onto the 2 KiB of CIRAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    /// `$2000 == $2400`, `$2800 == $2C00`
+    Horizontal,
+    /// `$2000 == $2800`, `$2400 == $2C00`
+    Vertical,
+    /// Every nametable shows the first 1 KiB
+    SingleScreenLower,
+    /// Every nametable shows the second 1 KiB
+    SingleScreenUpper,
+    /// The cartridge supplies 2 extra KiB
+    FourScreen,
+}
+
+impl Mirroring {
+    /// 1 KiB page nametable `table` (0-3) shows. Four-screen boards
+    /// supply pages 2 and 3 themselves.
+    pub fn ciram_page(self, table: usize) -> usize {
+        match self {
+            Mirroring::Horizontal => (table >> 1) & 0x01,
+            Mirroring::Vertical => table & 0x01,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => table & 0x03,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    /// The file doesn't start with `NES<EOF>`
+    BadMagic,
+    /// The file is shorter than its header claims
+    Truncated {
+        expected: usize,
+        actual: usize,
+    },
+    /// A NES 2.0 exponent size doesn't fit in memory
+    SizeOverflow,
+    /// The header declares no PRG-ROM
+    NoPrgRom,
+    /// No board is implemented for this mapper number
+    UnsupportedMapper(u16),
+    /// The FDS BIOS image isn't 8 KiB
+    BadBios(usize),
+    Io(io::Error),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CartridgeError::BadMagic => write!(f, "not an iNES file"),
+            CartridgeError::Truncated { expected, actual } => {
+                write!(f, "file truncated: expected {expected} bytes, got {actual}")
+            }
+            CartridgeError::SizeOverflow => write!(f, "ROM size does not fit in memory"),
+            CartridgeError::NoPrgRom => write!(f, "cartridge has no PRG-ROM"),
+            CartridgeError::UnsupportedMapper(mapper) => write!(f, "mapper {mapper} not supported"),
+            CartridgeError::BadBios(size) => {
+                write!(f, "FDS BIOS must be 8192 bytes, got {size}")
+            }
+            CartridgeError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CartridgeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CartridgeError {
+    fn from(err: io::Error) -> Self {
+        CartridgeError::Io(err)
+    }
+}
+
+/// ### Cartridge image
+/// The contents of an `.nes` file split into its memories.
+/// When the header declares no CHR-ROM, `chr_rom` is empty and
+/// the board is expected to provide `header.chr_ram_size` bytes of CHR-RAM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cartridge {
+    pub header: Header,
+    pub trainer: Option<Vec<Byte>>,
+    pub prg_rom: Vec<Byte>,
+    pub chr_rom: Vec<Byte>,
+    pub misc_rom: Vec<Byte>,
+}
+
+impl Cartridge {
+    pub fn from_bytes(bytes: &[Byte]) -> Result<Self, CartridgeError> {
+        let header = Header::parse(bytes)?;
+        let trainer_size = if header.trainer { TRAINER_SIZE } else { 0 };
+        let prg_start = HEADER_SIZE + trainer_size;
+        let chr_start = prg_start
+            .checked_add(header.prg_rom_size)
+            .ok_or(CartridgeError::SizeOverflow)?;
+        let chr_end = chr_start
+            .checked_add(header.chr_rom_size)
+            .ok_or(CartridgeError::SizeOverflow)?;
+        if bytes.len() < chr_end {
+            return Err(CartridgeError::Truncated {
+                expected: chr_end,
+                actual: bytes.len(),
+            });
+        }
+        Ok(Self {
+            header,
+            trainer: header
+                .trainer
+                .then(|| bytes[HEADER_SIZE..prg_start].to_vec()),
+            prg_rom: bytes[prg_start..chr_start].to_vec(),
+            chr_rom: bytes[chr_start..chr_end].to_vec(),
+            misc_rom: if header.misc_roms != 0 {
+                bytes[chr_end..].to_vec()
+            } else {
+                Vec::new()
+            },
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CartridgeError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Number of `size` byte banks in PRG-ROM (at least one).
+    pub fn prg_bank_count(&self, size: usize) -> usize {
+        self.prg_rom.len().div_ceil(size).max(1)
+    }
+
+    /// PRG-ROM bank `bank` of `size` bytes, wrapping around like the address lines of a board would.
+    pub fn prg_bank(&self, bank: usize, size: usize) -> &[Byte] {
+        bank_slice(&self.prg_rom, bank, size)
+    }
+
+    /// Number of `size` byte banks in CHR-ROM, `0` for CHR-RAM boards.
+    pub fn chr_bank_count(&self, size: usize) -> usize {
+        self.chr_rom.len().div_ceil(size)
+    }
+
+    /// CHR-ROM bank `bank` of `size` bytes, wrapping around.
+    pub fn chr_bank(&self, bank: usize, size: usize) -> &[Byte] {
+        bank_slice(&self.chr_rom, bank, size)
+    }
+
+    /// 16 KiB PRG banks as counted by the iNES header.
+    pub fn prg_banks(&self) -> std::slice::Chunks<'_, Byte> {
+        self.prg_rom.chunks(PRG_BANK_SIZE)
+    }
+
+    /// 8 KiB CHR banks as counted by the iNES header.
+    pub fn chr_banks(&self) -> std::slice::Chunks<'_, Byte> {
+        self.chr_rom.chunks(CHR_BANK_SIZE)
+    }
+}
+
+fn bank_slice(data: &[Byte], bank: usize, size: usize) -> &[Byte] {
+    if data.is_empty() {
+        return data;
+    }
+    let count = data.len().div_ceil(size);
+    let start = (bank % count) * size;
+    &data[start..(start + size).min(data.len())]
+}
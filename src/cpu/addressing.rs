@@ -46,7 +46,9 @@ impl CPU {
     }
 
     /// ### Addressing Modes - Implied
+    /// The 6502 still reads the byte after the opcode, then ignores it.
     pub fn imp(&mut self) -> Byte {
+        self.dummy_read(self.pc);
         self.fetched = self.a;
         0
     }
@@ -64,15 +66,20 @@ impl CPU {
     }
 
     /// ### Addressing Modes - Zero page with X offset
+    /// The 6502 reads the unindexed address while it adds the offset.
     pub fn zpx(&mut self) -> Byte {
-        let addr: Byte = self.fetch_byte().wrapping_add(self.x);
+        let base = self.fetch_byte();
+        self.dummy_read(base as Word);
+        let addr: Byte = base.wrapping_add(self.x);
         self.addr_abs = addr as Word;
         self.addr_abs &= 0x00FF;
         0
     }
     /// ### Addressing Modes - Zero page with Y offset
     pub fn zpy(&mut self) -> Byte {
-        let addr: Byte = self.fetch_byte().wrapping_add(self.y);
+        let base = self.fetch_byte();
+        self.dummy_read(base as Word);
+        let addr: Byte = base.wrapping_add(self.y);
         self.addr_abs = addr as Word;
         self.addr_abs &= 0x00FF;
         0
@@ -87,22 +94,14 @@ impl CPU {
         self.addr_abs = self.fetch_word();
         let [_, hi] = self.addr_abs.to_le_bytes();
         self.addr_abs = self.addr_abs.wrapping_add(self.x as Word);
-        if Self::page_crossed(self.addr_abs, hi) {
-            1
-        } else {
-            0
-        }
+        self.page_cross_penalty(hi)
     }
     /// ### Addressing Modes - Absolute with Y offset
     pub fn aby(&mut self) -> Byte {
         self.addr_abs = self.fetch_word();
         let [_, hi] = self.addr_abs.to_le_bytes();
         self.addr_abs = self.addr_abs.wrapping_add(self.y as Word);
-        if Self::page_crossed(self.addr_abs, hi) {
-            1
-        } else {
-            0
-        }
+        self.page_cross_penalty(hi)
     }
     /// ### Addressing Modes - Indirect (aka Pointers)
     /// only used with **JMP** instruction
@@ -115,6 +114,7 @@ impl CPU {
     /// ### Addressing Modes - Indexed Indirect (X)
     pub fn idx(&mut self) -> Byte {
         let mut addr = self.fetch_byte();
+        self.dummy_read(addr as Word);
         addr = addr.wrapping_add(self.x);
         self.addr_abs = self.read_word_zp(addr);
         0
@@ -126,7 +126,14 @@ impl CPU {
         let addr = self.read_word_zp(zp_addr);
         let [_, hi] = addr.to_le_bytes();
         self.addr_abs = addr.wrapping_add(self.y as Word);
+        self.page_cross_penalty(hi)
+    }
+    /// When indexing crosses a page the 6502 first reads from the un-carried
+    /// address, then fixes the high byte, costing an extra cycle.
+    fn page_cross_penalty(&mut self, hi: Byte) -> Byte {
         if Self::page_crossed(self.addr_abs, hi) {
+            let [lo, _] = self.addr_abs.to_le_bytes();
+            self.dummy_read(Word::from_le_bytes([lo, hi]));
             1
         } else {
             0
@@ -0,0 +1,114 @@
+use crate::{
+    bus::{Byte, Word},
+    cpu::{Access, CPU},
+};
+
+// * OAM DMA
+
+/// Writing a page number here copies that page to the PPU's OAM
+pub const OAM_DMA: Word = 0x4014;
+/// PPU register the DMA writes every byte to
+pub const OAM_DATA: Word = 0x2004;
+/// Halt and dummy cycles a DMC fetch waits before its read
+const DMC_SETUP_CYCLES: u8 = 2;
+
+/// ### OAM DMA transfer
+/// The 2A03 halts the CPU on the cycle after the `$4014` write, then alternates
+/// reading `$XX00-$XXFF` on get (even) cycles and writing `$2004` on put (odd)
+/// cycles. A halt landing right before a put cycle waits one more cycle to align,
+/// so a transfer takes 513 or 514 cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OamDma {
+    page: Byte,
+    halted: bool,
+    /// Read and write cycles done so far, 512 in all
+    transfers: u16,
+    value: Byte,
+}
+
+impl OamDma {
+    pub fn new(page: Byte) -> Self {
+        Self {
+            page,
+            halted: false,
+            transfers: 0,
+            value: 0,
+        }
+    }
+}
+
+/// ### DMC DMA
+/// A sample fetch asked by a device through [`crate::bus::clock::Lines::request_dma`].
+/// It halts the CPU, waits a dummy cycle, then reads on the next get cycle:
+/// 3 or 4 stolen cycles. During an OAM DMA the halt and dummy cycles overlap the
+/// transfer and the read takes over a get cycle, which then costs OAM DMA a
+/// realignment cycle, 2 cycles in all.
+///
+/// The CPU only yields the bus between instructions, so the halted cycles repeat
+/// the opcode fetch instead of whatever read the instruction was at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmcDma {
+    setup: u8,
+}
+
+impl CPU {
+    /// Runs one cycle of a pending DMA, returns `false` when there is none.
+    pub(crate) fn dma_cycle(&mut self) -> bool {
+        if self.dmc_dma.is_none() && self.scheduler.dma_request().is_some() {
+            self.dmc_dma = Some(DmcDma { setup: 0 });
+        }
+        if self.oam_dma.is_none() && self.dmc_dma.is_none() {
+            return false;
+        }
+        self.bus_accesses = 0;
+        let get_cycle = self.general_cycles.is_multiple_of(2);
+        let dmc_ready = self
+            .dmc_dma
+            .is_some_and(|dmc| dmc.setup == DMC_SETUP_CYCLES);
+
+        if dmc_ready && get_cycle {
+            self.dmc_read();
+        } else if !self.oam_transfer(get_cycle) {
+            // Halt, dummy and alignment cycles repeat the read the CPU was stopped on
+            self.dummy_read(self.pc);
+        }
+        if let Some(dmc) = self.dmc_dma.as_mut() {
+            dmc.setup = (dmc.setup + 1).min(DMC_SETUP_CYCLES);
+        }
+        self.end_cycle();
+        true
+    }
+
+    fn dmc_read(&mut self) {
+        self.dmc_dma = None;
+        if let Some(addr) = self.scheduler.dma_request() {
+            let value = self.bus_read(addr, Access::DmaRead);
+            self.scheduler.complete_dma(value);
+        }
+    }
+
+    /// Runs the OAM DMA's part of the cycle, returns whether it used the bus.
+    fn oam_transfer(&mut self, get_cycle: bool) -> bool {
+        let Some(mut dma) = self.oam_dma else {
+            return false;
+        };
+        let mut used = false;
+        if !dma.halted {
+            dma.halted = true;
+        } else if dma.transfers % 2 == 0 {
+            // Waits for a get cycle otherwise
+            if get_cycle {
+                let addr = Word::from_le_bytes([(dma.transfers / 2) as Byte, dma.page]);
+                dma.value = self.bus_read(addr, Access::DmaRead);
+                dma.transfers += 1;
+                used = true;
+            }
+        } else {
+            self.bus_write(OAM_DATA, dma.value, Access::DmaWrite);
+            dma.transfers += 1;
+            used = true;
+        }
+        self.oam_dma = (dma.transfers < 512).then_some(dma);
+        used
+    }
+}
@@ -256,6 +256,21 @@ impl CPU {
         self.fetched
     }
 
+    /// Indexed stores and read-modify-writes always read the un-carried address
+    /// before their own access. When the index crossed a page that read was
+    /// already made while resolving the address, otherwise it is `addr_abs`.
+    fn indexed_dummy_read(&mut self) {
+        let index = match Self::INSTRUCTIONS[self.opcode as usize].addr_mode {
+            AddrMode::ABX => self.x,
+            AddrMode::ABY | AddrMode::IDY => self.y,
+            _ => return,
+        };
+        let [_, hi] = self.addr_abs.wrapping_sub(index as Word).to_le_bytes();
+        if !Self::page_crossed(self.addr_abs, hi) {
+            self.dummy_read(self.addr_abs);
+        }
+    }
+
     fn set_a_flags(&mut self) {
         self.flag.set(Flag::ZERO, self.a == 0);
         self.flag.set(Flag::NEGATIVE, (self.a & 0x80) != 0);
@@ -290,26 +305,35 @@ impl CPU {
         1
     }
     fn sta(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.write(self.addr_abs, self.a);
         0
     }
     fn stx(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.write(self.addr_abs, self.x);
         0
     }
     fn sty(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.write(self.addr_abs, self.y);
         0
     }
+    /// The stack read comes between the two operand bytes on the 6502,
+    /// here the operand is already fetched.
     fn jsr(&mut self) -> Byte {
+        self.stack_dummy_read();
         self.pc -= 1;
         self.push_word(self.pc);
         self.pc = self.addr_abs;
         0
     }
     fn rts(&mut self) -> Byte {
+        self.stack_dummy_read();
         let addr = self.pull_word();
-        self.pc = addr + 1;
+        // read while PC is incremented past the JSR operand
+        self.dummy_read(addr);
+        self.pc = addr.wrapping_add(1);
         0
     }
     fn jmp(&mut self) -> Byte {
@@ -334,11 +358,13 @@ impl CPU {
         0
     }
     fn pla(&mut self) -> Byte {
+        self.stack_dummy_read();
         self.a = self.pull_byte();
         self.set_a_flags();
         0
     }
     fn plp(&mut self) -> Byte {
+        self.stack_dummy_read();
         let bits = self.pull_byte();
         self.flag = Flag::from_bits_truncate(bits);
         0
@@ -389,19 +415,21 @@ impl CPU {
         0
     }
     fn inc(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.fetch();
         let tmp = self.fetched.wrapping_add(1);
+        self.dummy_write(self.addr_abs, self.fetched);
         self.write(self.addr_abs, tmp);
-        self.flag = Flag::empty();
         self.flag.set(Flag::ZERO, tmp == 0);
         self.flag.set(Flag::NEGATIVE, (tmp & 0x80) != 0);
         0
     }
     fn dec(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.fetch();
         let tmp = self.fetched.wrapping_sub(1);
+        self.dummy_write(self.addr_abs, self.fetched);
         self.write(self.addr_abs, tmp);
-        self.flag = Flag::empty();
         self.flag.set(Flag::ZERO, tmp == 0);
         self.flag.set(Flag::NEGATIVE, (tmp & 0x80) != 0);
         0
@@ -428,10 +456,16 @@ impl CPU {
     }
     fn branch_helper(&mut self, condition: bool) {
         if condition {
+            // A taken branch reads the next opcode, then the target before
+            // its high byte is fixed when the branch crosses a page
             self.cycles += 1;
+            self.dummy_read(self.pc);
             self.addr_abs = self.pc.wrapping_add(self.addr_rel);
             if (self.addr_abs & 0xFF00) != (self.pc & 0xFF00) {
                 self.cycles += 1;
+                let [lo, _] = self.addr_abs.to_le_bytes();
+                let [_, hi] = self.pc.to_le_bytes();
+                self.dummy_read(Word::from_le_bytes([lo, hi]));
             }
             self.pc = self.addr_abs;
         }
@@ -497,8 +531,16 @@ impl CPU {
         self.flag.insert(Flag::INTERRUPT_DISABLE);
         0
     }
+    fn decimal_enabled(&self) -> bool {
+        self.flag.contains(Flag::DECIMAL_MODE) && self.profile.has_decimal_mode()
+    }
+
     fn adc(&mut self) -> Byte {
         self.fetch();
+        if self.decimal_enabled() {
+            self.adc_decimal();
+            return 0;
+        }
         let mut tmp: Word = self.a as Word;
         tmp += self.fetched as Word;
         tmp += self.flag.contains(Flag::CARRY) as Word;
@@ -510,6 +552,27 @@ impl CPU {
         self.a = tmp as Byte;
         0
     }
+    /// NMOS decimal add: `Z` follows the binary sum, `N` and `V` the sum
+    /// before the high nibble is adjusted.
+    fn adc_decimal(&mut self) {
+        let carry = self.flag.contains(Flag::CARRY) as Word;
+        let (a, m) = (self.a as Word, self.fetched as Word);
+        let mut lo = (a & 0x0F) + (m & 0x0F) + carry;
+        if lo > 0x09 {
+            lo += 0x06;
+        }
+        let mut hi = (a >> 4) + (m >> 4) + (lo > 0x0F) as Word;
+        let tmp = ((hi << 4) | (lo & 0x0F)) as Byte;
+        let is_overflow = ((!(self.a ^ self.fetched) & (self.a ^ tmp)) & 0x80) != 0;
+        self.flag.set(Flag::ZERO, (a + m + carry) & 0x00FF == 0);
+        self.flag.set(Flag::NEGATIVE, (tmp & 0x80) != 0);
+        self.flag.set(Flag::OVERFLOW, is_overflow);
+        if hi > 0x09 {
+            hi += 0x06;
+        }
+        self.flag.set(Flag::CARRY, hi > 0x0F);
+        self.a = ((hi << 4) | (lo & 0x0F)) as Byte;
+    }
     fn sbc(&mut self) -> Byte {
         self.fetch();
         let mut tmp: Word = self.a as Word;
@@ -519,10 +582,29 @@ impl CPU {
         self.flag.set(Flag::OVERFLOW, is_overflow);
         self.flag.set(Flag::ZERO, (tmp & 0x00FF) == 0);
         self.flag.set(Flag::NEGATIVE, (tmp & 0x80) != 0);
+        if self.decimal_enabled() {
+            self.a = self.sbc_decimal();
+        } else {
+            self.a = tmp as Byte;
+        }
         self.flag.set(Flag::CARRY, (tmp & 0x100) != 0);
-        self.a = tmp as Byte;
         0
     }
+    /// NMOS decimal subtract, the flags are the ones of the binary subtraction.
+    fn sbc_decimal(&self) -> Byte {
+        let borrow = !self.flag.contains(Flag::CARRY) as i16;
+        let (a, m) = (self.a as i16, self.fetched as i16);
+        let mut lo = (a & 0x0F) - (m & 0x0F) - borrow;
+        let mut hi = (a >> 4) - (m >> 4);
+        if lo < 0 {
+            lo -= 0x06;
+            hi -= 1;
+        }
+        if hi < 0 {
+            hi -= 0x06;
+        }
+        (((hi << 4) as Byte) & 0xF0) | (lo as Byte & 0x0F)
+    }
     fn cmp(&mut self) -> Byte {
         self.fetch();
         let tmp = self.a.wrapping_sub(self.fetched);
@@ -548,6 +630,7 @@ impl CPU {
         0
     }
     fn asl(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.fetch();
         let tmp = (self.fetched as Word).wrapping_shl(1);
         self.flag.set(Flag::CARRY, (tmp & 0x100) != 0);
@@ -556,11 +639,13 @@ impl CPU {
         if Self::INSTRUCTIONS[self.opcode as usize].addr_mode == AddrMode::IMP {
             self.a = tmp as Byte;
         } else {
+            self.dummy_write(self.addr_abs, self.fetched);
             self.write(self.addr_abs, tmp as Byte);
         }
         0
     }
     fn lsr(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.fetch();
         self.flag.set(Flag::CARRY, (self.fetched & 0x01) != 0);
         let tmp = (self.fetched as Word).wrapping_shr(1);
@@ -569,11 +654,13 @@ impl CPU {
         if Self::INSTRUCTIONS[self.opcode as usize].addr_mode == AddrMode::IMP {
             self.a = tmp as Byte;
         } else {
+            self.dummy_write(self.addr_abs, self.fetched);
             self.write(self.addr_abs, tmp as Byte);
         }
         0
     }
     fn rol(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.fetch();
         let tmp = (self.fetched as Word).wrapping_shl(1) | self.flag.contains(Flag::CARRY) as Word;
         self.flag.set(Flag::CARRY, (self.fetched & 0x80) != 0);
@@ -582,11 +669,13 @@ impl CPU {
         if Self::INSTRUCTIONS[self.opcode as usize].addr_mode == AddrMode::IMP {
             self.a = tmp as Byte;
         } else {
+            self.dummy_write(self.addr_abs, self.fetched);
             self.write(self.addr_abs, tmp as Byte);
         }
         0
     }
     fn ror(&mut self) -> Byte {
+        self.indexed_dummy_read();
         self.fetch();
         let tmp = (self.fetched as Word).wrapping_shr(1)
             | (self.flag.contains(Flag::CARRY) as Word).wrapping_shl(7);
@@ -596,6 +685,7 @@ impl CPU {
         if Self::INSTRUCTIONS[self.opcode as usize].addr_mode == AddrMode::IMP {
             self.a = tmp as Byte;
         } else {
+            self.dummy_write(self.addr_abs, self.fetched);
             self.write(self.addr_abs, tmp as Byte);
         }
         0
@@ -613,6 +703,7 @@ impl CPU {
         0
     }
     fn rti(&mut self) -> Byte {
+        self.stack_dummy_read();
         self.flag = Flag::from_bits_truncate(self.pull_byte());
         self.flag.remove(Flag::BREAK_COMMAND);
         self.pc = self.pull_word();
@@ -0,0 +1,53 @@
+use crate::{
+    bus::{Byte, Word},
+    cpu::{CPU, Flag},
+};
+
+// * Interrupts
+
+pub const NMI_VECTOR: Word = 0xFFFA;
+pub const RESET_VECTOR: Word = 0xFFFC;
+pub const IRQ_VECTOR: Word = 0xFFFE;
+
+/// Cycles taken to push the state and jump through a vector
+pub const INTERRUPT_CYCLES: Byte = 7;
+
+impl CPU {
+    /// Latches a non-maskable interrupt, serviced before the next instruction.
+    pub fn nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    /// Drives the external IRQ line, serviced while held and `I` is clear.
+    pub fn set_irq(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+
+    /// Holds the CPU through RDY for `cycles` cycles before its next instruction.
+    pub fn stall(&mut self, cycles: u32) {
+        self.stall_cycles += cycles;
+    }
+
+    /// Returns the vector of the interrupt that should be taken now, if any.
+    pub(crate) fn poll_interrupt(&mut self) -> Option<Word> {
+        let nmi_edge = self.scheduler.take_nmi();
+        if self.nmi_pending || nmi_edge {
+            self.nmi_pending = false;
+            return Some(NMI_VECTOR);
+        }
+        let irq = self.irq_line || self.scheduler.irq();
+        if irq && !self.flag.contains(Flag::INTERRUPT_DISABLE) {
+            return Some(IRQ_VECTOR);
+        }
+        None
+    }
+
+    /// Pushes `PC` and the status (with `B` clear) then jumps through `vector`.
+    pub(crate) fn interrupt(&mut self, vector: Word) {
+        self.push_word(self.pc);
+        let status = (self.flag.bits() & !Flag::BREAK_COMMAND.bits()) | Flag::UNUSED.bits();
+        self.push_byte(status);
+        self.flag.insert(Flag::INTERRUPT_DISABLE);
+        self.pc = self.read_word(vector);
+    }
+}
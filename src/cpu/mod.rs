@@ -1,13 +1,24 @@
 use std::collections::BTreeMap;
 
 use crate::{
-    bus::{Bus, Byte, Word},
-    cpu::addressing::AddrMode,
+    bus::{
+        Bus, Byte, Word,
+        clock::{Clocked, Scheduler},
+    },
+    cpu::{
+        addressing::AddrMode,
+        dma::{DmcDma, OAM_DMA, OamDma},
+        interrupt::INTERRUPT_CYCLES,
+        observer::Observers,
+    },
 };
 use bitflags::{Flags, bitflags};
 
 pub mod addressing;
+pub mod dma;
 pub mod instructions;
+pub mod interrupt;
+pub mod observer;
 pub mod stack;
 
 bitflags! {
@@ -23,9 +34,43 @@ bitflags! {
     }
 }
 
+/// ### CPU profile
+/// Which 6502 derivative is being emulated. [`CPU::new`] gets the NMOS 6502,
+/// so `ADC` and `SBC` do BCD arithmetic while the decimal flag is set; use
+/// [`Profile::Ricoh2A03`] for the binary results whatever the flag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// Original NMOS 6502, `ADC`/`SBC` honor the decimal flag
+    #[default]
+    Nmos6502,
+    /// Ricoh 2A03/2A07 used in the NES, the BCD circuitry is cut off
+    /// so the decimal flag can be set but has no effect
+    Ricoh2A03,
+}
+
+impl Profile {
+    pub fn has_decimal_mode(self) -> bool {
+        match self {
+            Profile::Nmos6502 => true,
+            Profile::Ricoh2A03 => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Access {
     Read,
     Write,
+    /// Opcode fetch (the 6502 `SYNC` cycle)
+    Fetch,
+    /// Read whose value the CPU throws away (e.g. page crossing fix-ups)
+    DummyRead,
+    /// Write of the unmodified value done by read-modify-write instructions
+    DummyWrite,
+    /// Read made by the DMA unit while the CPU is halted
+    DmaRead,
+    /// Write made by the DMA unit while the CPU is halted
+    DmaWrite,
 }
 
 /// This a CPU struct that emulate the 6502
@@ -66,6 +111,19 @@ pub struct CPU {
     pub bus: Option<Box<dyn Bus>>,
     pub general_cycles: u64,
     pub cycles: Byte,
+    /// ### Data bus latch
+    /// last value transferred by a CPU read or write
+    pub data_bus: Byte,
+    pub profile: Profile,
+    pub irq_line: bool,
+    pub nmi_pending: bool,
+    pub stall_cycles: u32,
+    /// OAM DMA started by a `$4014` write, Ricoh profile only
+    oam_dma: Option<OamDma>,
+    dmc_dma: Option<DmcDma>,
+    scheduler: Scheduler,
+    observers: Observers,
+    bus_accesses: u64,
 }
 
 impl Default for CPU {
@@ -90,8 +148,25 @@ impl CPU {
             bus: None,
             general_cycles: 0,
             cycles: 0,
+            data_bus: 0,
+            profile: Profile::default(),
+            irq_line: false,
+            nmi_pending: false,
+            stall_cycles: 0,
+            oam_dma: None,
+            dmc_dma: None,
+            scheduler: Scheduler::new(),
+            observers: Observers::default(),
+            bus_accesses: 0,
+        }
+    }
+    pub fn with_profile(profile: Profile) -> Self {
+        Self {
+            profile,
+            ..Self::new()
         }
     }
+
     pub fn reset(&mut self) {
         self.pc = 0xFFFC;
         self.sp = 0xFD;
@@ -107,41 +182,86 @@ impl CPU {
         }
     }
 
+    /// Attaches a device that is ticked once for every cycle the CPU consumes.
+    pub fn attach_device(&mut self, device: Box<dyn Clocked>) {
+        self.scheduler.attach(device);
+    }
+
     pub fn read_byte(&mut self, addr: Word) -> Byte {
+        self.bus_read(addr, Access::Read)
+    }
+
+    /// Reads `addr` the way a debugger would, without side effects on the devices behind the bus.
+    pub fn peek(&mut self, addr: Word) -> Byte {
         match self.bus {
-            Some(ref mut bus) => bus.read(addr, false),
+            Some(ref mut bus) => bus.peek(addr),
             _ => panic!("You must connect to a bus first"),
         }
     }
 
     pub fn read(&mut self, addr: Word, read_only: bool) -> Byte {
-        match self.bus {
-            Some(ref mut bus) => bus.read(addr, read_only),
-            _ => panic!("You must connect to a bus first"),
+        if read_only {
+            self.peek(addr)
+        } else {
+            self.read_byte(addr)
         }
     }
 
     pub fn write(&mut self, addr: Word, data: Byte) {
+        self.bus_write(addr, data, Access::Write);
+    }
+
+    fn bus_read(&mut self, addr: Word, access: Access) -> Byte {
+        let data = match self.bus {
+            Some(ref mut bus) => bus.read(addr, false),
+            _ => panic!("You must connect to a bus first"),
+        };
+        self.data_bus = data;
+        self.notify(addr, data, access);
+        data
+    }
+
+    fn bus_write(&mut self, addr: Word, data: Byte, access: Access) {
         match self.bus {
             Some(ref mut bus) => {
                 bus.write(addr, data);
             }
             _ => panic!("You must connect to a bus first"),
         }
+        if addr == OAM_DMA && self.profile == Profile::Ricoh2A03 {
+            self.oam_dma = Some(OamDma::new(data));
+        }
+        self.data_bus = data;
+        self.notify(addr, data, access);
+    }
+
+    fn dummy_read(&mut self, addr: Word) {
+        self.bus_read(addr, Access::DummyRead);
+    }
+
+    fn dummy_write(&mut self, addr: Word, data: Byte) {
+        self.bus_write(addr, data, Access::DummyWrite);
     }
 
+    /// Runs a whole instruction (or interrupt sequence), including any RDY stall
+    /// or DMA pending before it, and returns the cycles it took.
     pub fn execute(&mut self) -> i32 {
         let mut cycles: i32 = 0;
-        self.opcode = self.fetch_byte();
-        let ins = &Self::INSTRUCTIONS[self.opcode as usize];
-        let additional_1 = self.resolve_addr(ins.addr_mode);
-        let additional_2 = (ins.operate)(self);
-        self.cycles += ins.cycles;
-        self.cycles += additional_1 & additional_2;
+        loop {
+            if self.dma_cycle() {
+                cycles += 1;
+            } else if self.take_stall() {
+                cycles += 1;
+                self.end_cycle();
+            } else {
+                break;
+            }
+        }
+        self.step();
         while self.cycles != 0 {
             cycles += 1;
-            self.general_cycles += 1;
             self.cycles -= 1;
+            self.end_cycle();
         }
         cycles
     }
@@ -149,32 +269,68 @@ impl CPU {
     /// Advances the CPU by a single clock cycle.
     pub fn clock(&mut self) {
         if self.cycles == 0 {
-            self.opcode = self.fetch_byte();
-            let ins = &Self::INSTRUCTIONS[self.opcode as usize];
-            let additional_1 = self.resolve_addr(ins.addr_mode);
-            let additional_2 = (ins.operate)(self);
-            self.cycles += ins.cycles;
-            self.cycles += additional_1 & additional_2;
+            if self.dma_cycle() {
+                return;
+            }
+            if self.take_stall() {
+                self.end_cycle();
+                return;
+            }
+            self.step();
         }
         self.cycles -= 1;
+        self.end_cycle();
+    }
+
+    /// Starts the next interrupt or instruction, leaving its length in `cycles`.
+    fn step(&mut self) {
+        if let Some(vector) = self.poll_interrupt() {
+            self.bus_accesses = 0;
+            self.interrupt(vector);
+            self.cycles += INTERRUPT_CYCLES;
+            return;
+        }
+        self.opcode = self.fetch_opcode();
+        let ins = &Self::INSTRUCTIONS[self.opcode as usize];
+        let additional_1 = self.resolve_addr(ins.addr_mode);
+        let additional_2 = (ins.operate)(self);
+        self.cycles += ins.cycles;
+        self.cycles += additional_1 & additional_2;
+    }
+
+    /// Consumes one RDY stall cycle, if any is pending.
+    fn take_stall(&mut self) -> bool {
+        self.stall_cycles += self.scheduler.take_stall();
+        if self.stall_cycles == 0 {
+            return false;
+        }
+        self.stall_cycles -= 1;
+        true
+    }
+
+    fn end_cycle(&mut self) {
         self.general_cycles += 1;
+        if !self.scheduler.is_empty() {
+            self.scheduler.tick();
+        }
     }
 
     pub fn load_program(&mut self, program: &[Byte]) {
-        let mut iter = program.iter();
-        if program.len() >= 2 {
-            let mut start_addr = {
-                let mut tmp = *iter.next().unwrap() as Word;
-                tmp |= (*iter.next().unwrap() as Word) << 8;
-                tmp
-            };
-            for &v in iter {
-                self.write(start_addr, v);
-                start_addr += 1;
+        if let [lo, hi, data @ ..] = program {
+            let start_addr = Word::from_le_bytes([*lo, *hi]);
+            for (offset, &v) in data.iter().enumerate() {
+                self.write(start_addr.wrapping_add(offset as Word), v);
             }
         }
     }
 
+    fn fetch_opcode(&mut self) -> Byte {
+        self.bus_accesses = 0;
+        let data = self.bus_read(self.pc, Access::Fetch);
+        self.pc = self.pc.wrapping_add(1);
+        data
+    }
+
     fn fetch_byte(&mut self) -> Byte {
         let data = self.read_byte(self.pc);
         self.pc = self.pc.wrapping_add(1);
@@ -214,7 +370,7 @@ impl CPU {
         while addr <= stop as u32 {
             line_addr = addr;
             let mut ins = format!("${:04X}: ", line_addr);
-            let opcode = self.read(addr as Word, true);
+            let opcode = self.peek(addr as Word);
             addr += 1;
             ins += Self::INSTRUCTIONS[opcode as usize].name;
             ins += " ";
@@ -223,65 +379,65 @@ impl CPU {
                     ins += "{IMP}";
                 }
                 AddrMode::IMM => {
-                    let v = self.read(addr as Word, true);
+                    let v = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("#${v:02X} {{IMM}}");
                 }
                 AddrMode::ZPG => {
-                    let byte = self.read(addr as Word, true);
+                    let byte = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("${:02X} {{ZPG}}", byte);
                 }
                 AddrMode::ZPX => {
-                    let byte = self.read(addr as Word, true);
+                    let byte = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("${:02X}, X {{ZPX}}", byte);
                 }
                 AddrMode::ZPY => {
-                    let byte = self.read(addr as Word, true);
+                    let byte = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("${:02X}, Y {{ZPY}}", byte);
                 }
                 AddrMode::IDX => {
-                    let byte = self.read(addr as Word, true);
+                    let byte = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("(${:02X}, X) {{IDX}}", byte);
                 }
                 AddrMode::IDY => {
-                    let byte = self.read(addr as Word, true);
+                    let byte = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("(${:02X}, Y) {{IDY}}", byte);
                 }
                 AddrMode::ABS => {
-                    let lo = self.read(addr as Word, true);
+                    let lo = self.peek(addr as Word);
                     addr += 1;
-                    let hi = self.read(addr as Word, true);
+                    let hi = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("${:04X} {{ABS}}", Word::from_le_bytes([lo, hi]));
                 }
                 AddrMode::ABX => {
-                    let lo = self.read(addr as Word, true);
+                    let lo = self.peek(addr as Word);
                     addr += 1;
-                    let hi = self.read(addr as Word, true);
+                    let hi = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("${:04X}, X {{ABX}}", Word::from_le_bytes([lo, hi]));
                 }
                 AddrMode::ABY => {
-                    let lo = self.read(addr as Word, true);
+                    let lo = self.peek(addr as Word);
                     addr += 1;
-                    let hi = self.read(addr as Word, true);
+                    let hi = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("${:04X}, Y {{ABY}}", Word::from_le_bytes([lo, hi]));
                 }
                 AddrMode::IND => {
-                    let lo = self.read(addr as Word, true);
+                    let lo = self.peek(addr as Word);
                     addr += 1;
-                    let hi = self.read(addr as Word, true);
+                    let hi = self.peek(addr as Word);
                     addr += 1;
                     ins += &format!("(${:04X}) {{IND}}", Word::from_le_bytes([lo, hi]));
                 }
                 AddrMode::REL => {
-                    let byte = self.read(addr as Word, true) as i8;
+                    let byte = self.peek(addr as Word) as i8;
                     addr += 1;
                     ins += &format!(
                         "${:02X} [${:04X}] {{REL}}",
@@ -0,0 +1,92 @@
+use crate::{
+    bus::{Byte, Word},
+    cpu::{Access, CPU},
+};
+
+// * Bus Observers
+
+/// A single transfer on the CPU bus, as seen by an observer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusEvent {
+    pub addr: Word,
+    pub value: Byte,
+    pub access: Access,
+    /// ### Cycle of the access
+    /// `general_cycles` at the start of the instruction plus the number of
+    /// bus accesses the instruction has made so far.
+    pub cycle: u64,
+}
+
+/// Handle returned when registering an observer, used to remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u32);
+
+pub type Observer = Box<dyn FnMut(&BusEvent)>;
+
+/// The observers installed on a CPU bus port.
+/// Dispatch is skipped entirely while the list is empty.
+#[derive(Default)]
+pub struct Observers {
+    next_id: u32,
+    list: Vec<(ObserverId, Observer)>,
+}
+
+impl Observers {
+    pub fn add(&mut self, observer: Observer) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        self.list.push((id, observer));
+        id
+    }
+
+    pub fn remove(&mut self, id: ObserverId) -> bool {
+        let len = self.list.len();
+        self.list.retain(|(other, _)| *other != id);
+        self.list.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    fn notify(&mut self, event: &BusEvent) {
+        for (_, observer) in self.list.iter_mut() {
+            observer(event);
+        }
+    }
+}
+
+impl CPU {
+    /// Installs `observer`, which is called for every access the CPU makes on its bus.
+    pub fn add_observer(&mut self, observer: impl FnMut(&BusEvent) + 'static) -> ObserverId {
+        self.observers.add(Box::new(observer))
+    }
+
+    /// Removes a previously installed observer, returns `false` if it was already gone.
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(id)
+    }
+
+    #[inline]
+    pub(crate) fn notify(&mut self, addr: Word, value: Byte, access: Access) {
+        let cycle = self.general_cycles + self.bus_accesses;
+        self.bus_accesses += 1;
+        if self.observers.is_empty() {
+            return;
+        }
+        self.observers.notify(&BusEvent {
+            addr,
+            value,
+            access,
+            cycle,
+        });
+    }
+}
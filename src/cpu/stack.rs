@@ -21,6 +21,11 @@ impl CPU {
         self.push_byte(lo);
     }
 
+    /// Pulling instructions read the top of the stack once before moving S.
+    pub(crate) fn stack_dummy_read(&mut self) {
+        self.dummy_read(self.stack_addr());
+    }
+
     pub fn pull_byte(&mut self) -> Byte {
         self.sp = self.sp.wrapping_add(1);
         self.read_byte(self.stack_addr())
@@ -1,2 +1,8 @@
+pub mod apu;
+pub mod audio;
 pub mod bus;
+pub mod cartridge;
 pub mod cpu;
+pub mod nes;
+pub mod nsf;
+pub mod ppu;
@@ -0,0 +1,119 @@
+use crate::{
+    apu::Apu,
+    bus::{Bus, Byte, Word, open_bus::OpenBus},
+};
+
+pub const RAM_SIZE: usize = 0x0800;
+
+/// ### NES CPU memory map
+/// | Range | Component |
+/// |-------|-----------|
+/// | `$0000-$1FFF` | 2 KiB internal RAM, mirrored 4 times |
+/// | `$2000-$3FFF` | PPU registers, mirrored every 8 bytes |
+/// | `$4000-$4017` | APU and I/O registers |
+/// | `$4018-$401F` | CPU test mode registers |
+/// | `$4020-$FFFF` | Cartridge space |
+///
+/// Components always receive the CPU address, folded onto `$2000-$2007` for the PPU.
+/// Reads from a component that isn't connected return open bus, as do the bits
+/// of `$4000-$4017` the 2A03 doesn't drive (see [`Apu::driven_bits`]) and the
+/// bits other components leave undriven (see [`Bus::driven_bits`]).
+/// The cartridge connector carries the whole CPU bus, so the cartridge also
+/// sees writes to the PPU registers (MMC5 snoops `$2000` and `$2001`).
+pub struct NesBus {
+    pub ram: [Byte; RAM_SIZE],
+    ppu: Option<Box<dyn Bus>>,
+    apu: Option<Box<dyn Bus>>,
+    test_registers: Option<Box<dyn Bus>>,
+    cartridge: Option<Box<dyn Bus>>,
+    open_bus: OpenBus,
+}
+
+impl Default for NesBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NesBus {
+    pub fn new() -> Self {
+        Self {
+            ram: [0; RAM_SIZE],
+            ppu: None,
+            apu: None,
+            test_registers: None,
+            cartridge: None,
+            open_bus: OpenBus::new(),
+        }
+    }
+
+    pub fn connect_ppu(&mut self, ppu: Box<dyn Bus>) {
+        self.ppu = Some(ppu);
+    }
+
+    pub fn connect_apu(&mut self, apu: Box<dyn Bus>) {
+        self.apu = Some(apu);
+    }
+
+    pub fn connect_test_registers(&mut self, test_registers: Box<dyn Bus>) {
+        self.test_registers = Some(test_registers);
+    }
+
+    pub fn connect_cartridge(&mut self, cartridge: Box<dyn Bus>) {
+        self.cartridge = Some(cartridge);
+    }
+
+    /// Last value seen on the CPU data bus.
+    pub fn open_bus(&self) -> Byte {
+        self.open_bus.value()
+    }
+
+    fn component(&mut self, addr: Word) -> (Option<&mut Box<dyn Bus>>, Word) {
+        match addr {
+            0x2000..=0x3FFF => (self.ppu.as_mut(), 0x2000 | (addr & 0x0007)),
+            0x4000..=0x4017 => (self.apu.as_mut(), addr),
+            0x4018..=0x401F => (self.test_registers.as_mut(), addr),
+            _ => (self.cartridge.as_mut(), addr),
+        }
+    }
+}
+
+impl Bus for NesBus {
+    fn read(&mut self, addr: Word, read_only: bool) -> Byte {
+        let value = if addr < 0x2000 {
+            self.ram[addr as usize % RAM_SIZE]
+        } else {
+            let open = self.open_bus.value();
+            match self.component(addr) {
+                (Some(component), 0x4000..=0x4017) => {
+                    let driven = Apu::driven_bits(addr);
+                    (component.read(addr, read_only) & driven) | (open & !driven)
+                }
+                (Some(component), addr) => {
+                    let driven = component.driven_bits(addr);
+                    (component.read(addr, read_only) & driven) | (open & !driven)
+                }
+                (None, _) => open,
+            }
+        };
+        if !read_only {
+            self.open_bus.latch(value);
+        }
+        value
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        self.open_bus.latch(value);
+        if addr < 0x2000 {
+            self.ram[addr as usize % RAM_SIZE] = value;
+        } else {
+            let (component, addr) = self.component(addr);
+            if let Some(component) = component {
+                component.write(addr, value);
+            }
+            if let (0x2000..=0x2007, Some(cartridge)) = (addr, self.cartridge.as_mut()) {
+                cartridge.write(addr, value);
+            }
+        }
+    }
+}
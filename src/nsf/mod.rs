@@ -0,0 +1,282 @@
+use std::{fmt, io};
+
+use bitflags::bitflags;
+
+use crate::{
+    bus::{Byte, Word},
+    cartridge::header::Region,
+};
+
+pub mod player;
+
+pub const NSF_HEADER_SIZE: usize = 0x80;
+const NSF_MAGIC: [Byte; 5] = *b"NESM\x1A";
+const NSFE_MAGIC: [Byte; 4] = *b"NSFE";
+/// Play rate used when the file leaves it at 0, in microseconds
+pub const DEFAULT_NTSC_SPEED: u16 = 16_639;
+pub const DEFAULT_PAL_SPEED: u16 = 19_997;
+
+bitflags! {
+    /// Expansion sound chips the tune writes to
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ExpansionChips: Byte {
+        const VRC6 = 1 << 0;
+        const VRC7 = 1 << 1;
+        const FDS  = 1 << 2;
+        const MMC5 = 1 << 3;
+        const N163 = 1 << 4;
+        const S5B  = 1 << 5;
+    }
+}
+
+#[derive(Debug)]
+pub enum NsfError {
+    /// The file starts with neither `NESM<EOF>` nor `NSFE`
+    BadMagic,
+    /// The file is shorter than its header or a chunk claims
+    Truncated {
+        expected: usize,
+        actual: usize,
+    },
+    /// An NSFe file without its `INFO` or `DATA` chunk
+    MissingChunk(&'static str),
+    /// The tune has no program data
+    NoData,
+    /// An NSFe chunk the player must understand but doesn't
+    UnsupportedChunk([Byte; 4]),
+    Io(io::Error),
+}
+
+impl fmt::Display for NsfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NsfError::BadMagic => write!(f, "not an NSF or NSFe file"),
+            NsfError::Truncated { expected, actual } => {
+                write!(f, "file truncated: expected {expected} bytes, got {actual}")
+            }
+            NsfError::MissingChunk(id) => write!(f, "NSFe file has no {id} chunk"),
+            NsfError::NoData => write!(f, "tune has no program data"),
+            NsfError::UnsupportedChunk(id) => {
+                write!(f, "unsupported NSFe chunk {}", String::from_utf8_lossy(id))
+            }
+            NsfError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for NsfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NsfError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NsfError {
+    fn from(err: io::Error) -> Self {
+        NsfError::Io(err)
+    }
+}
+
+/// ### NSF / NSFe tune
+/// The 6502 code of a soundtrack with the entry points to drive it.
+/// Songs are numbered from 0 here, NSF headers count them from 1.
+///
+/// | NSF offset | Content |
+/// |------------|---------|
+/// | `$00` | `NESM<EOF>`, version |
+/// | `$06` | Song count, starting song |
+/// | `$08` | Load, init and play addresses |
+/// | `$0E` | Title, artist and copyright, 32 bytes each |
+/// | `$6E` | NTSC play period in microseconds |
+/// | `$70` | Initial banks for `$5FF8-$5FFF`, all 0 when not bankswitched |
+/// | `$78` | PAL play period in microseconds |
+/// | `$7A` | Region: bit 0 PAL, bit 1 dual |
+/// | `$7B` | Expansion chips |
+/// | `$80` | Program data |
+///
+/// NSFe carries the same fields in `INFO`, `DATA`, `BANK`, `RATE` and `auth`
+/// chunks, plus optional track lengths (`time`) and names (`tlbl`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nsf {
+    pub song_count: Byte,
+    pub starting_song: Byte,
+    pub load_address: Word,
+    pub init_address: Word,
+    pub play_address: Word,
+    pub title: String,
+    pub artist: String,
+    pub copyright: String,
+    pub ntsc_speed: u16,
+    pub pal_speed: u16,
+    /// Initial values of `$5FF8-$5FFF` for bankswitched tunes
+    pub banks: Option<[Byte; 8]>,
+    pub region: Region,
+    pub chips: ExpansionChips,
+    /// Track lengths in milliseconds (NSFe), negative when unknown
+    pub track_lengths: Vec<i32>,
+    pub track_names: Vec<String>,
+    pub data: Vec<Byte>,
+}
+
+impl Nsf {
+    pub fn from_bytes(bytes: &[Byte]) -> Result<Self, NsfError> {
+        if bytes.starts_with(&NSF_MAGIC) {
+            Self::parse_nsf(bytes)
+        } else if bytes.starts_with(&NSFE_MAGIC) {
+            Self::parse_nsfe(bytes)
+        } else {
+            Err(NsfError::BadMagic)
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, NsfError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    fn parse_nsf(bytes: &[Byte]) -> Result<Self, NsfError> {
+        if bytes.len() < NSF_HEADER_SIZE {
+            return Err(NsfError::Truncated {
+                expected: NSF_HEADER_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        if bytes.len() == NSF_HEADER_SIZE {
+            return Err(NsfError::NoData);
+        }
+        let word = |at: usize| Word::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let banks: [Byte; 8] = bytes[0x70..0x78].try_into().unwrap();
+        Ok(Self {
+            song_count: bytes[0x06],
+            starting_song: bytes[0x07].saturating_sub(1),
+            load_address: word(0x08),
+            init_address: word(0x0A),
+            play_address: word(0x0C),
+            title: text(&bytes[0x0E..0x2E]),
+            artist: text(&bytes[0x2E..0x4E]),
+            copyright: text(&bytes[0x4E..0x6E]),
+            ntsc_speed: word(0x6E),
+            pal_speed: word(0x78),
+            banks: banks.iter().any(|&bank| bank != 0).then_some(banks),
+            region: region(bytes[0x7A]),
+            chips: ExpansionChips::from_bits_truncate(bytes[0x7B]),
+            track_lengths: Vec::new(),
+            track_names: Vec::new(),
+            data: bytes[NSF_HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    fn parse_nsfe(bytes: &[Byte]) -> Result<Self, NsfError> {
+        let mut nsf = Self {
+            song_count: 1,
+            starting_song: 0,
+            load_address: 0,
+            init_address: 0,
+            play_address: 0,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ntsc_speed: DEFAULT_NTSC_SPEED,
+            pal_speed: DEFAULT_PAL_SPEED,
+            banks: None,
+            region: Region::Ntsc,
+            chips: ExpansionChips::empty(),
+            track_lengths: Vec::new(),
+            track_names: Vec::new(),
+            data: Vec::new(),
+        };
+        let (mut info, mut data) = (false, false);
+        let mut at = NSFE_MAGIC.len();
+        while at + 8 <= bytes.len() {
+            let length = u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+            let id: [Byte; 4] = bytes[at + 4..at + 8].try_into().unwrap();
+            let start = at + 8;
+            let end = start + length;
+            if bytes.len() < end {
+                return Err(NsfError::Truncated {
+                    expected: end,
+                    actual: bytes.len(),
+                });
+            }
+            let chunk = &bytes[start..end];
+            let byte = |at: usize| chunk.get(at).copied();
+            let word = |at: usize| Some(Word::from_le_bytes([byte(at)?, byte(at + 1)?]));
+            match &id {
+                b"INFO" => {
+                    info = true;
+                    nsf.load_address = word(0).unwrap_or(0);
+                    nsf.init_address = word(2).unwrap_or(0);
+                    nsf.play_address = word(4).unwrap_or(0);
+                    nsf.region = region(byte(6).unwrap_or(0));
+                    nsf.chips = ExpansionChips::from_bits_truncate(byte(7).unwrap_or(0));
+                    nsf.song_count = byte(8).unwrap_or(1);
+                    nsf.starting_song = byte(9).unwrap_or(0);
+                }
+                b"DATA" => {
+                    data = true;
+                    nsf.data = chunk.to_vec();
+                }
+                b"BANK" => {
+                    let mut banks = [0; 8];
+                    for (bank, &value) in banks.iter_mut().zip(chunk) {
+                        *bank = value;
+                    }
+                    nsf.banks = Some(banks);
+                }
+                b"RATE" => {
+                    nsf.ntsc_speed = word(0).unwrap_or(DEFAULT_NTSC_SPEED);
+                    nsf.pal_speed = word(2).unwrap_or(DEFAULT_PAL_SPEED);
+                }
+                b"auth" => {
+                    let mut strings = chunk.split(|&b| b == 0).map(text);
+                    nsf.title = strings.next().unwrap_or_default();
+                    nsf.artist = strings.next().unwrap_or_default();
+                    nsf.copyright = strings.next().unwrap_or_default();
+                }
+                b"time" => {
+                    nsf.track_lengths = chunk
+                        .chunks_exact(4)
+                        .map(|ms| i32::from_le_bytes(ms.try_into().unwrap()))
+                        .collect();
+                }
+                b"tlbl" => {
+                    nsf.track_names = chunk
+                        .split(|&b| b == 0)
+                        .map(text)
+                        .take(nsf.song_count as usize)
+                        .collect();
+                }
+                b"NEND" => break,
+                // Chunks starting with a capital letter can't be skipped
+                _ if id[0].is_ascii_uppercase() => return Err(NsfError::UnsupportedChunk(id)),
+                _ => {}
+            }
+            at = end;
+        }
+        if !info {
+            return Err(NsfError::MissingChunk("INFO"));
+        }
+        if !data {
+            return Err(NsfError::MissingChunk("DATA"));
+        }
+        if nsf.data.is_empty() {
+            return Err(NsfError::NoData);
+        }
+        Ok(nsf)
+    }
+}
+
+/// Null padded or terminated text
+fn text(bytes: &[Byte]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn region(flags: Byte) -> Region {
+    match flags & 0x03 {
+        0 => Region::Ntsc,
+        1 => Region::Pal,
+        _ => Region::MultiRegion,
+    }
+}
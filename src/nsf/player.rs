@@ -0,0 +1,240 @@
+use std::{cell::RefCell, io, rc::Rc};
+
+use crate::{
+    apu::Apu,
+    audio::{AudioOutput, AudioSink, NTSC_CPU_CLOCK, SampleSource},
+    bus::{
+        Bus, Byte, Word,
+        clock::{Clocked, Lines},
+    },
+    cpu::{CPU, Flag, Profile},
+    nes::bus::{NesBus, RAM_SIZE},
+    nsf::{DEFAULT_NTSC_SPEED, Nsf},
+};
+
+/// `JMP $5FF0`, where the CPU waits between calls
+pub const IDLE_LOOP: Word = 0x5FF0;
+const IDLE_CODE: [Byte; 3] = [0x4C, 0xF0, 0x5F];
+pub const BANK_REGISTERS: Word = 0x5FF8;
+const BANK_SIZE: usize = 0x1000;
+const WRAM_SIZE: usize = 0x2000;
+/// Give up waiting for a routine to return after a second
+const MAX_CALL_CYCLES: u64 = NTSC_CPU_CLOCK as u64;
+
+/// ### NSF memory
+/// | Range | Content |
+/// |-------|---------|
+/// | `$5FF0-$5FF2` | Idle loop the player returns to |
+/// | `$5FF8-$5FFF` | Bank registers, 4 KiB bank for each of `$8000-$FFFF` |
+/// | `$6000-$7FFF` | 8 KiB of RAM |
+/// | `$8000-$FFFF` | Program data |
+///
+/// Bankswitched tunes are split into 4 KiB banks after `load_address & $0FFF`
+/// bytes of padding, the others are loaded as is at `load_address`.
+struct NsfMemory {
+    rom: Vec<Byte>,
+    banks: Option<[Byte; 8]>,
+    wram: [Byte; WRAM_SIZE],
+}
+
+impl NsfMemory {
+    fn new(nsf: &Nsf) -> Self {
+        let rom = match nsf.banks {
+            Some(_) => {
+                let mut rom = vec![0; nsf.load_address as usize & (BANK_SIZE - 1)];
+                rom.extend_from_slice(&nsf.data);
+                rom.resize(rom.len().div_ceil(BANK_SIZE) * BANK_SIZE, 0);
+                rom
+            }
+            None => {
+                let mut rom = vec![0; 0x8000];
+                let start = (nsf.load_address as usize).saturating_sub(0x8000);
+                let end = (start + nsf.data.len()).min(rom.len());
+                rom[start..end].copy_from_slice(&nsf.data[..end - start]);
+                rom
+            }
+        };
+        Self {
+            rom,
+            banks: nsf.banks,
+            wram: [0; WRAM_SIZE],
+        }
+    }
+
+    fn rom_offset(&self, addr: Word) -> usize {
+        let offset = addr as usize - 0x8000;
+        match self.banks {
+            Some(banks) => {
+                let bank = banks[offset / BANK_SIZE] as usize;
+                (bank * BANK_SIZE + offset % BANK_SIZE) % self.rom.len()
+            }
+            None => offset,
+        }
+    }
+}
+
+impl Bus for NsfMemory {
+    fn read(&mut self, addr: Word, _read_only: bool) -> Byte {
+        match addr {
+            IDLE_LOOP..=0x5FF2 => IDLE_CODE[(addr - IDLE_LOOP) as usize],
+            0x6000..=0x7FFF => self.wram[addr as usize - 0x6000],
+            0x8000..=0xFFFF => self.rom[self.rom_offset(addr)],
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        match addr {
+            BANK_REGISTERS..=0x5FFF => {
+                if let Some(banks) = self.banks.as_mut() {
+                    banks[(addr - BANK_REGISTERS) as usize] = value;
+                }
+            }
+            0x6000..=0x7FFF => self.wram[addr as usize - 0x6000] = value,
+            _ => {}
+        }
+    }
+}
+
+/// Samples the APU into the audio output on every CPU cycle.
+struct AudioTap {
+    apu: Rc<RefCell<Apu>>,
+    output: Rc<RefCell<AudioOutput>>,
+}
+
+impl Clocked for AudioTap {
+    fn tick(&mut self, _lines: &mut Lines) {
+        let level = self.apu.borrow().sample();
+        self.output.borrow_mut().push(level);
+    }
+}
+
+/// ### NSF player
+/// Runs a tune on the [`CPU`] with an [`Apu`], as an NTSC console would: INIT is
+/// called with the song in `A` and `0` (NTSC) in `X`, then PLAY once per
+/// play period. A routine returns to [`IDLE_LOOP`], where the CPU spins until
+/// the next call, so the APU keeps running in between. Tunes whose INIT never
+/// returns are simply left running without PLAY calls.
+///
+/// Expansion chips are not emulated, only the 2A03 channels are heard.
+pub struct NsfPlayer {
+    nsf: Nsf,
+    cpu: CPU,
+    memory: Rc<RefCell<NsfMemory>>,
+    output: Rc<RefCell<AudioOutput>>,
+    play_period: u64,
+    next_play: u64,
+    sample_rate: u32,
+}
+
+impl NsfPlayer {
+    pub fn new(nsf: Nsf, sample_rate: u32) -> Self {
+        let memory = Rc::new(RefCell::new(NsfMemory::new(&nsf)));
+        let apu = Rc::new(RefCell::new(Apu::new()));
+        let output = Rc::new(RefCell::new(AudioOutput::new(sample_rate)));
+        let mut bus = NesBus::new();
+        bus.connect_apu(Box::new(apu.clone()));
+        bus.connect_cartridge(Box::new(memory.clone()));
+        let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+        cpu.connect_bus(Box::new(bus));
+        cpu.attach_device(Box::new(apu.clone()));
+        cpu.attach_device(Box::new(AudioTap {
+            apu,
+            output: output.clone(),
+        }));
+        cpu.reset();
+        cpu.pc = IDLE_LOOP;
+
+        let speed = match nsf.ntsc_speed {
+            0 => DEFAULT_NTSC_SPEED,
+            speed => speed,
+        };
+        Self {
+            play_period: (speed as f64 * NTSC_CPU_CLOCK / 1_000_000.0) as u64,
+            next_play: 0,
+            nsf,
+            cpu,
+            memory,
+            output,
+            sample_rate,
+        }
+    }
+
+    pub fn nsf(&self) -> &Nsf {
+        &self.nsf
+    }
+
+    /// Reads memory as the tune sees it, without side effects.
+    pub fn peek(&mut self, addr: Word) -> Byte {
+        self.cpu.peek(addr)
+    }
+
+    /// CPU cycles between two PLAY calls.
+    pub fn play_period(&self) -> u64 {
+        self.play_period
+    }
+
+    /// Resets the machine and runs INIT for `song`, counted from 0.
+    pub fn start_song(&mut self, song: Byte) {
+        for addr in 0..RAM_SIZE as Word {
+            self.cpu.write(addr, 0);
+        }
+        {
+            let mut memory = self.memory.borrow_mut();
+            memory.wram.fill(0);
+            memory.banks = self.nsf.banks;
+        }
+        for addr in 0x4000..=0x4013 {
+            self.cpu.write(addr, 0);
+        }
+        self.cpu.write(0x4015, 0x00);
+        self.cpu.write(0x4015, 0x0F);
+        self.cpu.write(0x4017, 0x40);
+
+        self.cpu.sp = 0xFF;
+        self.cpu.flag = Flag::INTERRUPT_DISABLE;
+        self.cpu.a = song;
+        self.cpu.x = 0;
+        self.cpu.y = 0;
+        self.cpu.pc = IDLE_LOOP;
+        self.call(self.nsf.init_address);
+        self.next_play = self.cpu.general_cycles;
+        // Drop what INIT played, a song starts with its first PLAY
+        self.output.borrow_mut().end_frame();
+    }
+
+    /// Calls PLAY and idles until the next call is due,
+    /// returns the samples of that stretch.
+    pub fn run_frame(&mut self) -> Vec<f32> {
+        if self.cpu.pc == IDLE_LOOP {
+            self.call(self.nsf.play_address);
+        }
+        self.next_play += self.play_period;
+        while self.cpu.general_cycles < self.next_play {
+            self.cpu.execute();
+        }
+        self.output.borrow_mut().end_frame().to_vec()
+    }
+
+    /// Plays `seconds` of the current song into `sink`.
+    pub fn render(&mut self, seconds: f64, sink: &mut dyn AudioSink) -> io::Result<()> {
+        let mut remaining = (seconds * self.sample_rate as f64) as usize;
+        while remaining > 0 {
+            let samples = self.run_frame();
+            let count = samples.len().min(remaining);
+            sink.write_samples(&samples[..count])?;
+            remaining -= count;
+        }
+        Ok(())
+    }
+
+    /// JSRs to `addr` from the idle loop and runs until the routine returns.
+    fn call(&mut self, addr: Word) {
+        self.cpu.push_word(IDLE_LOOP - 1);
+        self.cpu.pc = addr;
+        let start = self.cpu.general_cycles;
+        while self.cpu.pc != IDLE_LOOP && self.cpu.general_cycles - start < MAX_CALL_CYCLES {
+            self.cpu.execute();
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    bus::{Byte, Word},
+    ppu::{Control, Mask, Ppu},
+};
+
+/// ### Background pipeline
+/// Every 8 dots of dots 1-256 and 321-336 the PPU fetches a tile: its nametable
+/// byte, attribute byte and the two pattern planes, then moves `v` one tile right.
+/// Fetched tiles are loaded into the low byte of 16 bit shift registers that shift
+/// once per dot, the pixel being picked `x` bits from the top.
+impl Ppu {
+    pub(super) fn fetch_background(&mut self, dot: usize) {
+        if (2..=257).contains(&dot) || (322..=337).contains(&dot) {
+            self.shift_background();
+        }
+        if (1..=256).contains(&dot) || (321..=336).contains(&dot) {
+            match (dot - 1) % 8 {
+                0 => {
+                    self.load_background();
+                    self.next_tile = self.read_memory(0x2000 | (self.v & 0x0FFF));
+                }
+                2 => {
+                    let v = self.v;
+                    let addr = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+                    let shift = ((v >> 4) & 0x04) | (v & 0x02);
+                    self.next_attribute = (self.read_memory(addr) >> shift) & 0x03;
+                }
+                4 => self.next_pattern[0] = self.read_memory(self.pattern_address()),
+                6 => self.next_pattern[1] = self.read_memory(self.pattern_address() + 8),
+                7 => self.increment_x(),
+                _ => {}
+            }
+        }
+        match dot {
+            256 => self.increment_y(),
+            257 => {
+                self.load_background();
+                self.v = (self.v & !0x041F) | (self.t & 0x041F);
+            }
+            // Unused nametable fetches ending the scanline
+            337 | 339 => {
+                self.read_memory(0x2000 | (self.v & 0x0FFF));
+            }
+            _ => {}
+        }
+    }
+
+    /// Pre-render scanline dots 280-304 reload the vertical scroll.
+    pub(super) fn reload_vertical(&mut self) {
+        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+    }
+
+    /// Background pixel at screen column `x`: palette in bits 2-3, color in bits 0-1,
+    /// `0` when transparent.
+    pub(super) fn background_pixel(&self, x: usize) -> Byte {
+        if !self.mask.contains(Mask::BACKGROUND)
+            || (x < 8 && !self.mask.contains(Mask::BACKGROUND_LEFT))
+        {
+            return 0;
+        }
+        let bit = 0x8000 >> self.x;
+        let plane = |shifter: Word| (shifter & bit != 0) as Byte;
+        let color = plane(self.pattern_shifters[0]) | (plane(self.pattern_shifters[1]) << 1);
+        if color == 0 {
+            return 0;
+        }
+        let palette = plane(self.attribute_shifters[0]) | (plane(self.attribute_shifters[1]) << 1);
+        (palette << 2) | color
+    }
+
+    pub(super) fn increment_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v = (self.v & !0x001F) ^ 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    pub(super) fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03E0) >> 5;
+        match coarse_y {
+            29 => {
+                coarse_y = 0;
+                self.v ^= 0x0800;
+            }
+            // Rows 30 and 31 hold attributes, scrolling into them wraps without switching tables
+            31 => coarse_y = 0,
+            _ => coarse_y += 1,
+        }
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+
+    fn pattern_address(&self) -> Word {
+        let table = if self.ctrl.contains(Control::BACKGROUND_TABLE) {
+            0x1000
+        } else {
+            0
+        };
+        table | ((self.next_tile as Word) << 4) | ((self.v >> 12) & 0x07)
+    }
+
+    fn shift_background(&mut self) {
+        for shifter in self
+            .pattern_shifters
+            .iter_mut()
+            .chain(self.attribute_shifters.iter_mut())
+        {
+            *shifter <<= 1;
+        }
+    }
+
+    fn load_background(&mut self) {
+        for plane in 0..2 {
+            let pattern = &mut self.pattern_shifters[plane];
+            *pattern = (*pattern & 0xFF00) | self.next_pattern[plane] as Word;
+            let attribute = &mut self.attribute_shifters[plane];
+            let fill = if self.next_attribute & (1 << plane) != 0 {
+                0xFF
+            } else {
+                0x00
+            };
+            *attribute = (*attribute & 0xFF00) | fill;
+        }
+    }
+}
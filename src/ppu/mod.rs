@@ -0,0 +1,514 @@
+use bitflags::bitflags;
+
+use crate::{
+    bus::{
+        Bus, Byte, Word,
+        clock::{Clocked, Lines},
+    },
+    cartridge::{Mirroring, mapper::SharedMapper},
+    ppu::sprites::Sprite,
+};
+
+mod background;
+pub mod palette;
+mod sprites;
+
+pub use sprites::SPRITES_PER_LINE;
+
+pub const DOTS_PER_SCANLINE: usize = 341;
+pub const SCANLINES: usize = 262;
+pub const VISIBLE_SCANLINES: usize = 240;
+/// Scanline whose second dot raises the VBlank flag
+pub const VBLANK_SCANLINE: usize = 241;
+pub const PRE_RENDER_SCANLINE: usize = 261;
+/// PPU dots per CPU cycle on NTSC
+pub const DOTS_PER_CPU_CYCLE: usize = 3;
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = VISIBLE_SCANLINES;
+
+pub const OAM_SIZE: usize = 256;
+/// 2 KiB of console CIRAM plus the 2 KiB four-screen carts add
+const NAMETABLE_RAM_SIZE: usize = 0x1000;
+const PALETTE_SIZE: usize = 32;
+
+bitflags! {
+    /// `$2000`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Control: Byte {
+        const NAMETABLE_X        = 1 << 0;
+        const NAMETABLE_Y        = 1 << 1;
+        const INCREMENT_32       = 1 << 2;
+        const SPRITE_TABLE       = 1 << 3;
+        const BACKGROUND_TABLE   = 1 << 4;
+        const SPRITE_8X16        = 1 << 5;
+        const MASTER_SLAVE       = 1 << 6;
+        const NMI_ENABLE         = 1 << 7;
+    }
+
+    /// `$2001`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Mask: Byte {
+        const GREYSCALE          = 1 << 0;
+        const BACKGROUND_LEFT    = 1 << 1;
+        const SPRITES_LEFT       = 1 << 2;
+        const BACKGROUND         = 1 << 3;
+        const SPRITES            = 1 << 4;
+        const EMPHASIZE_RED      = 1 << 5;
+        const EMPHASIZE_GREEN    = 1 << 6;
+        const EMPHASIZE_BLUE     = 1 << 7;
+    }
+
+    /// `$2002`, the low 5 bits read back the I/O latch
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Status: Byte {
+        const SPRITE_OVERFLOW    = 1 << 5;
+        const SPRITE_0_HIT       = 1 << 6;
+        const VBLANK             = 1 << 7;
+    }
+}
+
+/// ### 2C02 PPU (NTSC)
+/// | Register | Access | Content |
+/// |----------|--------|---------|
+/// | `$2000` | write | PPUCTRL: NMI enable, sprite size, pattern tables, increment, nametable |
+/// | `$2001` | write | PPUMASK: emphasis, rendering enables, left column, greyscale |
+/// | `$2002` | read | PPUSTATUS: VBlank, sprite 0 hit, overflow; clears VBlank and `w` |
+/// | `$2003` | write | OAMADDR |
+/// | `$2004` | read/write | OAMDATA, writes increment OAMADDR |
+/// | `$2005` | write x2 | PPUSCROLL: X then Y |
+/// | `$2006` | write x2 | PPUADDR: high then low byte |
+/// | `$2007` | read/write | PPUDATA, reads below the palette go through a one byte buffer |
+///
+/// `$2005` and `$2006` share the `w` toggle and the loopy `v`/`t`/`x` registers.
+/// Reading a write-only register returns the I/O latch, the last value written
+/// to any register or read from a readable bit.
+///
+/// A frame is 262 scanlines of 341 dots: 240 visible ones, a post-render one,
+/// VBlank from dot 1 of scanline 241 and the pre-render scanline 261, which is
+/// one dot shorter on odd frames while rendering is on.
+///
+/// The PPU is shared between the CPU bus and the scheduler, each CPU cycle
+/// running 3 dots. NMI is driven while VBlank and `NMI_ENABLE` are both set,
+/// sampled at the start of a cycle: reading `$2002` on the dot before VBlank
+/// suppresses the flag and the NMI, reading it right after VBlank was raised
+/// returns the flag but still suppresses the NMI.
+///
+/// Pattern tables come from the cartridge through [`Mapper::ppu_read`],
+/// nametables from CIRAM laid out by [`Mapper::ciram_page`] unless the board
+/// answers [`Mapper::nametable_read`]. Without a cartridge pattern reads return 0
+/// and nametables mirror vertically.
+///
+/// Rendering fetches tiles dot by dot the way the chip does, so boards watching
+/// the PPU bus see the real access pattern, and writes 6-bit palette indices
+/// into [`Ppu::frame`], which a [`palette::Palette`] turns into RGBA. It needs
+/// nothing but memory and runs headless.
+/// Setting `unlimited_sprites` draws every sprite in range of a scanline instead
+/// of the first 8, without changing the bus accesses or the overflow flag.
+///
+/// [`Mapper::ppu_read`]: crate::cartridge::mapper::Mapper::ppu_read
+/// [`Mapper::ciram_page`]: crate::cartridge::mapper::Mapper::ciram_page
+/// [`Mapper::nametable_read`]: crate::cartridge::mapper::Mapper::nametable_read
+pub struct Ppu {
+    pub ctrl: Control,
+    pub mask: Mask,
+    pub status: Status,
+    pub oam_addr: Byte,
+    pub oam: [Byte; OAM_SIZE],
+    /// Current VRAM address
+    pub v: Word,
+    /// Temporary VRAM address, the top left of the screen
+    pub t: Word,
+    /// Fine X scroll
+    pub x: Byte,
+    /// First or second write toggle of `$2005`/`$2006`
+    pub w: bool,
+    /// Lifts the 8 sprites per scanline limit, an enhancement that reduces flicker
+    pub unlimited_sprites: bool,
+    read_buffer: Byte,
+    io_latch: Byte,
+    nametables: [Byte; NAMETABLE_RAM_SIZE],
+    palette: [Byte; PALETTE_SIZE],
+    cartridge: Option<SharedMapper>,
+    scanline: usize,
+    dot: usize,
+    frame: u64,
+    suppress_vblank: bool,
+    framebuffer: Box<[Byte; WIDTH * HEIGHT]>,
+    next_tile: Byte,
+    next_attribute: Byte,
+    next_pattern: [Byte; 2],
+    pattern_shifters: [Word; 2],
+    attribute_shifters: [Word; 2],
+    /// Sprites drawn on the current scanline
+    sprites: Vec<Sprite>,
+    /// Sprites found for the next scanline
+    next_sprites: Vec<Sprite>,
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self {
+            ctrl: Control::empty(),
+            mask: Mask::empty(),
+            status: Status::empty(),
+            oam_addr: 0,
+            oam: [0; OAM_SIZE],
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            unlimited_sprites: false,
+            read_buffer: 0,
+            io_latch: 0,
+            nametables: [0; NAMETABLE_RAM_SIZE],
+            palette: [0; PALETTE_SIZE],
+            cartridge: None,
+            scanline: 0,
+            dot: 0,
+            frame: 0,
+            suppress_vblank: false,
+            framebuffer: Box::new([0; WIDTH * HEIGHT]),
+            next_tile: 0,
+            next_attribute: 0,
+            next_pattern: [0; 2],
+            pattern_shifters: [0; 2],
+            attribute_shifters: [0; 2],
+            sprites: Vec::new(),
+            next_sprites: Vec::new(),
+        }
+    }
+
+    pub fn connect_cartridge(&mut self, cartridge: SharedMapper) {
+        self.cartridge = Some(cartridge);
+    }
+
+    /// Scanline of the next dot, 261 being the pre-render one.
+    pub fn scanline(&self) -> usize {
+        self.scanline
+    }
+
+    /// Next dot to run on the current scanline.
+    pub fn dot(&self) -> usize {
+        self.dot
+    }
+
+    /// Frames completed since power on.
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    /// Palette indices of the picture, `WIDTH` per row, from the top left.
+    /// Rows are written as their scanline runs, so between VBlank and the
+    /// next frame's first scanline this is the complete last frame.
+    pub fn frame(&self) -> &[Byte] {
+        self.framebuffer.as_slice()
+    }
+
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask.intersects(Mask::BACKGROUND | Mask::SPRITES)
+    }
+
+    /// Level of the NMI output, `/NMI` being active low on the real chip.
+    pub fn nmi_output(&self) -> bool {
+        self.status.contains(Status::VBLANK) && self.ctrl.contains(Control::NMI_ENABLE)
+    }
+
+    /// Runs a single dot.
+    pub fn clock_dot(&mut self) {
+        let (scanline, dot) = (self.scanline, self.dot);
+        let visible = scanline < VISIBLE_SCANLINES;
+        if self.rendering_enabled() && (visible || scanline == PRE_RENDER_SCANLINE) {
+            self.fetch_background(dot);
+            self.fetch_sprites(dot);
+            if visible && dot == 256 {
+                self.evaluate_sprites();
+            }
+            if scanline == PRE_RENDER_SCANLINE && (280..=304).contains(&dot) {
+                self.reload_vertical();
+            }
+        }
+        if visible && (1..=WIDTH).contains(&dot) {
+            self.render_pixel(dot - 1);
+        }
+        match (scanline, dot) {
+            (VBLANK_SCANLINE, 1) => {
+                if !self.suppress_vblank {
+                    self.status.insert(Status::VBLANK);
+                }
+                self.suppress_vblank = false;
+            }
+            (PRE_RENDER_SCANLINE, 1) => self.status = Status::empty(),
+            _ => {}
+        }
+        self.advance();
+    }
+
+    fn render_pixel(&mut self, x: usize) {
+        let color = if self.rendering_enabled() {
+            let background = self.background_pixel(x);
+            let index = match self.sprite_pixel(x) {
+                Some(sprite) => {
+                    if sprite.zero && background != 0 && x != WIDTH - 1 {
+                        self.status.insert(Status::SPRITE_0_HIT);
+                    }
+                    if background == 0 || !sprite.behind {
+                        sprite.color
+                    } else {
+                        background
+                    }
+                }
+                None => background,
+            };
+            self.palette[index as usize]
+        } else if self.v & 0x3F00 == 0x3F00 {
+            // With rendering off the PPU shows the palette entry `v` points at
+            self.palette[palette_index(self.v)]
+        } else {
+            self.palette[0]
+        };
+        self.framebuffer[self.scanline * WIDTH + x] = color & 0x3F;
+    }
+
+    fn advance(&mut self) {
+        let skip = self.scanline == PRE_RENDER_SCANLINE
+            && self.dot == DOTS_PER_SCANLINE - 2
+            && self.frame % 2 == 1
+            && self.rendering_enabled();
+        self.dot += 1;
+        if self.dot == DOTS_PER_SCANLINE || skip {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline == SCANLINES {
+                self.scanline = 0;
+                self.frame += 1;
+            }
+        }
+    }
+
+    /// Value the PPU reads at `addr` on its own bus.
+    pub fn read_memory(&mut self, addr: Word) -> Byte {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => match &self.cartridge {
+                Some(cartridge) => cartridge.borrow_mut().ppu_read(addr),
+                None => 0,
+            },
+            0x2000..=0x3EFF => {
+                let addr = 0x2000 | (addr & 0x0FFF);
+                let board = self
+                    .cartridge
+                    .as_ref()
+                    .and_then(|cartridge| cartridge.borrow_mut().nametable_read(addr));
+                board.unwrap_or_else(|| self.nametables[self.ciram_offset(addr)])
+            }
+            _ => self.palette[palette_index(addr)],
+        }
+    }
+
+    /// Side-effect-free read of `addr` on the PPU bus.
+    pub fn peek_memory(&mut self, addr: Word) -> Byte {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => match &self.cartridge {
+                Some(cartridge) => cartridge.borrow_mut().ppu_peek(addr),
+                None => 0,
+            },
+            0x2000..=0x3EFF => {
+                let addr = 0x2000 | (addr & 0x0FFF);
+                let board = self
+                    .cartridge
+                    .as_ref()
+                    .and_then(|cartridge| cartridge.borrow_mut().nametable_peek(addr));
+                board.unwrap_or_else(|| self.nametables[self.ciram_offset(addr)])
+            }
+            _ => self.palette[palette_index(addr)],
+        }
+    }
+
+    pub fn write_memory(&mut self, addr: Word, value: Byte) {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => {
+                if let Some(cartridge) = &self.cartridge {
+                    cartridge.borrow_mut().ppu_write(addr, value);
+                }
+            }
+            0x2000..=0x3EFF => {
+                let addr = 0x2000 | (addr & 0x0FFF);
+                let taken = self
+                    .cartridge
+                    .as_ref()
+                    .is_some_and(|cartridge| cartridge.borrow_mut().nametable_write(addr, value));
+                if !taken {
+                    let offset = self.ciram_offset(addr);
+                    self.nametables[offset] = value;
+                }
+            }
+            _ => self.palette[palette_index(addr)] = value & 0x3F,
+        }
+    }
+
+    fn ciram_offset(&self, addr: Word) -> usize {
+        let table = ((addr >> 10) & 0x03) as usize;
+        let page = match &self.cartridge {
+            Some(cartridge) => cartridge.borrow().ciram_page(table),
+            None => Mirroring::Vertical.ciram_page(table),
+        };
+        page * 0x400 + (addr as usize & 0x03FF)
+    }
+
+    fn increment_v(&mut self) {
+        let rendering = self.scanline < VISIBLE_SCANLINES || self.scanline == PRE_RENDER_SCANLINE;
+        if rendering && self.rendering_enabled() {
+            // The access lands in the middle of tile fetches, which bump both scroll counters
+            self.increment_x();
+            self.increment_y();
+            return;
+        }
+        let step = if self.ctrl.contains(Control::INCREMENT_32) {
+            32
+        } else {
+            1
+        };
+        self.v = self.v.wrapping_add(step) & 0x7FFF;
+    }
+
+    fn read_register(&mut self, addr: Word, read_only: bool) -> Byte {
+        match addr & 0x0007 {
+            0x0002 => {
+                let value = self.status.bits() | (self.io_latch & 0x1F);
+                if !read_only {
+                    // One dot before VBlank: the flag reads clear and never gets set
+                    if self.scanline == VBLANK_SCANLINE && self.dot == 1 {
+                        self.suppress_vblank = true;
+                    }
+                    self.status.remove(Status::VBLANK);
+                    self.w = false;
+                    self.io_latch = (self.io_latch & 0x1F) | (value & 0xE0);
+                }
+                value
+            }
+            0x0004 => {
+                let clearing = self.scanline < VISIBLE_SCANLINES && (1..=64).contains(&self.dot);
+                let mut value = if clearing && self.rendering_enabled() {
+                    // Secondary OAM is being filled with $FF
+                    0xFF
+                } else {
+                    self.oam[self.oam_addr as usize]
+                };
+                if self.oam_addr & 0x03 == 0x02 {
+                    value &= 0xE3;
+                }
+                if !read_only {
+                    self.io_latch = value;
+                }
+                value
+            }
+            0x0007 => {
+                let addr = self.v & 0x3FFF;
+                if read_only {
+                    return if addr >= 0x3F00 {
+                        (self.io_latch & 0xC0) | self.peek_memory(addr)
+                    } else {
+                        self.read_buffer
+                    };
+                }
+                let value = if addr >= 0x3F00 {
+                    self.read_buffer = self.read_memory(addr - 0x1000);
+                    (self.io_latch & 0xC0) | self.read_memory(addr)
+                } else {
+                    let buffered = self.read_buffer;
+                    self.read_buffer = self.read_memory(addr);
+                    buffered
+                };
+                self.increment_v();
+                self.io_latch = value;
+                value
+            }
+            _ => self.io_latch,
+        }
+    }
+
+    fn write_register(&mut self, addr: Word, value: Byte) {
+        self.io_latch = value;
+        match addr & 0x0007 {
+            0x0000 => {
+                self.ctrl = Control::from_bits_retain(value);
+                self.t = (self.t & !0x0C00) | ((value as Word & 0x03) << 10);
+            }
+            0x0001 => self.mask = Mask::from_bits_retain(value),
+            0x0002 => {}
+            0x0003 => self.oam_addr = value,
+            0x0004 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            0x0005 => {
+                if !self.w {
+                    self.t = (self.t & !0x001F) | (value as Word >> 3);
+                    self.x = value & 0x07;
+                } else {
+                    self.t = (self.t & !0x73E0)
+                        | ((value as Word & 0x07) << 12)
+                        | ((value as Word & 0xF8) << 2);
+                }
+                self.w = !self.w;
+            }
+            0x0006 => {
+                if !self.w {
+                    self.t = (self.t & 0x00FF) | ((value as Word & 0x3F) << 8);
+                } else {
+                    self.t = (self.t & 0x7F00) | value as Word;
+                    self.v = self.t;
+                    if let Some(cartridge) = &self.cartridge {
+                        cartridge.borrow_mut().ppu_address(self.v & 0x3FFF);
+                    }
+                }
+                self.w = !self.w;
+            }
+            _ => {
+                self.write_memory(self.v, value);
+                self.increment_v();
+            }
+        }
+    }
+}
+
+/// Palette RAM index of `addr`, `$3F10/$3F14/$3F18/$3F1C` mirroring the backdrop entries.
+fn palette_index(addr: Word) -> usize {
+    let index = addr as usize & 0x1F;
+    if index & 0x13 == 0x10 {
+        index & 0x0F
+    } else {
+        index
+    }
+}
+
+impl Bus for Ppu {
+    fn read(&mut self, addr: Word, read_only: bool) -> Byte {
+        self.read_register(addr, read_only)
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        self.write_register(addr, value);
+    }
+}
+
+impl Clocked for Ppu {
+    fn tick(&mut self, lines: &mut Lines) {
+        // Sampled before the dots run, so a `$2002` read racing VBlank cancels the NMI
+        if self.nmi_output() {
+            lines.assert_nmi();
+        }
+        for _ in 0..DOTS_PER_CPU_CYCLE {
+            self.clock_dot();
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::{fmt, io, path::Path};
+
+use crate::{bus::Byte, ppu::Mask};
+
+/// Colors the PPU can output, before emphasis
+pub const COLORS: usize = 64;
+/// One set of colors for each combination of the 3 emphasis bits
+pub const EMPHASIS_COLORS: usize = COLORS * 8;
+/// Bytes of an RGBA8 pixel
+pub const RGBA_SIZE: usize = 4;
+/// How much an emphasis bit dims the other two channels, set bits stacking
+const EMPHASIS_ATTENUATION: f32 = 0.816;
+
+/// ### Default 2C02 palette
+/// RGB of the 64 colors of an NTSC PPU, without emphasis.
+#[rustfmt::skip]
+const DEFAULT_2C02: [[Byte; 3]; COLORS] = [
+    [84, 84, 84],    [0, 30, 116],    [8, 16, 144],    [48, 0, 136],
+    [68, 0, 100],    [92, 0, 48],     [84, 4, 0],      [60, 24, 0],
+    [32, 42, 0],     [8, 58, 0],      [0, 64, 0],      [0, 60, 0],
+    [0, 50, 60],     [0, 0, 0],       [0, 0, 0],       [0, 0, 0],
+    [152, 150, 152], [8, 76, 196],    [48, 50, 236],   [92, 30, 228],
+    [136, 20, 176],  [160, 20, 100],  [152, 34, 32],   [120, 60, 0],
+    [84, 90, 0],     [40, 114, 0],    [8, 124, 0],     [0, 118, 40],
+    [0, 102, 120],   [0, 0, 0],       [0, 0, 0],       [0, 0, 0],
+    [236, 238, 236], [76, 154, 236],  [120, 124, 236], [176, 98, 236],
+    [228, 84, 236],  [236, 88, 180],  [236, 106, 100], [212, 136, 32],
+    [160, 170, 0],   [116, 196, 0],   [76, 208, 32],   [56, 204, 108],
+    [56, 180, 204],  [60, 60, 60],    [0, 0, 0],       [0, 0, 0],
+    [236, 238, 236], [168, 204, 236], [188, 188, 236], [212, 178, 236],
+    [236, 174, 236], [236, 174, 212], [236, 180, 176], [228, 196, 144],
+    [204, 210, 120], [180, 222, 120], [168, 226, 144], [152, 226, 180],
+    [160, 214, 228], [160, 162, 160], [0, 0, 0],       [0, 0, 0],
+];
+
+#[derive(Debug)]
+pub enum PaletteError {
+    /// A `.pal` file holds 64 or 512 RGB triplets, 192 or 1536 bytes
+    BadSize(usize),
+    Io(io::Error),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::BadSize(size) => write!(
+                f,
+                "palette must be {} or {} bytes, got {size}",
+                COLORS * 3,
+                EMPHASIS_COLORS * 3
+            ),
+            PaletteError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PaletteError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PaletteError {
+    fn from(err: io::Error) -> Self {
+        PaletteError::Io(err)
+    }
+}
+
+/// ### Palette
+/// Maps the PPU's output, a 6-bit color plus the PPUMASK emphasis bits, to RGB.
+/// Colors are stored for all 8 emphasis combinations, entry
+/// `emphasis << 6 | color` where emphasis bit 0 is red, 1 green and 2 blue,
+/// the layout of 512-entry `.pal` files. Palettes with 64 colors get their
+/// emphasized sets by having every set bit dim the other two channels, so
+/// all three bits dim every channel twice.
+///
+/// Greyscale and emphasis are taken from a whole frame's [`Mask`], a game
+/// changing them mid-frame gets the last value everywhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Box<[[Byte; 3]; EMPHASIS_COLORS]>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::from_colors(&DEFAULT_2C02)
+    }
+}
+
+impl Palette {
+    /// Reads a `.pal` file of 64 or 512 RGB triplets.
+    pub fn from_bytes(bytes: &[Byte]) -> Result<Self, PaletteError> {
+        let colors: Vec<[Byte; 3]> = bytes
+            .chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect();
+        match bytes.len() {
+            len if len == COLORS * 3 => Ok(Self::from_colors(&colors)),
+            len if len == EMPHASIS_COLORS * 3 => Ok(Self {
+                colors: colors.into_boxed_slice().try_into().unwrap(),
+            }),
+            len => Err(PaletteError::BadSize(len)),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PaletteError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Builds the emphasized sets from the 64 base colors.
+    fn from_colors(base: &[[Byte; 3]]) -> Self {
+        let mut colors = Box::new([[0; 3]; EMPHASIS_COLORS]);
+        for (entry, rgb) in colors.iter_mut().enumerate() {
+            let emphasis = entry / COLORS;
+            *rgb = base[entry % COLORS];
+            if emphasis == 0 {
+                continue;
+            }
+            for (channel, value) in rgb.iter_mut().enumerate() {
+                let dimming = (emphasis & !(1 << channel)).count_ones() as i32;
+                *value = (*value as f32 * EMPHASIS_ATTENUATION.powi(dimming)).round() as Byte;
+            }
+        }
+        Self { colors }
+    }
+
+    /// RGB of `color` (a 6-bit palette index) under `mask`.
+    pub fn rgb(&self, color: Byte, mask: Mask) -> [Byte; 3] {
+        let color = if mask.contains(Mask::GREYSCALE) {
+            color & 0x30
+        } else {
+            color & 0x3F
+        };
+        let emphasis = (mask.bits() >> 5) as usize;
+        self.colors[emphasis * COLORS + color as usize]
+    }
+
+    /// Converts an indexed framebuffer like [`crate::ppu::Ppu::frame`] to RGBA8,
+    /// `out` holding 4 bytes per pixel.
+    pub fn to_rgba(&self, frame: &[Byte], mask: Mask, out: &mut [Byte]) {
+        for (&color, pixel) in frame.iter().zip(out.chunks_exact_mut(RGBA_SIZE)) {
+            let [r, g, b] = self.rgb(color, mask);
+            pixel.copy_from_slice(&[r, g, b, 0xFF]);
+        }
+    }
+
+    pub fn frame_to_rgba(&self, frame: &[Byte], mask: Mask) -> Vec<Byte> {
+        let mut out = vec![0; frame.len() * RGBA_SIZE];
+        self.to_rgba(frame, mask, &mut out);
+        out
+    }
+}
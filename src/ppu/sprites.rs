@@ -0,0 +1,191 @@
+use crate::{
+    bus::{Byte, Word},
+    ppu::{Control, Mask, OAM_SIZE, Ppu, Status},
+};
+
+/// Sprites the hardware fetches per scanline
+pub const SPRITES_PER_LINE: usize = 8;
+
+const ATTRIBUTE_PALETTE: Byte = 0x03;
+const ATTRIBUTE_BEHIND: Byte = 0x20;
+const ATTRIBUTE_FLIP_X: Byte = 0x40;
+const ATTRIBUTE_FLIP_Y: Byte = 0x80;
+
+/// A sprite found in range of the next scanline
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Sprite {
+    y: Byte,
+    tile: Byte,
+    attributes: Byte,
+    x: Byte,
+    /// Pattern planes, already flipped so bit 7 is the leftmost pixel
+    pattern: [Byte; 2],
+    zero: bool,
+}
+
+/// Sprite pixel that won the priority among the sprites of a column
+pub(super) struct SpritePixel {
+    /// Palette RAM index, `$10-$1F`
+    pub color: Byte,
+    pub behind: bool,
+    pub zero: bool,
+}
+
+/// ### Sprite pipeline
+/// Dots 1-64 clear secondary OAM, dots 65-256 scan OAM for the sprites in range
+/// of the next scanline and dots 257-320 fetch their patterns, 8 dots per slot:
+/// two unused nametable fetches then the two pattern planes. Slots left empty
+/// fetch tile `$FF`, so boards watching A12 see the same pattern whatever the
+/// sprites. Evaluation is done in one go at the end of the scan.
+///
+/// Once 8 sprites are found the hardware keeps scanning for the overflow flag,
+/// but increments both the sprite and the byte index on a miss, so it compares
+/// tile numbers, attributes and X positions against the scanline too.
+impl Ppu {
+    pub(super) fn sprite_height(&self) -> usize {
+        if self.ctrl.contains(Control::SPRITE_8X16) {
+            16
+        } else {
+            8
+        }
+    }
+
+    pub(super) fn evaluate_sprites(&mut self) {
+        let height = self.sprite_height();
+        let scanline = self.scanline;
+        let in_range = |y: Byte| scanline.wrapping_sub(y as usize) < height;
+        let sprite = |oam: &[Byte; OAM_SIZE], n: usize| Sprite {
+            y: oam[n * 4],
+            tile: oam[n * 4 + 1],
+            attributes: oam[n * 4 + 2],
+            x: oam[n * 4 + 3],
+            pattern: [0; 2],
+            zero: n == 0,
+        };
+
+        self.next_sprites.clear();
+        let mut n = 0;
+        while n < OAM_SIZE / 4 && self.next_sprites.len() < SPRITES_PER_LINE {
+            if in_range(self.oam[n * 4]) {
+                self.next_sprites.push(sprite(&self.oam, n));
+            }
+            n += 1;
+        }
+        let extra = n;
+
+        let mut m = 0;
+        while n < OAM_SIZE / 4 {
+            if in_range(self.oam[n * 4 + m]) {
+                self.status.insert(Status::SPRITE_OVERFLOW);
+                break;
+            }
+            n += 1;
+            m = (m + 1) & 0x03;
+        }
+
+        if self.unlimited_sprites {
+            for n in extra..OAM_SIZE / 4 {
+                if in_range(self.oam[n * 4]) {
+                    self.next_sprites.push(sprite(&self.oam, n));
+                }
+            }
+        }
+    }
+
+    pub(super) fn fetch_sprites(&mut self, dot: usize) {
+        if dot == 257 {
+            std::mem::swap(&mut self.sprites, &mut self.next_sprites);
+            self.next_sprites.clear();
+        }
+        if !(257..=320).contains(&dot) {
+            return;
+        }
+        // OAMADDR is held at 0 while the slots are fetched
+        self.oam_addr = 0;
+        let slot = (dot - 257) / 8;
+        match (dot - 1) % 8 {
+            // Unused nametable fetches
+            0 | 2 => {
+                self.read_memory(0x2000 | (self.v & 0x0FFF));
+            }
+            4 | 6 => {
+                let plane = ((dot - 1) % 8 - 4) / 2;
+                let addr = match self.sprites.get(slot) {
+                    Some(&sprite) => self.sprite_pattern_address(sprite),
+                    None => self.sprite_pattern_address(Sprite {
+                        y: self.scanline as Byte,
+                        tile: 0xFF,
+                        attributes: 0,
+                        x: 0xFF,
+                        pattern: [0; 2],
+                        zero: false,
+                    }),
+                };
+                let pattern = self.read_memory(addr + plane as Word * 8);
+                if let Some(sprite) = self.sprites.get_mut(slot) {
+                    sprite.pattern[plane] = flip_x(sprite.attributes, pattern);
+                }
+            }
+            _ => {}
+        }
+        // Sprites past the hardware limit are fetched off the bus
+        if dot == 320 {
+            for slot in SPRITES_PER_LINE..self.sprites.len() {
+                let addr = self.sprite_pattern_address(self.sprites[slot]);
+                for plane in 0..2 {
+                    let pattern = self.peek_memory(addr + plane as Word * 8);
+                    let sprite = &mut self.sprites[slot];
+                    sprite.pattern[plane] = flip_x(sprite.attributes, pattern);
+                }
+            }
+        }
+    }
+
+    pub(super) fn sprite_pixel(&self, x: usize) -> Option<SpritePixel> {
+        if !self.mask.contains(Mask::SPRITES) || (x < 8 && !self.mask.contains(Mask::SPRITES_LEFT))
+        {
+            return None;
+        }
+        self.sprites.iter().find_map(|sprite| {
+            let column = x.wrapping_sub(sprite.x as usize);
+            if column >= 8 {
+                return None;
+            }
+            let bit = |plane: Byte| (plane >> (7 - column)) & 0x01;
+            let color = bit(sprite.pattern[0]) | (bit(sprite.pattern[1]) << 1);
+            (color != 0).then_some(SpritePixel {
+                color: 0x10 | ((sprite.attributes & ATTRIBUTE_PALETTE) << 2) | color,
+                behind: sprite.attributes & ATTRIBUTE_BEHIND != 0,
+                zero: sprite.zero,
+            })
+        })
+    }
+
+    fn sprite_pattern_address(&self, sprite: Sprite) -> Word {
+        let height = self.sprite_height();
+        let mut row = (self.scanline.wrapping_sub(sprite.y as usize) % height) as Word;
+        if sprite.attributes & ATTRIBUTE_FLIP_Y != 0 {
+            row = height as Word - 1 - row;
+        }
+        let tile = sprite.tile as Word;
+        if height == 16 {
+            let table = (tile & 0x01) << 12;
+            table | ((tile & 0xFE) << 4) | ((row & 0x08) << 1) | (row & 0x07)
+        } else {
+            let table = if self.ctrl.contains(Control::SPRITE_TABLE) {
+                0x1000
+            } else {
+                0
+            };
+            table | (tile << 4) | row
+        }
+    }
+}
+
+fn flip_x(attributes: Byte, pattern: Byte) -> Byte {
+    if attributes & ATTRIBUTE_FLIP_X != 0 {
+        pattern.reverse_bits()
+    } else {
+        pattern
+    }
+}
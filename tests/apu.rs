@@ -0,0 +1,358 @@
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    apu::{
+        Apu,
+        mixer::{pulse_out, tnd_out},
+    },
+    audio::SampleSource,
+    bus::{
+        Bus, Byte, Word,
+        clock::{Clocked, Lines},
+    },
+    cpu::{CPU, Profile, instructions::opcode::Opcode},
+    nes::bus::NesBus,
+};
+
+/// Runs `cycles` CPU cycles, returns whether the IRQ line was asserted
+fn run(apu: &mut Apu, cycles: usize) -> bool {
+    let mut irq = false;
+    for _ in 0..cycles {
+        let mut lines = Lines::default();
+        apu.tick(&mut lines);
+        irq |= lines.irq;
+    }
+    irq
+}
+
+fn status(apu: &mut Apu) -> Byte {
+    apu.peek(0x4015)
+}
+
+/// Output above the idle triangle, which holds step 0 at level 15
+fn level(apu: &Apu) -> f32 {
+    apu.sample() - tnd_out(15, 0, 0)
+}
+
+/// Cycles between two rising edges of the output
+fn wave_period(apu: &mut Apu) -> usize {
+    let mut rises = Vec::new();
+    let mut last = apu.sample();
+    let mut cycle = 0;
+    while rises.len() < 3 {
+        run(apu, 1);
+        cycle += 1;
+        let level = apu.sample();
+        if level > last {
+            rises.push(cycle);
+        }
+        last = level;
+    }
+    rises[2] - rises[1]
+}
+
+#[test]
+fn mixer_follows_the_nonlinear_formulas() {
+    assert_eq!(pulse_out(0, 0), 0.0);
+    assert!((pulse_out(15, 0) - 0.1494).abs() < 1e-4);
+    assert!((pulse_out(15, 15) - 0.2585).abs() < 1e-4);
+    assert_eq!(tnd_out(0, 0, 0), 0.0);
+    assert!((tnd_out(15, 15, 127) - 0.7415).abs() < 1e-4);
+    // Two pulses together are quieter than twice one
+    assert!(pulse_out(15, 15) < 2.0 * pulse_out(15, 0));
+}
+
+#[test]
+fn length_counters_load_only_while_enabled() {
+    let mut apu = Apu::new();
+    apu.write(0x4003, 0x08);
+    assert_eq!(status(&mut apu), 0x00);
+    apu.write(0x4015, 0x0F);
+    for addr in [0x4003, 0x4007, 0x400B, 0x400F] {
+        apu.write(addr, 0x08);
+    }
+    assert_eq!(status(&mut apu), 0x0F);
+    apu.write(0x4015, 0x05);
+    assert_eq!(status(&mut apu), 0x05);
+}
+
+#[test]
+fn length_counters_count_half_frames() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x03);
+    // Index 3 loads a length of 2, pulse 2 is halted
+    apu.write(0x4003, 0x18);
+    apu.write(0x4004, 0x20);
+    apu.write(0x4007, 0x18);
+    run(&mut apu, 14912);
+    assert_eq!(status(&mut apu), 0x03);
+    run(&mut apu, 1);
+    assert_eq!(status(&mut apu), 0x03);
+    run(&mut apu, 29829 - 14913);
+    assert_eq!(status(&mut apu) & 0x03, 0x02);
+}
+
+#[test]
+fn four_step_mode_raises_the_frame_irq() {
+    let mut apu = Apu::new();
+    assert!(!run(&mut apu, 29827));
+    assert!(run(&mut apu, 1));
+    assert_eq!(status(&mut apu) & 0x40, 0x40);
+    // The read acknowledges it
+    apu.read(0x4015, false);
+    assert!(!run(&mut apu, 0));
+    assert_eq!(status(&mut apu) & 0x40, 0x00);
+    // ...until the flag is raised again on the last cycles of the sequence
+    assert!(run(&mut apu, 2));
+    apu.read(0x4015, false);
+    assert!(!run(&mut apu, 29827));
+    assert!(run(&mut apu, 1));
+}
+
+#[test]
+fn inhibit_clears_and_blocks_the_frame_irq() {
+    let mut apu = Apu::new();
+    assert!(run(&mut apu, 29830));
+    apu.write(0x4017, 0x40);
+    assert_eq!(status(&mut apu) & 0x40, 0x00);
+    assert!(!run(&mut apu, 3 * 29830));
+}
+
+#[test]
+fn five_step_mode_has_no_irq_and_clocks_on_write() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x01);
+    apu.write(0x4003, 0x18);
+    apu.write(0x4017, 0x80);
+    run(&mut apu, 4);
+    apu.write(0x4017, 0x80);
+    run(&mut apu, 4);
+    assert_eq!(status(&mut apu), 0x00);
+    assert!(!run(&mut apu, 3 * 37282));
+}
+
+#[test]
+fn frame_counter_reset_waits_for_the_apu_cycle() {
+    for (skew, delay) in [(0, 3), (1, 4)] {
+        let mut apu = Apu::new();
+        apu.write(0x4015, 0x01);
+        apu.write(0x4003, 0x18);
+        run(&mut apu, skew);
+        apu.write(0x4017, 0x80);
+        run(&mut apu, delay - 1);
+        assert_eq!(status(&mut apu), 0x01);
+        run(&mut apu, 1);
+        // The 5-step write clocked a half frame
+        apu.write(0x4017, 0x80);
+        run(&mut apu, 4);
+        assert_eq!(status(&mut apu), 0x00);
+    }
+}
+
+#[test]
+fn pulse_plays_its_duty_at_the_timer_rate() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x01);
+    // 25% duty, constant volume 15, period $0FF
+    apu.write(0x4000, 0x7F);
+    apu.write(0x4002, 0xFF);
+    apu.write(0x4003, 0x00);
+    assert_eq!(wave_period(&mut apu), 16 * 0x100);
+    let mut highs = 0;
+    for _ in 0..16 * 0x100 {
+        run(&mut apu, 1);
+        if level(&apu) > 0.0 {
+            highs += 1;
+        }
+    }
+    assert_eq!(highs, 4 * 0x100);
+}
+
+#[test]
+fn sweep_mutes_on_overflow_and_tiny_periods() {
+    let silent = |period: Word, sweep: Byte| {
+        let mut apu = Apu::new();
+        apu.write(0x4015, 0x01);
+        apu.write(0x4000, 0xBF);
+        apu.write(0x4001, sweep);
+        apu.write(0x4002, period as Byte);
+        apu.write(0x4003, (period >> 8) as Byte);
+        (0..10_000).all(|_| {
+            run(&mut apu, 1);
+            level(&apu) == 0.0
+        })
+    };
+    assert!(!silent(0x400, 0x01));
+    // The target is checked even with the sweep disabled
+    assert!(silent(0x400, 0x00));
+    assert!(silent(0x007, 0x01));
+    assert!(!silent(0x008, 0x01));
+}
+
+#[test]
+fn pulse_1_negates_with_ones_complement() {
+    for (channel, expected) in [(0x4000, 0x80), (0x4004, 0x81)] {
+        let mut apu = Apu::new();
+        apu.write(0x4015, 0x03);
+        apu.write(channel, 0xBF);
+        // Enabled, period 0, negate, shift 1
+        apu.write(channel + 1, 0x89);
+        apu.write(channel + 2, 0x01);
+        apu.write(channel + 3, 0x01);
+        // The divider starts at 0, so the first half frame sweeps
+        run(&mut apu, 14913);
+        assert_eq!(wave_period(&mut apu), 16 * (expected + 1));
+    }
+}
+
+#[test]
+fn envelope_decays_one_step_per_quarter_frame() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x01);
+    apu.write(0x4000, 0x80);
+    apu.write(0x4002, 0x10);
+    apu.write(0x4003, 0x08);
+    run(&mut apu, 7457);
+    let peak = |apu: &mut Apu| {
+        (0..7457)
+            .map(|_| {
+                run(apu, 1);
+                level(apu)
+            })
+            .fold(0.0, f32::max)
+    };
+    assert!((peak(&mut apu) - pulse_out(15, 0)).abs() < 1e-6);
+    assert!((peak(&mut apu) - pulse_out(14, 0)).abs() < 1e-6);
+    for _ in 0..14 {
+        peak(&mut apu);
+    }
+    assert_eq!(peak(&mut apu), 0.0);
+}
+
+#[test]
+fn triangle_needs_both_counters_to_run() {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x04);
+    apu.write(0x400A, 0x3F);
+    apu.write(0x400B, 0x08);
+    // Linear counter still 0: the level holds
+    let level = apu.sample();
+    run(&mut apu, 5000);
+    assert_eq!(apu.sample(), level);
+
+    apu.write(0x4008, 0xFF);
+    apu.write(0x400B, 0x08);
+    run(&mut apu, 7457);
+    let wave: Vec<u32> = (0..3 * 32 * 0x40)
+        .map(|_| {
+            run(&mut apu, 1);
+            apu.sample().to_bits()
+        })
+        .collect();
+    // 32 steps of 64 cycles
+    assert!((0..2 * 32 * 0x40).all(|i| wave[i] == wave[i + 32 * 0x40]));
+    assert!((0..32 * 0x40).any(|i| wave[i] != wave[i + 16 * 0x40]));
+    assert_eq!(wave.iter().collect::<BTreeSet<_>>().len(), 16);
+}
+
+#[test]
+fn noise_short_mode_repeats_every_93_steps() {
+    let bits = |mode: Byte| {
+        let mut apu = Apu::new();
+        apu.write(0x4015, 0x08);
+        apu.write(0x400C, 0x3F);
+        apu.write(0x400E, mode);
+        apu.write(0x400F, 0x08);
+        (0..400)
+            .map(|_| {
+                run(&mut apu, 4);
+                level(&apu) > 0.0
+            })
+            .collect::<Vec<_>>()
+    };
+    let short = bits(0x80);
+    assert!((0..300).all(|i| short[i] == short[i + 93]));
+    assert!(short.iter().any(|&bit| bit));
+    let long = bits(0x00);
+    assert!((0..300).any(|i| long[i] != long[i + 93]));
+}
+
+#[test]
+fn expansion_audio_is_mixed_in() {
+    let board = load_board(
+        &Ines::new(24, 2, 1)
+            .prg(&[0; 32 * 1024])
+            .chr(&[0; 8 * 1024])
+            .build(),
+    );
+    let mut apu = Apu::new();
+    apu.connect_cartridge(board.clone());
+    assert_eq!(apu.sample(), apu.mix());
+    // VRC6 pulse 1 held high at volume 15
+    board.borrow_mut().write(0x9000, 0x8F);
+    board.borrow_mut().write(0x9002, 0x80);
+    let expansion = board.borrow().sample_source().unwrap().sample();
+    assert!(expansion > 0.0);
+    assert_eq!(apu.sample(), apu.mix() + expansion);
+}
+
+#[test]
+fn frame_irq_reaches_the_cpu() {
+    let mut rom = vec![0xEA; 0x8000];
+    let program: [Byte; _] = [
+        Opcode::CliIMP.into(),
+        Opcode::JmpABS.into(),
+        0x01,
+        0x80,
+        // IRQ handler acknowledges the frame IRQ
+        Opcode::IncZPG.into(),
+        0x10,
+        Opcode::LdaABS.into(),
+        0x15,
+        0x40,
+        Opcode::RtiIMP.into(),
+    ];
+    rom[..program.len()].copy_from_slice(&program);
+    rom[0x7FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x04, 0x80]);
+    let board = load_board(&Ines::new(0, 2, 0).prg(&rom).build());
+
+    let apu = Rc::new(RefCell::new(Apu::new()));
+    let mut bus = NesBus::new();
+    bus.connect_apu(Box::new(apu.clone()));
+    bus.connect_cartridge(Box::new(board));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.attach_device(Box::new(apu.clone()));
+    cpu.reset();
+    cpu.pc = 0x8000;
+    while cpu.general_cycles < 3 * 29830 + 100 {
+        cpu.execute();
+    }
+    assert_eq!(cpu.peek(0x0010), 3);
+}
+
+#[test]
+fn undriven_register_bits_read_open_bus() {
+    let mut bus = NesBus::new();
+    bus.connect_apu(Box::new(Apu::new()));
+    bus.write(0x0000, 0xA5);
+    assert_eq!(bus.peek(0x4000), 0xA5);
+    assert_eq!(bus.read(0x4017, false), 0xA0);
+    bus.write(0x0000, 0xFF);
+    // status bit 5 floats
+    assert_eq!(bus.read(0x4015, false), 0x20);
+
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.reset();
+    cpu.write(0x0000, Opcode::LdaABS.into());
+    cpu.write(0x0001, 0x08);
+    cpu.write(0x0002, 0x40);
+    cpu.pc = 0x0000;
+    cpu.execute();
+    // the operand's high byte was the last value on the bus
+    assert_eq!(cpu.a, 0x40);
+}
@@ -0,0 +1,171 @@
+use std::io::Cursor;
+
+use cpu_6502::audio::{
+    AudioOutput, AudioSink, NTSC_CPU_CLOCK,
+    blip::Blip,
+    filter::{Filter, HighPass, LowPass, NesFilters},
+    wav::WavWriter,
+};
+
+const RATE: u32 = 44_100;
+
+/// Pushes `clocks` levels from `level(clock)`, returns every resulting sample
+fn resample(blip: &mut Blip, clocks: u64, level: impl Fn(u64) -> f32) -> Vec<f32> {
+    for clock in 0..clocks {
+        blip.push(level(clock));
+    }
+    blip.end_frame(None);
+    let mut out = vec![0.0; blip.samples_available()];
+    blip.read_samples(&mut out);
+    out
+}
+
+fn square(frequency: f64) -> impl Fn(u64) -> f32 {
+    move |clock| {
+        if (clock as f64 * frequency / NTSC_CPU_CLOCK).fract() < 0.5 {
+            0.5
+        } else {
+            -0.5
+        }
+    }
+}
+
+/// Sign changes, with some hysteresis against ringing around 0
+fn crossings(samples: &[f32]) -> usize {
+    let mut positive = samples[0] > 0.0;
+    let mut count = 0;
+    for &sample in samples {
+        if positive && sample < -0.05 || !positive && sample > 0.05 {
+            positive = !positive;
+            count += 1;
+        }
+    }
+    count
+}
+
+#[test]
+fn one_second_of_clocks_gives_one_second_of_samples() {
+    let mut blip = Blip::new(NTSC_CPU_CLOCK, RATE);
+    let mut total = 0;
+    // Frames of uneven lengths keep the fractional position
+    for frame in 0..60 {
+        total += resample(&mut blip, 29_829 + frame % 2, |_| 0.0).len();
+    }
+    let expected = (60.0 * 29_829.5 * RATE as f64 / NTSC_CPU_CLOCK) as usize;
+    assert!(total.abs_diff(expected) <= 1);
+}
+
+#[test]
+fn steps_settle_on_their_level() {
+    let mut blip = Blip::new(NTSC_CPU_CLOCK, RATE);
+    let out = resample(&mut blip, 10_000, |_| 0.25);
+    assert!(out.iter().rev().take(100).all(|&s| (s - 0.25).abs() < 1e-5));
+    // Band-limited: some ringing, but no sample beyond the Gibbs overshoot
+    assert!(out.iter().all(|&s| (-0.03..=0.28).contains(&s)));
+    assert!(out.iter().any(|&s| s > 0.0 && s < 0.24));
+}
+
+#[test]
+fn tones_keep_their_frequency() {
+    let mut blip = Blip::new(NTSC_CPU_CLOCK, RATE);
+    let out = resample(&mut blip, NTSC_CPU_CLOCK as u64, square(1000.0));
+    assert!(crossings(&out).abs_diff(2000) <= 1);
+}
+
+#[test]
+fn tones_above_nyquist_are_filtered_out() {
+    let mut blip = Blip::new(NTSC_CPU_CLOCK, RATE);
+    let out = resample(&mut blip, NTSC_CPU_CLOCK as u64 / 10, square(100_000.0));
+    let loudest = out[100..].iter().fold(0.0f32, |max, s| max.max(s.abs()));
+    assert!(loudest < 0.05, "{loudest}");
+}
+
+#[test]
+fn high_pass_removes_dc() {
+    let mut filter = HighPass::new(90.0, RATE);
+    let first = filter.process(1.0);
+    assert!(first > 0.9);
+    let last = (0..RATE).map(|_| filter.process(1.0)).last().unwrap();
+    assert!(last.abs() < 1e-3);
+}
+
+#[test]
+fn low_pass_smooths_the_highest_frequencies() {
+    let mut filter = LowPass::new(14_000.0, RATE);
+    let settled = (0..1000).map(|_| filter.process(1.0)).last().unwrap();
+    assert!((settled - 1.0).abs() < 1e-3);
+    let alternating: Vec<f32> = (0..1000)
+        .map(|i| filter.process(if i % 2 == 0 { 1.0 } else { -1.0 }))
+        .collect();
+    let loudest = alternating[500..]
+        .iter()
+        .fold(0.0f32, |m, s| m.max(s.abs()));
+    assert!(loudest < 0.5);
+}
+
+#[test]
+fn nes_filters_center_the_mixer_output() {
+    let mut filters = NesFilters::new(RATE);
+    let tail: Vec<f32> = (0..RATE)
+        .map(|i| filters.process(if i % 100 < 50 { 0.3 } else { 0.1 }))
+        .skip(RATE as usize - 1000)
+        .collect();
+    let mean = tail.iter().sum::<f32>() / tail.len() as f32;
+    assert!(mean.abs() < 0.01);
+    assert!(tail.iter().any(|&s| s > 0.05) && tail.iter().any(|&s| s < -0.05));
+}
+
+#[test]
+fn audio_output_plays_a_tone() {
+    let mut output = AudioOutput::new(RATE);
+    let tone = square(440.0);
+    let mut samples = Vec::new();
+    let mut clock = 0;
+    for _ in 0..60 {
+        for _ in 0..29_830 {
+            output.push(tone(clock) * 0.2 + 0.2);
+            clock += 1;
+        }
+        samples.extend_from_slice(output.end_frame());
+    }
+    let second = &samples[samples.len() - RATE as usize..];
+    assert!(crossings(second).abs_diff(880) <= 1);
+    assert!(output.end_frame().is_empty());
+}
+
+#[test]
+fn wav_writer_patches_the_header() {
+    let mut wav = WavWriter::new(Cursor::new(Vec::new()), RATE).unwrap();
+    wav.write_samples(&[0.0, 1.0, -1.0]).unwrap();
+    wav.write_samples(&[2.0, 0.5]).unwrap();
+    let bytes = wav.finish().unwrap().into_inner();
+
+    let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let u16_at = |at: usize| u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap());
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(u32_at(4), 36 + 10);
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(u16_at(20), 1);
+    assert_eq!(u16_at(22), 1);
+    assert_eq!(u32_at(24), RATE);
+    assert_eq!(u32_at(28), RATE * 2);
+    assert_eq!(u16_at(34), 16);
+    assert_eq!(&bytes[36..40], b"data");
+    assert_eq!(u32_at(40), 10);
+    let samples: Vec<i16> = bytes[44..]
+        .chunks(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    assert_eq!(samples, [0, 32767, -32767, 32767, 16384]);
+}
+
+#[test]
+fn wav_writer_creates_a_file() {
+    let path = std::env::temp_dir().join(format!("cpu_6502_wav_{}.wav", std::process::id()));
+    let mut wav = WavWriter::create(&path, 48_000).unwrap();
+    wav.write_samples(&[0.25; 480]).unwrap();
+    wav.finish().unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(bytes.len(), 44 + 960);
+}
@@ -0,0 +1,189 @@
+use cpu_6502::{
+    bus::Byte,
+    cartridge::{
+        Cartridge, CartridgeError, Mirroring,
+        header::{ConsoleType, Format, Region},
+    },
+};
+
+mod common;
+
+use common::Ines;
+
+/// PRG 16 KiB and CHR 8 KiB banks hold their bank number, CHR ones with bit 7 set
+fn numbered(ines: Ines) -> Vec<Byte> {
+    ines.prg_banks(16 * 1024, |bank, _| bank as Byte)
+        .chr_banks(8 * 1024, |bank, _| 0x80 | bank as Byte)
+        .build()
+}
+
+#[test]
+fn parses_an_ines_header() {
+    let rom = numbered(Ines::new(0x41, 2, 1).flags6(0x01));
+    let cart = Cartridge::from_bytes(&rom).unwrap();
+    let header = cart.header;
+    assert_eq!(header.format, Format::INes);
+    assert_eq!(header.mapper, 0x41);
+    assert_eq!(header.prg_rom_size, 32 * 1024);
+    assert_eq!(header.chr_rom_size, 8 * 1024);
+    assert_eq!(header.prg_ram_size, 8 * 1024);
+    assert_eq!(header.chr_ram_size, 0);
+    assert_eq!(header.mirroring, Mirroring::Vertical);
+    assert!(!header.battery);
+    assert_eq!(header.console, ConsoleType::Nes);
+    assert_eq!(header.region, Region::Ntsc);
+    assert_eq!(cart.prg_rom.len(), 32 * 1024);
+    assert_eq!(cart.chr_rom.len(), 8 * 1024);
+}
+
+#[test]
+fn ines_flags_and_chr_ram() {
+    let ines = Ines::new(0, 1, 0)
+        .flags6(0x0A)
+        .flags7(0x01)
+        .set(9, 0x01)
+        .trainer(&[0xEE; 512]);
+    let cart = Cartridge::from_bytes(&numbered(ines)).unwrap();
+    let header = cart.header;
+    assert_eq!(header.mirroring, Mirroring::FourScreen);
+    assert!(header.battery);
+    assert!(header.trainer);
+    assert_eq!(header.prg_nvram_size, 8 * 1024);
+    assert_eq!(header.prg_ram_size, 0);
+    assert_eq!(header.chr_ram_size, 8 * 1024);
+    assert!(header.has_chr_ram());
+    assert_eq!(header.console, ConsoleType::VsSystem);
+    assert_eq!(header.region, Region::Pal);
+    assert_eq!(cart.trainer, Some(vec![0xEE; 512]));
+    assert_eq!(cart.prg_rom[0], 0x00);
+}
+
+#[test]
+fn ignores_diskdude_garbage() {
+    let mut ines = Ines::new(0x01, 1, 1).flags7(b'D');
+    ines.header[8..16].copy_from_slice(b"iskDude!");
+    let cart = Cartridge::from_bytes(&numbered(ines)).unwrap();
+    assert_eq!(cart.header.mapper, 0x01);
+    assert_eq!(cart.header.console, ConsoleType::Nes);
+}
+
+#[test]
+fn parses_a_nes2_header() {
+    let ines = Ines::new(0x154, 2, 2)
+        .flags6(0x02)
+        .flags7(0x0B)
+        .submapper(3)
+        .set(10, 0x70)
+        .set(11, 0x07)
+        .set(12, 0x03)
+        .set(13, 0x05);
+    let cart = Cartridge::from_bytes(&numbered(ines)).unwrap();
+    let header = cart.header;
+    assert_eq!(header.format, Format::Nes2);
+    assert_eq!(header.mapper, 0x154);
+    assert_eq!(header.submapper, 3);
+    assert_eq!(header.prg_nvram_size, 8 * 1024);
+    assert_eq!(header.prg_ram_size, 0);
+    assert_eq!(header.chr_ram_size, 8 * 1024);
+    assert!(header.battery);
+    assert_eq!(header.mirroring, Mirroring::Horizontal);
+    assert_eq!(header.console, ConsoleType::Extended(5));
+    assert_eq!(header.region, Region::Dendy);
+}
+
+#[test]
+fn nes2_exponent_sizes() {
+    let ines = Ines::new(0, 0b0000_1101, 1).flags7(0x08).set(9, 0x0F);
+    let mut header = ines.header;
+    let rom = ines.prg(&[0xAA; 24]).chr(&[0x80; 8 * 1024]).build();
+    let cart = Cartridge::from_bytes(&rom).unwrap();
+    assert_eq!(cart.header.prg_rom_size, 24);
+    assert_eq!(cart.prg_rom, vec![0xAA; 24]);
+
+    header[4] = 0xFF;
+    assert!(matches!(
+        Cartridge::from_bytes(&header),
+        Err(CartridgeError::SizeOverflow)
+    ));
+}
+
+#[test]
+fn nes2_sizes_that_overflow_together() {
+    // 2^61 * 7 bytes of PRG and of CHR each fit a usize, their sum doesn't
+    let ines = Ines::new(0, 0xF7, 0xF7).flags7(0x08).set(9, 0xFF);
+    assert!(matches!(
+        Cartridge::from_bytes(&ines.build()),
+        Err(CartridgeError::SizeOverflow)
+    ));
+}
+
+#[test]
+fn nes2_large_bank_counts() {
+    let ines = Ines::new(0, 0x00, 0x00).flags7(0x08).set(9, 0x01);
+    let cart = Cartridge::from_bytes(&numbered(ines)).unwrap();
+    assert_eq!(cart.header.prg_rom_size, 4 * 1024 * 1024);
+    assert!(!cart.header.has_chr_ram());
+}
+
+#[test]
+fn rejects_bad_magic() {
+    let mut rom = numbered(Ines::new(0, 1, 1));
+    rom[3] = 0x00;
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::BadMagic)
+    ));
+}
+
+#[test]
+fn rejects_truncated_files() {
+    assert!(matches!(
+        Cartridge::from_bytes(b"NES\x1A"),
+        Err(CartridgeError::Truncated {
+            expected: 16,
+            actual: 4
+        })
+    ));
+    let mut rom = numbered(Ines::new(0, 2, 1));
+    rom.truncate(rom.len() - 1);
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::Truncated {
+            expected: 40976,
+            actual: 40975
+        })
+    ));
+}
+
+#[test]
+fn rejects_missing_prg() {
+    assert!(matches!(
+        Cartridge::from_bytes(&numbered(Ines::new(0, 0, 1))),
+        Err(CartridgeError::NoPrgRom)
+    ));
+}
+
+#[test]
+fn load_reports_io_errors() {
+    assert!(matches!(
+        Cartridge::load("/does/not/exist.nes"),
+        Err(CartridgeError::Io(_))
+    ));
+}
+
+#[test]
+fn exposes_banks() {
+    let cart = Cartridge::from_bytes(&numbered(Ines::new(0, 4, 2))).unwrap();
+    assert_eq!(cart.prg_bank_count(16 * 1024), 4);
+    assert_eq!(cart.prg_bank_count(32 * 1024), 2);
+    assert_eq!(cart.prg_bank(3, 16 * 1024)[0], 3);
+    assert_eq!(cart.prg_bank(5, 16 * 1024)[0], 1);
+    assert_eq!(cart.prg_bank(1, 8 * 1024)[0], 0);
+    assert_eq!(cart.chr_bank_count(4 * 1024), 4);
+    assert_eq!(cart.chr_bank(3, 4 * 1024)[0], 0x81);
+    assert_eq!(cart.prg_banks().count(), 4);
+    assert_eq!(
+        cart.chr_banks().map(|bank| bank[0]).collect::<Vec<_>>(),
+        [0x80, 0x81]
+    );
+}
@@ -0,0 +1,193 @@
+mod common;
+use std::{cell::RefCell, rc::Rc};
+
+use common::setup_cpu_bus;
+use cpu_6502::{
+    bus::{
+        Word,
+        clock::{Clocked, Lines},
+    },
+    cpu::{
+        CPU, Flag,
+        instructions::opcode::Opcode,
+        interrupt::{IRQ_VECTOR, NMI_VECTOR},
+    },
+};
+
+/// Counts cycles and raises its lines once the counter reaches `fire_at`
+#[derive(Default)]
+struct Timer {
+    ticks: u64,
+    fire_at: Option<u64>,
+    irq: bool,
+    nmi: bool,
+    stall: u32,
+}
+
+impl Clocked for Timer {
+    fn tick(&mut self, lines: &mut Lines) {
+        self.ticks += 1;
+        if self.fire_at == Some(self.ticks) {
+            if self.irq {
+                lines.assert_irq();
+            }
+            if self.nmi {
+                lines.assert_nmi();
+            }
+            lines.stall(self.stall);
+        }
+    }
+}
+
+fn setup_nop_program(cpu: &mut CPU) {
+    cpu.pc = 0x0200;
+    for addr in 0x0200..0x0210 {
+        cpu.write(addr, Opcode::NopIMP.into());
+    }
+    cpu.write(IRQ_VECTOR, 0x00);
+    cpu.write(IRQ_VECTOR + 1, 0x30);
+    cpu.write(NMI_VECTOR, 0x00);
+    cpu.write(NMI_VECTOR + 1, 0x40);
+}
+
+fn attach_timer(cpu: &mut CPU, timer: Timer) -> Rc<RefCell<Timer>> {
+    let timer = Rc::new(RefCell::new(timer));
+    cpu.attach_device(Box::new(timer.clone()));
+    timer
+}
+
+#[test]
+fn devices_advance_with_execute() {
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    let timer = attach_timer(&mut cpu, Timer::default());
+    cpu.write(0x0202, Opcode::LdaABS.into());
+    let mut total = 0;
+    for _ in 0..3 {
+        total += cpu.execute();
+    }
+    assert_eq!(total, 8);
+    assert_eq!(timer.borrow().ticks, 8);
+    assert_eq!(cpu.general_cycles, 8);
+}
+
+#[test]
+fn devices_advance_with_clock() {
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    let timer = attach_timer(&mut cpu, Timer::default());
+    for _ in 0..5 {
+        cpu.clock();
+    }
+    assert_eq!(timer.borrow().ticks, 5);
+    assert_eq!(cpu.pc, 0x0203);
+}
+
+#[test]
+fn device_irq_jumps_through_the_vector() {
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    attach_timer(
+        &mut cpu,
+        Timer {
+            fire_at: Some(2),
+            irq: true,
+            ..Timer::default()
+        },
+    );
+    cpu.execute();
+    let cycles = cpu.execute();
+    assert_eq!(cycles, 7);
+    assert_eq!(cpu.pc, 0x3000);
+    assert!(cpu.flag.contains(Flag::INTERRUPT_DISABLE));
+    assert_eq!(cpu.read_byte(0x01FD), 0x02);
+    assert_eq!(cpu.read_byte(0x01FC), 0x01);
+    assert_eq!(cpu.read_byte(0x01FB), Flag::UNUSED.bits());
+}
+
+#[test]
+fn device_irq_is_masked_by_interrupt_disable() {
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    cpu.flag.insert(Flag::INTERRUPT_DISABLE);
+    attach_timer(
+        &mut cpu,
+        Timer {
+            fire_at: Some(2),
+            irq: true,
+            ..Timer::default()
+        },
+    );
+    cpu.execute();
+    cpu.execute();
+    assert_eq!(cpu.pc, 0x0202);
+}
+
+#[test]
+fn device_nmi_is_taken_once_per_edge() {
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    cpu.flag.insert(Flag::INTERRUPT_DISABLE);
+    attach_timer(
+        &mut cpu,
+        Timer {
+            fire_at: Some(2),
+            nmi: true,
+            ..Timer::default()
+        },
+    );
+    cpu.write(0x4000, Opcode::NopIMP.into());
+    cpu.write(0x4001, Opcode::NopIMP.into());
+    cpu.execute();
+    cpu.execute();
+    assert_eq!(cpu.pc, 0x4000);
+    cpu.execute();
+    assert_eq!(cpu.pc, 0x4001);
+}
+
+#[test]
+fn device_can_stall_the_cpu() {
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    let timer = attach_timer(
+        &mut cpu,
+        Timer {
+            fire_at: Some(2),
+            stall: 3,
+            ..Timer::default()
+        },
+    );
+    cpu.execute();
+    let cycles = cpu.execute();
+    assert_eq!(cycles, 5);
+    assert_eq!(cpu.pc, 0x0202);
+    assert_eq!(timer.borrow().ticks, 7);
+}
+
+#[test]
+fn stall_holds_clock_before_fetching() {
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    cpu.stall(2);
+    cpu.clock();
+    cpu.clock();
+    assert_eq!(cpu.pc, 0x0200 as Word);
+    cpu.clock();
+    assert_eq!(cpu.pc, 0x0201 as Word);
+    assert_eq!(cpu.general_cycles, 3);
+}
+
+#[test]
+fn manual_nmi_and_irq() {
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    cpu.nmi();
+    cpu.execute();
+    assert_eq!(cpu.pc, 0x4000);
+
+    let mut cpu = setup_cpu_bus();
+    setup_nop_program(&mut cpu);
+    cpu.set_irq(true);
+    cpu.execute();
+    assert_eq!(cpu.pc, 0x3000);
+}
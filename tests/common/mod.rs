@@ -1,4 +1,19 @@
-use cpu_6502::{bus::simple_bus::SimpleBus, cpu::CPU};
+// Each test crate uses its own subset of these helpers
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use cpu_6502::{
+    bus::{Byte, simple_bus::SimpleBus},
+    cartridge::{
+        Cartridge,
+        mapper::{self, SharedMapper},
+    },
+    cpu::CPU,
+};
+
+const PRG_UNIT: usize = 16 * 1024;
+const CHR_UNIT: usize = 8 * 1024;
 
 pub fn setup_cpu_bus() -> CPU {
     let mut cpu = CPU::new();
@@ -7,3 +22,123 @@ pub fn setup_cpu_bus() -> CPU {
     cpu.reset();
     cpu
 }
+
+/// Test ROMs aren't shipped with the tree, so the tests using them are
+/// `#[ignore]`d. Once the files are in place, run them with `--ignored`;
+/// this fails naming the first one still missing.
+pub fn require_test_files(paths: &[&str]) {
+    if let Some(missing) = paths.iter().map(Path::new).find(|path| !path.exists()) {
+        panic!("{} not found", missing.display());
+    }
+}
+
+/// ### iNES image builder
+/// Header fields are kept as set, never derived from the data, so tests can
+/// build inconsistent images as well. `header` is public for raw edits.
+#[derive(Debug, Clone)]
+pub struct Ines {
+    pub header: [Byte; 16],
+    trainer: Vec<Byte>,
+    prg: Vec<Byte>,
+    chr: Vec<Byte>,
+}
+
+impl Ines {
+    /// Header for board `mapper` with `prg` 16 KiB and `chr` 8 KiB banks.
+    /// Mapper bits above 8 go to the NES 2.0 byte, which stays iNES until set.
+    pub fn new(mapper: u16, prg: Byte, chr: Byte) -> Self {
+        let mut header = [0; 16];
+        header[0..4].copy_from_slice(b"NES\x1A");
+        header[4] = prg;
+        header[5] = chr;
+        header[6] = (mapper << 4) as Byte;
+        header[7] = (mapper & 0xF0) as Byte;
+        header[8] = (mapper >> 8) as Byte & 0x0F;
+        Self {
+            header,
+            trainer: Vec::new(),
+            prg: Vec::new(),
+            chr: Vec::new(),
+        }
+    }
+
+    /// Sets bits of flags 6: mirroring, battery, trainer, four screen.
+    pub fn flags6(mut self, flags: Byte) -> Self {
+        self.header[6] |= flags;
+        self
+    }
+
+    /// Sets bits of flags 7: console type and format.
+    pub fn flags7(mut self, flags: Byte) -> Self {
+        self.header[7] |= flags;
+        self
+    }
+
+    /// Makes the header NES 2.0 with `submapper`.
+    pub fn submapper(mut self, submapper: Byte) -> Self {
+        self.header[7] |= 0x08;
+        self.header[8] |= submapper << 4;
+        self
+    }
+
+    pub fn set(mut self, at: usize, value: Byte) -> Self {
+        self.header[at] = value;
+        self
+    }
+
+    pub fn trainer(mut self, trainer: &[Byte]) -> Self {
+        self.header[6] |= 0x04;
+        self.trainer = trainer.to_vec();
+        self
+    }
+
+    pub fn prg(mut self, data: &[Byte]) -> Self {
+        self.prg.extend_from_slice(data);
+        self
+    }
+
+    pub fn chr(mut self, data: &[Byte]) -> Self {
+        self.chr.extend_from_slice(data);
+        self
+    }
+
+    /// Fills the PRG-ROM the header declares with `size` byte banks,
+    /// byte `offset` of bank `bank` being `fill(bank, offset)`.
+    pub fn prg_banks(self, size: usize, fill: impl Fn(usize, usize) -> Byte) -> Self {
+        let banks = self.rom_units(4, 0) * PRG_UNIT / size;
+        self.prg(&numbered(banks, size, fill))
+    }
+
+    /// Fills the CHR-ROM the header declares, like [`Ines::prg_banks`].
+    pub fn chr_banks(self, size: usize, fill: impl Fn(usize, usize) -> Byte) -> Self {
+        let banks = self.rom_units(5, 4) * CHR_UNIT / size;
+        self.chr(&numbered(banks, size, fill))
+    }
+
+    pub fn build(&self) -> Vec<Byte> {
+        [&self.header[..], &self.trainer, &self.prg, &self.chr].concat()
+    }
+
+    /// Bank count of header byte `lsb`, with its NES 2.0 MSB nibble from byte 9
+    fn rom_units(&self, lsb: usize, msb_shift: u32) -> usize {
+        let nes2 = self.header[7] & 0x0C == 0x08;
+        let msb = if nes2 {
+            (self.header[9] >> msb_shift) as usize & 0x0F
+        } else {
+            0
+        };
+        msb << 8 | self.header[lsb] as usize
+    }
+}
+
+fn numbered(banks: usize, size: usize, fill: impl Fn(usize, usize) -> Byte) -> Vec<Byte> {
+    (0..banks)
+        .flat_map(|bank| (0..size).map(move |offset| (bank, offset)))
+        .map(|(bank, offset)| fill(bank, offset))
+        .collect()
+}
+
+/// The board for an iNES image.
+pub fn load_board(image: &[Byte]) -> SharedMapper {
+    mapper::from_cartridge(Cartridge::from_bytes(image).unwrap()).unwrap()
+}
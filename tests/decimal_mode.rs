@@ -0,0 +1,122 @@
+mod common;
+use common::setup_cpu_bus;
+use cpu_6502::{
+    bus::{Byte, simple_bus::SimpleBus},
+    cpu::{CPU, Flag, Profile, instructions::opcode::Opcode},
+};
+
+fn run_decimal(cpu: &mut CPU, op: Opcode, a: Byte, operand: Byte, carry: bool) -> i32 {
+    cpu.write(0xFFFC, op.into());
+    cpu.write(0xFFFD, operand);
+    cpu.a = a;
+    cpu.flag.insert(Flag::DECIMAL_MODE);
+    cpu.flag.set(Flag::CARRY, carry);
+    cpu.execute()
+}
+
+fn setup_2a03() -> CPU {
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(SimpleBus::default()));
+    cpu.reset();
+    cpu
+}
+
+#[test]
+fn adc_adds_bcd_digits() {
+    for (a, operand, carry, answer, carry_out) in [
+        (0x09, 0x01, false, 0x10, false),
+        (0x58, 0x46, true, 0x05, true),
+        (0x99, 0x01, false, 0x00, true),
+        (0x12, 0x34, false, 0x46, false),
+    ] {
+        let mut cpu = setup_cpu_bus();
+        let cycles = run_decimal(&mut cpu, Opcode::AdcIMM, a, operand, carry);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.a, answer, "{a:02X} + {operand:02X}");
+        assert_eq!(cpu.flag.contains(Flag::CARRY), carry_out);
+    }
+}
+
+#[test]
+fn adc_decimal_zero_flag_follows_binary_sum() {
+    let mut cpu = setup_cpu_bus();
+    run_decimal(&mut cpu, Opcode::AdcIMM, 0x99, 0x01, false);
+    assert_eq!(cpu.a, 0x00);
+    assert!(!cpu.flag.contains(Flag::ZERO));
+}
+
+#[test]
+fn sbc_subtracts_bcd_digits() {
+    for (a, operand, carry, answer, carry_out) in [
+        (0x46, 0x12, true, 0x34, true),
+        (0x40, 0x13, true, 0x27, true),
+        (0x32, 0x02, false, 0x29, true),
+        (0x00, 0x01, true, 0x99, false),
+    ] {
+        let mut cpu = setup_cpu_bus();
+        run_decimal(&mut cpu, Opcode::SbcIMM, a, operand, carry);
+        assert_eq!(cpu.a, answer, "{a:02X} - {operand:02X}");
+        assert_eq!(cpu.flag.contains(Flag::CARRY), carry_out);
+    }
+}
+
+#[test]
+fn ricoh_2a03_ignores_decimal_flag() {
+    let mut cpu = setup_2a03();
+    run_decimal(&mut cpu, Opcode::AdcIMM, 0x09, 0x01, false);
+    assert_eq!(cpu.a, 0x0A);
+    assert!(cpu.flag.contains(Flag::DECIMAL_MODE));
+
+    let mut cpu = setup_2a03();
+    run_decimal(&mut cpu, Opcode::SbcIMM, 0x00, 0x01, true);
+    assert_eq!(cpu.a, 0xFF);
+    assert!(!cpu.flag.contains(Flag::CARRY));
+}
+
+/// Runs `op` with the decimal flag set on a 2A03, checks A and `N`, `V`, `Z`, `C`
+fn check_binary(op: Opcode, carry: bool, cases: &[(Byte, Byte, Byte, Flag)]) {
+    let nvzc = (Flag::NEGATIVE | Flag::OVERFLOW | Flag::ZERO | Flag::CARRY).bits();
+    for (a, operand, answer, flags) in cases {
+        let mut cpu = setup_2a03();
+        let cycles = run_decimal(&mut cpu, op, *a, *operand, carry);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.a, *answer, "{op:?} {a:02X} {operand:02X}");
+        assert_eq!(
+            cpu.flag.bits() & nvzc,
+            flags.bits(),
+            "{op:?} {a:02X} {operand:02X}"
+        );
+    }
+}
+
+#[test]
+fn ricoh_2a03_keeps_binary_results() {
+    check_binary(
+        Opcode::AdcIMM,
+        false,
+        &[
+            (0x00, 0x00, 0x00, Flag::ZERO),
+            (0x09, 0x01, 0x0A, Flag::empty()),
+            (0x7F, 0x01, 0x80, Flag::NEGATIVE | Flag::OVERFLOW),
+            (0xFF, 0x01, 0x00, Flag::ZERO | Flag::CARRY),
+            (0x99, 0x01, 0x9A, Flag::NEGATIVE),
+        ],
+    );
+    check_binary(
+        Opcode::SbcIMM,
+        true,
+        &[
+            (0x00, 0x01, 0xFF, Flag::NEGATIVE),
+            (0x10, 0x01, 0x0F, Flag::CARRY),
+            (0x80, 0x01, 0x7F, Flag::OVERFLOW | Flag::CARRY),
+            (0x46, 0x12, 0x34, Flag::CARRY),
+        ],
+    );
+}
+
+#[test]
+fn profiles() {
+    assert_eq!(CPU::new().profile, Profile::Nmos6502);
+    assert!(Profile::Nmos6502.has_decimal_mode());
+    assert!(!Profile::Ricoh2A03.has_decimal_mode());
+}
@@ -0,0 +1,112 @@
+use cpu_6502::{
+    bus::Byte,
+    cartridge::{Mirroring, mapper::SharedMapper},
+};
+
+mod common;
+
+use common::{Ines, load_board};
+
+/// Image with `prg` 16 KiB and `chr` 8 KiB banks. Every byte of a PRG bank holds its
+/// bank number except the first page, which is `0xFF` so writes there don't conflict.
+fn load(mapper: u16, submapper: Option<Byte>, prg: Byte, chr: Byte) -> SharedMapper {
+    let mut ines = Ines::new(mapper, prg, chr);
+    if let Some(submapper) = submapper {
+        ines = ines.submapper(submapper);
+    }
+    let image = ines
+        .prg_banks(
+            16 * 1024,
+            |bank, offset| {
+                if offset < 0x100 { 0xFF } else { bank as Byte }
+            },
+        )
+        .chr_banks(8 * 1024, |bank, _| 0x80 | bank as Byte)
+        .build();
+    load_board(&image)
+}
+
+#[test]
+fn uxrom_switches_the_low_bank() {
+    let board = load(2, None, 8, 0);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x8100, false), 0);
+    assert_eq!(board.read(0xC100, false), 7);
+    board.write(0x8000, 5);
+    assert_eq!(board.read(0x8100, false), 5);
+    assert_eq!(board.read(0xC100, false), 7);
+    board.ppu_write(0x0123, 0x42);
+    assert_eq!(board.ppu_read(0x0123), 0x42);
+}
+
+#[test]
+fn uxrom_bus_conflicts() {
+    let board = load(2, None, 8, 0);
+    let mut board = board.borrow_mut();
+    // ROM drives 0x07 at $C100, 0x05 & 0x07 == 0x05
+    board.write(0xC100, 0x0D);
+    assert_eq!(board.read(0x8100, false), 5);
+
+    let board = load(2, Some(1), 8, 0);
+    let mut board = board.borrow_mut();
+    board.write(0xC100, 0x03);
+    assert_eq!(board.read(0x8100, false), 3);
+}
+
+#[test]
+fn cnrom_switches_chr() {
+    let board = load(3, None, 2, 4);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.ppu_read(0x0000), 0x80);
+    board.write(0x8000, 2);
+    assert_eq!(board.ppu_read(0x1FFF), 0x82);
+    // ROM drives 0x01 at $C100
+    board.write(0xC100, 0x03);
+    assert_eq!(board.ppu_read(0x0000), 0x81);
+    assert_eq!(board.read(0x8100, false), 0);
+    assert_eq!(board.read(0xC100, false), 1);
+}
+
+#[test]
+fn axrom_single_screen() {
+    let board = load(7, None, 8, 0);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.mirroring(), Mirroring::SingleScreenLower);
+    board.write(0x8100, 0x12);
+    assert_eq!(board.mirroring(), Mirroring::SingleScreenUpper);
+    assert_eq!(board.read(0x8100, false), 4);
+    assert_eq!(board.read(0xC100, false), 5);
+    board.write(0x8000, 0x03);
+    assert_eq!(board.mirroring(), Mirroring::SingleScreenLower);
+    assert_eq!(board.read(0xC100, false), 7);
+}
+
+#[test]
+fn amrom_has_bus_conflicts() {
+    let board = load(7, Some(2), 8, 0);
+    let mut board = board.borrow_mut();
+    // ROM drives 0x00 at $8100 after power on
+    board.write(0x8100, 0x13);
+    assert_eq!(board.mirroring(), Mirroring::SingleScreenLower);
+    assert_eq!(board.read(0x8100, false), 0);
+}
+
+#[test]
+fn gxrom_switches_prg_and_chr() {
+    let board = load(66, None, 8, 4);
+    let mut board = board.borrow_mut();
+    board.write(0x8000, 0x31);
+    assert_eq!(board.read(0x8100, false), 6);
+    assert_eq!(board.read(0xC100, false), 7);
+    assert_eq!(board.ppu_read(0x0000), 0x81);
+}
+
+#[test]
+fn color_dreams_switches_prg_and_chr() {
+    let board = load(11, None, 8, 16);
+    let mut board = board.borrow_mut();
+    board.write(0x8000, 0xA2);
+    assert_eq!(board.read(0x8100, false), 4);
+    assert_eq!(board.read(0xC100, false), 5);
+    assert_eq!(board.ppu_read(0x0000), 0x8A);
+}
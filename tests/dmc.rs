@@ -0,0 +1,233 @@
+use std::{cell::RefCell, rc::Rc};
+
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    apu::Apu,
+    audio::SampleSource,
+    bus::{
+        Bus, Byte, Word,
+        clock::{Clocked, Lines},
+    },
+    cpu::{Access, CPU, Profile, instructions::opcode::Opcode, observer::BusEvent},
+    nes::bus::NesBus,
+    ppu::Ppu,
+};
+
+/// Ticks the APU, serving its DMA reads with `memory(addr)`
+fn run(apu: &mut Apu, cycles: usize, memory: impl Fn(Word) -> Byte) -> Vec<Word> {
+    let mut fetched = Vec::new();
+    for _ in 0..cycles {
+        let mut lines = Lines::default();
+        apu.tick(&mut lines);
+        if let Some(addr) = lines.dma {
+            fetched.push(addr);
+            apu.dma_data(addr, memory(addr));
+        }
+    }
+    fetched
+}
+
+fn irq(apu: &mut Apu) -> bool {
+    let mut lines = Lines::default();
+    apu.tick(&mut lines);
+    lines.irq
+}
+
+/// Rate `$F` plays a bit every 54 cycles, a byte every 432
+fn start(apu: &mut Apu, flags: Byte, address: Byte, length: Byte) {
+    apu.write(0x4010, flags | 0x0F);
+    apu.write(0x4012, address);
+    apu.write(0x4013, length);
+    apu.write(0x4015, 0x10);
+}
+
+#[test]
+fn fetches_the_sample_then_stops() {
+    let mut apu = Apu::new();
+    start(&mut apu, 0x00, 0x01, 0x01);
+    assert_eq!(apu.peek(0x4015) & 0x10, 0x10);
+    let fetched = run(&mut apu, 20 * 432, |_| 0);
+    let expected: Vec<Word> = (0xC040..0xC051).collect();
+    assert_eq!(fetched, expected);
+    assert_eq!(apu.peek(0x4015) & 0x10, 0x00);
+}
+
+#[test]
+fn addresses_wrap_to_8000() {
+    let mut apu = Apu::new();
+    start(&mut apu, 0x00, 0xFF, 0x04);
+    let fetched = run(&mut apu, 70 * 432, |_| 0);
+    assert_eq!(fetched.len(), 65);
+    assert_eq!(fetched[63], 0xFFFF);
+    assert_eq!(fetched[64], 0x8000);
+}
+
+#[test]
+fn loop_restarts_the_sample() {
+    let mut apu = Apu::new();
+    start(&mut apu, 0x40, 0x00, 0x00);
+    let fetched = run(&mut apu, 5 * 432, |_| 0);
+    assert!(fetched.len() >= 4);
+    assert!(fetched.iter().all(|&addr| addr == 0xC000));
+    assert!(!irq(&mut apu));
+}
+
+#[test]
+fn irq_when_the_sample_ends() {
+    let mut apu = Apu::new();
+    start(&mut apu, 0x80, 0x00, 0x00);
+    run(&mut apu, 1, |_| 0);
+    assert!(irq(&mut apu));
+    assert_eq!(apu.peek(0x4015) & 0x80, 0x80);
+    // Reading `$4015` leaves it, writing acknowledges it
+    apu.read(0x4015, false);
+    assert!(irq(&mut apu));
+    apu.write(0x4015, 0x00);
+    assert!(!irq(&mut apu));
+
+    // So does clearing the IRQ enable
+    let mut apu = Apu::new();
+    start(&mut apu, 0x80, 0x00, 0x00);
+    run(&mut apu, 1, |_| 0);
+    assert!(irq(&mut apu));
+    apu.write(0x4010, 0x0F);
+    assert!(!irq(&mut apu));
+}
+
+#[test]
+fn output_moves_by_2_per_bit_within_7_bits() {
+    let mut apu = Apu::new();
+    let level = |apu: &Apu| apu.sample();
+    apu.write(0x4011, 0x40);
+    let direct = level(&apu);
+    apu.write(0x4011, 0x00);
+    assert!(direct > level(&apu));
+
+    // A byte of ones climbs from 120 and stops at 126
+    apu.write(0x4011, 120);
+    start(&mut apu, 0x40, 0x00, 0x00);
+    run(&mut apu, 20 * 432, |_| 0xFF);
+    let top = level(&apu);
+    apu.write(0x4011, 126);
+    assert_eq!(level(&apu), top);
+
+    // Zeroes walk it back down to 0 or 1
+    start(&mut apu, 0x40, 0x00, 0x00);
+    run(&mut apu, 100 * 432, |_| 0x00);
+    let bottom = level(&apu);
+    apu.write(0x4011, 0);
+    assert_eq!(level(&apu), bottom);
+}
+
+#[test]
+fn disabling_stops_fetching() {
+    let mut apu = Apu::new();
+    start(&mut apu, 0x00, 0x00, 0xFF);
+    run(&mut apu, 2 * 432, |_| 0);
+    apu.write(0x4015, 0x00);
+    assert_eq!(apu.peek(0x4015) & 0x10, 0x00);
+    assert!(run(&mut apu, 10 * 432, |_| 0).is_empty());
+}
+
+type Events = Rc<RefCell<Vec<BusEvent>>>;
+
+/// NES with a DMC playing 17 bytes from `$C000` in a loop, running NOPs at `$8000`.
+/// `$C000-$C010` hold their low address byte.
+fn nes() -> (CPU, Rc<RefCell<Ppu>>, Events) {
+    let mut prg = vec![Opcode::NopIMP.into(); 0x8000];
+    for i in 0..0x11 {
+        prg[0x4000 + i] = i as Byte;
+    }
+    prg[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    let board = load_board(&Ines::new(0, 2, 0).prg(&prg).build());
+
+    let apu = Rc::new(RefCell::new(Apu::new()));
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    let mut bus = NesBus::new();
+    bus.connect_apu(Box::new(apu.clone()));
+    bus.connect_ppu(Box::new(ppu.clone()));
+    bus.connect_cartridge(Box::new(board));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.attach_device(Box::new(apu.clone()));
+    cpu.reset();
+    cpu.pc = 0x8000;
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let sink = events.clone();
+    cpu.add_observer(move |event| sink.borrow_mut().push(*event));
+    cpu.write(0x4010, 0x4F);
+    cpu.write(0x4012, 0x00);
+    cpu.write(0x4013, 0x01);
+    cpu.write(0x4015, 0x10);
+    (cpu, ppu, events)
+}
+
+fn dmc_reads(events: &[BusEvent]) -> Vec<&BusEvent> {
+    events
+        .iter()
+        .filter(|e| e.access == Access::DmaRead && e.addr >= 0xC000)
+        .collect()
+}
+
+#[test]
+fn sample_dma_steals_3_or_4_cycles() {
+    let (mut cpu, _, events) = nes();
+    let mut stolen = Vec::new();
+    for _ in 0..5000 {
+        let reads = dmc_reads(&events.borrow()).len();
+        let cycles = cpu.execute();
+        if dmc_reads(&events.borrow()).len() > reads {
+            stolen.push(cycles - 2);
+        }
+    }
+    assert!(stolen.len() > 17);
+    assert!(stolen.iter().all(|&cycles| cycles == 3 || cycles == 4));
+    assert!(stolen.contains(&3) && stolen.contains(&4));
+
+    let events = events.borrow();
+    let values: Vec<Byte> = dmc_reads(&events).iter().map(|e| e.value).collect();
+    assert_eq!(
+        &values[..18],
+        [(0..0x11).collect::<Vec<_>>(), vec![0]].concat()
+    );
+    // The halted cycles repeat the opcode fetch
+    let read = dmc_reads(&events)[0].cycle;
+    assert!(
+        events
+            .iter()
+            .any(|e| e.access == Access::DummyRead && e.cycle == read - 1)
+    );
+}
+
+#[test]
+fn sample_dma_during_oam_dma_costs_2_cycles() {
+    // Start the OAM DMA at every point of a sample byte
+    for start in 0..432 {
+        let (mut cpu, ppu, events) = nes();
+        for i in 0..=0xFF {
+            cpu.write(0x0200 + i, i as Byte);
+        }
+        while cpu.general_cycles < 1000 + start {
+            cpu.execute();
+        }
+        let alone = if cpu.general_cycles.is_multiple_of(2) {
+            514
+        } else {
+            513
+        };
+        let before = dmc_reads(&events.borrow()).len();
+        cpu.write(0x4014, 0x02);
+        let extra = cpu.execute() - 2 - alone;
+        let during = dmc_reads(&events.borrow()).len() - before;
+        assert_eq!(ppu.borrow().oam.to_vec(), (0..=0xFF).collect::<Vec<Byte>>());
+        match during {
+            1 => assert_eq!(extra, 2),
+            // One fetch landing on either end of the transfer costs 1 or 3
+            2 => assert!((3..=5).contains(&extra)),
+            _ => panic!("{during} sample fetches during one OAM DMA"),
+        }
+    }
+}
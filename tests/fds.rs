@@ -0,0 +1,337 @@
+use cpu_6502::{
+    bus::{Bus, Byte, clock::Lines},
+    cartridge::{
+        CartridgeError,
+        fds::{
+            Fds,
+            disk::{DiskImage, SIDE_SIZE},
+        },
+        mapper::Mapper,
+    },
+    nes::bus::NesBus,
+};
+
+fn disk_info() -> Vec<Byte> {
+    let mut block = b"\x01*NINTENDO-HVC*".to_vec();
+    block.resize(56, 0x11);
+    block
+}
+
+fn file_header(id: Byte, size: u16) -> Vec<Byte> {
+    let mut block = vec![0x03, id, id];
+    block.extend(b"FILENAME");
+    block.extend([0x00, 0x60]);
+    block.extend(size.to_le_bytes());
+    block.push(0x00);
+    block
+}
+
+/// One side holding a single 4 byte file
+fn side(fill: Byte) -> Vec<Byte> {
+    let mut side = disk_info();
+    side.extend([0x02, 0x01]);
+    side.extend(file_header(0, 4));
+    side.extend([0x04, fill, fill, fill, fill]);
+    side.resize(SIDE_SIZE, 0);
+    side
+}
+
+fn image(sides: usize, fwnes: bool) -> Vec<Byte> {
+    let mut bytes = Vec::new();
+    if fwnes {
+        bytes.extend(b"FDS\x1A");
+        bytes.push(sides as Byte);
+        bytes.resize(16, 0);
+    }
+    for i in 0..sides {
+        bytes.extend(side(0xA0 + i as Byte));
+    }
+    bytes
+}
+
+fn fds(sides: usize) -> Fds {
+    let mut bios = vec![0xEA; 8 * 1024];
+    bios[0x1FFC] = 0x34;
+    Fds::new(bios, DiskImage::from_bytes(&image(sides, true)).unwrap()).unwrap()
+}
+
+fn clock(fds: &mut Fds, cycles: usize) -> bool {
+    let mut irq = false;
+    for _ in 0..cycles {
+        let mut lines = Lines::default();
+        fds.clock(&mut lines);
+        irq |= lines.irq;
+    }
+    irq
+}
+
+/// Waits for the drive to move a byte, returning what it read
+fn next_byte(fds: &mut Fds) -> Byte {
+    for _ in 0..1_000_000 {
+        if fds.peek(0x4030) & 0x02 != 0 {
+            return fds.read(0x4031, false);
+        }
+        clock(fds, 1);
+    }
+    panic!("drive stalled");
+}
+
+/// Reads a block of `size` bytes with its gap end mark and CRC
+fn read_block(fds: &mut Fds, size: usize) -> Vec<Byte> {
+    fds.write(0x4025, 0x65);
+    assert_eq!(next_byte(fds), 0x80);
+    let block = (0..size).map(|_| next_byte(fds)).collect();
+    next_byte(fds);
+    next_byte(fds);
+    // the BIOS lets the head pass a gap byte before looking for the next block
+    fds.write(0x4025, 0x25);
+    clock(fds, 150);
+    block
+}
+
+fn write_block(fds: &mut Fds, block: &[Byte]) {
+    fds.write(0x4025, 0x21);
+    clock(fds, 150 * 20);
+    fds.write(0x4024, 0x80);
+    fds.write(0x4025, 0x61);
+    for &byte in block {
+        next_byte(fds);
+        fds.write(0x4024, byte);
+    }
+    next_byte(fds);
+    fds.write(0x4025, 0x71);
+    clock(fds, 150 * 2);
+}
+
+#[test]
+fn parses_images_with_and_without_header() {
+    let with = DiskImage::from_bytes(&image(2, true)).unwrap();
+    let without = DiskImage::from_bytes(&image(2, false)).unwrap();
+    assert_eq!(with.sides, without.sides);
+    assert_eq!(with.sides.len(), 2);
+    assert_eq!(with.to_bytes(), image(2, true));
+    assert_eq!(without.to_bytes(), image(2, false));
+}
+
+#[test]
+fn rejects_bad_images_and_bios() {
+    assert!(matches!(
+        DiskImage::from_bytes(&[0; SIDE_SIZE]),
+        Err(CartridgeError::BadMagic)
+    ));
+    let mut truncated = image(1, false);
+    truncated.pop();
+    assert!(matches!(
+        DiskImage::from_bytes(&truncated),
+        Err(CartridgeError::Truncated {
+            expected: SIDE_SIZE,
+            ..
+        })
+    ));
+    let disk = DiskImage::from_bytes(&image(1, false)).unwrap();
+    assert!(matches!(
+        Fds::new(vec![0; 4096], disk),
+        Err(CartridgeError::BadBios(4096))
+    ));
+}
+
+#[test]
+fn raw_sides_round_trip() {
+    let mut disk = DiskImage::from_bytes(&image(1, false)).unwrap();
+    let raw = disk.raw_side(0);
+    assert!(raw.len() > SIDE_SIZE);
+    assert_eq!(raw.iter().position(|&b| b != 0).map(|i| raw[i]), Some(0x80));
+    disk.store_raw_side(0, &raw);
+    assert_eq!(disk.sides[0], side(0xA0));
+    assert!(!disk.is_modified());
+}
+
+#[test]
+fn changes_are_kept_as_an_ips_diff() {
+    let mut disk = DiskImage::from_bytes(&image(2, true)).unwrap();
+    assert_eq!(disk.diff(), b"PATCHEOF");
+    disk.sides[1][100] = 0x42;
+    disk.sides[1][101] = 0x43;
+    let diff = disk.diff();
+    let offset = (16 + SIDE_SIZE + 100) as u32;
+    assert_eq!(&diff[5..8], &offset.to_be_bytes()[1..]);
+    assert_eq!(&diff[8..12], [0x00, 0x02, 0x42, 0x43]);
+
+    let path = std::env::temp_dir().join(format!("fds-{}.ips", std::process::id()));
+    disk.save_diff(&path).unwrap();
+    let mut fresh = DiskImage::from_bytes(&image(2, true)).unwrap();
+    fresh.load_diff(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(fresh.sides, disk.sides);
+    assert!(fresh.is_modified());
+    assert!(fresh.apply_diff(b"PATCH\x00").is_err());
+    assert_eq!(
+        DiskImage::diff_path("games/zelda.fds"),
+        std::path::Path::new("games/zelda.ips")
+    );
+}
+
+#[test]
+fn memory_map() {
+    let mut fds = fds(1);
+    fds.write(0x6000, 0x12);
+    fds.write(0xDFFF, 0x34);
+    fds.write(0xFFFC, 0x00);
+    assert_eq!(fds.read(0x6000, false), 0x12);
+    assert_eq!(fds.read(0xDFFF, false), 0x34);
+    assert_eq!(fds.read(0xFFFC, false), 0x34);
+    fds.ppu_write(0x1FFF, 0x56);
+    assert_eq!(fds.ppu_read(0x1FFF), 0x56);
+    // disk registers are off until $4023 enables them
+    assert_eq!(fds.driven_bits(0x4032), 0x00);
+}
+
+#[test]
+fn undriven_register_bits_read_open_bus() {
+    let mut bus = NesBus::new();
+    bus.connect_cartridge(Box::new(fds(1)));
+    bus.write(0x4023, 0x03);
+    bus.write(0x0000, 0xA8);
+    // only D0-D2 of the drive status are driven
+    assert_eq!(bus.read(0x4032, false), 0xAA);
+    // and D0-D5 of the wavetable
+    bus.write(0x0000, 0xFF);
+    assert_eq!(bus.read(0x4040, false), 0xC0);
+}
+
+#[test]
+fn timer_irq() {
+    let mut fds = fds(1);
+    fds.write(0x4023, 0x01);
+    fds.write(0x4020, 10);
+    fds.write(0x4021, 0);
+    fds.write(0x4022, 0x02);
+    assert!(!clock(&mut fds, 10));
+    assert!(clock(&mut fds, 1));
+    assert_eq!(fds.read(0x4030, false) & 0x01, 0x01);
+    assert!(!clock(&mut fds, 100));
+
+    fds.write(0x4022, 0x03);
+    assert!(clock(&mut fds, 11));
+    fds.read(0x4030, false);
+    assert!(clock(&mut fds, 11));
+    fds.write(0x4023, 0x00);
+    assert!(!clock(&mut fds, 100));
+}
+
+#[test]
+fn reads_blocks_from_the_drive() {
+    let mut fds = fds(1);
+    fds.write(0x4023, 0x01);
+    assert_eq!(fds.read(0x4032, false) & 0x07, 0x02);
+    assert_eq!(read_block(&mut fds, 56), disk_info());
+    assert_eq!(fds.read(0x4032, false) & 0x07, 0x00);
+    assert_eq!(read_block(&mut fds, 2), [0x02, 0x01]);
+    assert_eq!(read_block(&mut fds, 16), file_header(0, 4));
+    assert_eq!(read_block(&mut fds, 5), [0x04, 0xA0, 0xA0, 0xA0, 0xA0]);
+}
+
+#[test]
+fn byte_transfer_irq() {
+    let mut fds = fds(1);
+    fds.write(0x4023, 0x01);
+    fds.write(0x4025, 0xE5);
+    let mut cycles = 0;
+    while !clock(&mut fds, 1) {
+        cycles += 1;
+    }
+    // the gap end mark doesn't raise an IRQ, the first block byte does
+    assert_eq!(fds.read(0x4031, false), 0x01);
+    assert!(cycles > 28300 / 8 * 150);
+    assert!(!clock(&mut fds, 100));
+}
+
+#[test]
+fn writes_a_new_file_after_the_last_one() {
+    let mut fds = fds(1);
+    fds.write(0x4023, 0x01);
+    for size in [56, 2, 16, 5] {
+        read_block(&mut fds, size);
+    }
+    write_block(&mut fds, &file_header(1, 3));
+    write_block(&mut fds, &[0x04, 0x55, 0x66, 0x77]);
+    fds.write(0x4025, 0x20);
+
+    let disk = fds.disk();
+    assert!(disk.is_modified());
+    let mut expected = side(0xA0);
+    let end = 56 + 2 + 16 + 5;
+    let mut new_file = file_header(1, 3);
+    new_file.extend([0x04, 0x55, 0x66, 0x77]);
+    expected[end..end + new_file.len()].copy_from_slice(&new_file);
+    assert_eq!(disk.sides[0], expected);
+}
+
+#[test]
+fn swapping_sides() {
+    let mut fds = fds(2);
+    fds.write(0x4023, 0x01);
+    assert_eq!(fds.inserted_side(), Some(0));
+    fds.eject_disk();
+    assert_eq!(fds.read(0x4032, false) & 0x07, 0x07);
+    fds.insert_disk(1);
+    assert_eq!(fds.inserted_side(), Some(1));
+    fds.swap_side(0);
+    assert_eq!(fds.inserted_side(), None);
+    clock(&mut fds, 1_000_000);
+    assert_eq!(fds.inserted_side(), Some(0));
+}
+
+#[test]
+fn inserting_a_shorter_side_rewinds_the_head() {
+    // every block adds its gap and CRC, so a side with many files is longer
+    let mut long = disk_info();
+    long.extend([0x02, 100]);
+    for id in 0..100 {
+        long.extend(file_header(id, 1));
+        long.extend([0x04, id]);
+    }
+    long.resize(SIDE_SIZE, 0);
+    let mut short = disk_info();
+    short.extend([0x02, 0x00]);
+    short.resize(SIDE_SIZE, 0);
+    let disk = DiskImage::from_bytes(&[long, short].concat()).unwrap();
+    let mut fds = Fds::new(vec![0xEA; 8 * 1024], disk).unwrap();
+    fds.write(0x4023, 0x01);
+    fds.write(0x4025, 0x25);
+    // run the head past where the short side ends
+    clock(&mut fds, 50_000 + 80_000 * 150);
+    fds.insert_disk(1);
+    assert_eq!(read_block(&mut fds, 56), disk_info());
+    assert_eq!(read_block(&mut fds, 2), [0x02, 0x00]);
+}
+
+#[test]
+fn wavetable_audio() {
+    let mut fds = fds(1);
+    let level = |fds: &Fds| fds.sample_source().unwrap().sample();
+    fds.write(0x4023, 0x03);
+    fds.write(0x4089, 0x80);
+    for i in 0..64 {
+        fds.write(0x4040 + i, if i < 32 { 63 } else { 0 });
+    }
+    fds.write(0x4089, 0x00);
+    assert_eq!(fds.read(0x4040, false) & 0x3F, 63);
+    fds.write(0x4080, 0x80 | 32);
+    assert_eq!(fds.read(0x4090, false) & 0x3F, 32);
+    fds.write(0x4082, 0x00);
+    fds.write(0x4083, 0x04);
+    clock(&mut fds, 1);
+    let high = level(&fds);
+    assert!(high > 0.3);
+    // 1024 per cycle, 64 cycles per step, half the table later the output is low
+    clock(&mut fds, 64 * 32);
+    assert_eq!(level(&fds), 0.0);
+
+    fds.write(0x4089, 0x03);
+    clock(&mut fds, 64 * 32);
+    assert!((level(&fds) - high * 12.0 / 30.0).abs() < 1e-3);
+
+    fds.write(0x4083, 0x80);
+    assert_eq!(fds.audio().read(0x4092), Some(0));
+}
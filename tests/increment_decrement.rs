@@ -56,6 +56,22 @@ fn inc_abs_x_can_incr_memory() {
     assert_eq!(cpu.flag.bits(), Flag::NEGATIVE.bits());
 }
 
+#[test]
+fn inc_and_dec_keep_the_other_flags() {
+    for opcode in [Opcode::IncZPG, Opcode::DecZPG] {
+        let mut cpu = setup_cpu_bus();
+        cpu.write(0xFFFC, opcode.into());
+        cpu.write(0xFFFD, 0x42);
+        cpu.write(0x0042, 0x10);
+        cpu.flag = Flag::CARRY | Flag::INTERRUPT_DISABLE | Flag::OVERFLOW | Flag::ZERO;
+        cpu.execute();
+        assert_eq!(
+            cpu.flag.bits(),
+            (Flag::CARRY | Flag::INTERRUPT_DISABLE | Flag::OVERFLOW).bits()
+        );
+    }
+}
+
 #[test]
 fn inx_can_incr_x_register_zero() {
     let mut cpu = setup_cpu_bus();
@@ -10,11 +10,10 @@ fn load_simple_program() {
         0x00, 0x10, 0xa9, 0xff, 0x85, 0x90, 0x8d, 0x00, 0x80, 0x49, 0xcc, 0x4c, 0x02, 0x10,
     ];
     cpu.load_program(&program);
-    let mut addr = 0x1000 as Word;
+    let addr = 0x1000 as Word;
     cpu.pc = addr;
-    for &v in program.iter().skip(2) {
-        assert_eq!(cpu.read_byte(addr), v);
-        addr += 1;
+    for (offset, &v) in program.iter().skip(2).enumerate() {
+        assert_eq!(cpu.read_byte(addr + offset as Word), v);
     }
 
     for _ in 0..1000 {
@@ -0,0 +1,141 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cpu_6502::{
+    bus::{
+        Bus, Byte, Word,
+        memory_map::{MapError, MemoryMap},
+    },
+    cpu::{CPU, instructions::opcode::Opcode},
+};
+
+type Accesses = Rc<RefCell<Vec<(Word, Option<Byte>)>>>;
+
+/// Records every address it sees and answers with the low byte of it
+struct Probe {
+    seen: Accesses,
+}
+
+impl Bus for Probe {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        self.seen.borrow_mut().push((addr, None));
+        addr as Byte
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        self.seen.borrow_mut().push((addr, Some(value)));
+    }
+}
+
+#[test]
+fn ram_is_mirrored_across_its_range() {
+    let mut map = MemoryMap::builder()
+        .mirrored_ram(0x0000..=0x1FFF, 0x0800)
+        .build()
+        .unwrap();
+    map.write(0x0012, 0x2A);
+    assert_eq!(map.read(0x0812, false), 0x2A);
+    assert_eq!(map.read(0x1012, false), 0x2A);
+    assert_eq!(map.read(0x1812, false), 0x2A);
+    map.write(0x1FFF, 0x37);
+    assert_eq!(map.read(0x07FF, false), 0x37);
+}
+
+#[test]
+fn rom_is_write_protected_and_mirrored() {
+    let mut map = MemoryMap::builder()
+        .rom(0x8000..=0xFFFF, (0..=0xFF).cycle().take(0x4000).collect())
+        .build()
+        .unwrap();
+    map.write(0x8001, 0xFF);
+    assert_eq!(map.read(0x8001, false), 0x01);
+    assert_eq!(map.read(0xC001, false), 0x01);
+    assert_eq!(map.read(0xFFFF, false), 0xFF);
+}
+
+#[test]
+fn unmapped_addresses_read_zero() {
+    let mut map = MemoryMap::builder().ram(0x0000..=0x00FF).build().unwrap();
+    map.write(0x5000, 0x42);
+    assert_eq!(map.read(0x5000, false), 0x00);
+    assert!(map.is_mapped(0x00FF));
+    assert!(!map.is_mapped(0x0100));
+}
+
+#[test]
+fn devices_see_folded_cpu_addresses() {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut map = MemoryMap::builder()
+        .mirrored_device(0x2000..=0x3FFF, 8, Box::new(Probe { seen: seen.clone() }))
+        .build()
+        .unwrap();
+    assert_eq!(map.read(0x3FFA, false), 0x02);
+    map.write(0x2009, 0x55);
+    assert_eq!(*seen.borrow(), vec![(0x2002, None), (0x2001, Some(0x55))]);
+}
+
+#[test]
+fn regions_can_share_a_page() {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut map = MemoryMap::builder()
+        .device(0x4000..=0x401F, Box::new(Probe { seen: seen.clone() }))
+        .ram(0x4020..=0x40FF)
+        .build()
+        .unwrap();
+    map.write(0x4020, 0x11);
+    map.write(0x401F, 0x22);
+    assert_eq!(map.read(0x4020, false), 0x11);
+    assert_eq!(*seen.borrow(), vec![(0x401F, Some(0x22))]);
+    assert!(!map.is_mapped(0x4100));
+}
+
+#[test]
+fn overlapping_regions_are_rejected() {
+    let result = MemoryMap::builder()
+        .ram(0x0000..=0x07FF)
+        .rom(0x0700..=0x0FFF, vec![0; 0x900])
+        .build();
+    assert_eq!(
+        result.err(),
+        Some(MapError::Overlap {
+            first: 0x0000..=0x07FF,
+            second: 0x0700..=0x0FFF,
+        })
+    );
+}
+
+#[test]
+fn invalid_mirrors_are_rejected() {
+    let empty = MemoryMap::builder().rom(0x8000..=0xFFFF, vec![]).build();
+    assert_eq!(empty.err(), Some(MapError::EmptyRegion(0x8000..=0xFFFF)));
+
+    let too_large = MemoryMap::builder()
+        .mirrored_ram(0x0000..=0x00FF, 0x200)
+        .build();
+    assert_eq!(
+        too_large.err(),
+        Some(MapError::MirrorTooLarge {
+            range: 0x0000..=0x00FF,
+            period: 0x200,
+        })
+    );
+}
+
+#[test]
+fn cpu_can_run_from_a_memory_map() {
+    let mut rom = vec![0xEA; 0x8000];
+    rom[0x7FFC] = Opcode::LdaIMM.into();
+    rom[0x7FFD] = 0x2A;
+    rom[0x7FFE] = Opcode::StaZPG.into();
+    rom[0x7FFF] = 0x10;
+    let map = MemoryMap::builder()
+        .mirrored_ram(0x0000..=0x1FFF, 0x0800)
+        .rom(0x8000..=0xFFFF, rom)
+        .build()
+        .unwrap();
+    let mut cpu = CPU::new();
+    cpu.connect_bus(Box::new(map));
+    cpu.reset();
+    cpu.execute();
+    cpu.execute();
+    assert_eq!(cpu.read_byte(0x0810), 0x2A);
+}
@@ -0,0 +1,216 @@
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{Byte, Word, clock::Lines},
+    cartridge::{Mirroring, mapper::SharedMapper},
+    cpu::{CPU, Profile, instructions::opcode::Opcode},
+    nes::bus::NesBus,
+};
+
+/// MMC1 image: PRG 16 KiB banks hold their bank number, CHR 4 KiB banks `0x80 | bank`
+fn build_mmc1(prg_banks: Byte, chr_banks: Byte, prg_ram_shift: Option<Byte>) -> Ines {
+    let mut ines = Ines::new(1, prg_banks, chr_banks / 2);
+    if let Some(shift) = prg_ram_shift {
+        ines = ines.submapper(0).set(10, shift);
+    }
+    ines.prg_banks(16 * 1024, |bank, _| bank as Byte)
+        .chr_banks(4 * 1024, |bank, _| 0x80 | bank as Byte)
+}
+
+/// Serially loads `value` into the register at `addr`, one CPU cycle apart
+fn load_register(board: &SharedMapper, addr: Word, value: Byte) {
+    let mut board = board.borrow_mut();
+    for bit in 0..5 {
+        board.write(addr, (value >> bit) & 0x01);
+        board.clock(&mut Lines::default());
+    }
+}
+
+#[test]
+fn powers_up_with_last_bank_fixed() {
+    let board = load_board(&build_mmc1(8, 2, None).build());
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x8000, false), 0);
+    assert_eq!(board.read(0xC000, false), 7);
+}
+
+#[test]
+fn switches_16k_bank_at_8000() {
+    let board = load_board(&build_mmc1(8, 2, None).build());
+    load_register(&board, 0xE000, 5);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x8000, false), 5);
+    assert_eq!(board.read(0xFFFF, false), 7);
+}
+
+#[test]
+fn fixes_first_bank_in_mode_2() {
+    let board = load_board(&build_mmc1(8, 2, None).build());
+    load_register(&board, 0x8000, 0b01000);
+    load_register(&board, 0xE000, 3);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x8000, false), 0);
+    assert_eq!(board.read(0xC000, false), 3);
+}
+
+#[test]
+fn switches_32k_ignoring_low_bit() {
+    let board = load_board(&build_mmc1(8, 2, None).build());
+    load_register(&board, 0x8000, 0b00000);
+    load_register(&board, 0xE000, 5);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x8000, false), 4);
+    assert_eq!(board.read(0xC000, false), 5);
+}
+
+#[test]
+fn bit_7_resets_the_shift_register() {
+    let board = load_board(&build_mmc1(8, 2, None).build());
+    load_register(&board, 0x8000, 0b01000);
+    {
+        let mut board = board.borrow_mut();
+        board.write(0xE000, 0x01);
+        board.clock(&mut Lines::default());
+        board.write(0xE000, 0x80);
+        board.clock(&mut Lines::default());
+    }
+    load_register(&board, 0xE000, 2);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x8000, false), 2);
+    assert_eq!(board.read(0xC000, false), 7);
+}
+
+#[test]
+fn ignores_writes_on_consecutive_cycles() {
+    let board = load_board(&build_mmc1(8, 2, None).build());
+    {
+        let mut board = board.borrow_mut();
+        board.clock(&mut Lines::default());
+        for bit in [1, 0, 1, 0, 0] {
+            board.write(0xE000, bit);
+            board.write(0xE000, 1);
+            board.clock(&mut Lines::default());
+        }
+    }
+    assert_eq!(board.borrow_mut().read(0x8000, false), 5);
+}
+
+#[test]
+fn takes_every_write_until_clocked() {
+    let board = load_board(&build_mmc1(8, 2, None).build());
+    let mut board = board.borrow_mut();
+    for bit in [1, 0, 1, 0, 0] {
+        board.write(0xE000, bit);
+    }
+    assert_eq!(board.read(0x8000, false), 5);
+}
+
+#[test]
+fn chr_banking_modes() {
+    let board = load_board(&build_mmc1(2, 8, None).build());
+    load_register(&board, 0xA000, 3);
+    assert_eq!(board.borrow_mut().ppu_read(0x0000), 0x82);
+    assert_eq!(board.borrow_mut().ppu_read(0x1000), 0x83);
+
+    load_register(&board, 0x8000, 0b11100);
+    load_register(&board, 0xC000, 6);
+    assert_eq!(board.borrow_mut().ppu_read(0x0000), 0x83);
+    assert_eq!(board.borrow_mut().ppu_read(0x1FFF), 0x86);
+}
+
+#[test]
+fn switchable_mirroring() {
+    let board = load_board(&build_mmc1(2, 2, None).build());
+    for (control, mirroring) in [
+        (0, Mirroring::SingleScreenLower),
+        (1, Mirroring::SingleScreenUpper),
+        (2, Mirroring::Vertical),
+        (3, Mirroring::Horizontal),
+    ] {
+        load_register(&board, 0x8000, 0x0C | control);
+        assert_eq!(board.borrow().mirroring(), mirroring);
+    }
+}
+
+#[test]
+fn prg_ram_can_be_disabled() {
+    let board = load_board(&build_mmc1(2, 2, None).build());
+    board.borrow_mut().write(0x6000, 0x42);
+    assert_eq!(board.borrow_mut().read(0x6000, false), 0x42);
+    load_register(&board, 0xE000, 0x10);
+    assert_eq!(board.borrow().driven_bits(0x6000), 0x00);
+    board.borrow_mut().write(0x6000, 0x24);
+    load_register(&board, 0xE000, 0x00);
+    assert_eq!(board.borrow_mut().read(0x6000, false), 0x42);
+}
+
+#[test]
+fn surom_selects_the_256k_half() {
+    let board = load_board(&build_mmc1(32, 0, None).build());
+    assert_eq!(board.borrow_mut().read(0xC000, false), 15);
+    load_register(&board, 0xA000, 0x10);
+    load_register(&board, 0xE000, 2);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x8000, false), 18);
+    assert_eq!(board.read(0xC000, false), 31);
+}
+
+#[test]
+fn sxrom_banks_32k_of_prg_ram() {
+    let board = load_board(&build_mmc1(32, 0, Some(9)).build());
+    for bank in 0..4 {
+        load_register(&board, 0xA000, bank << 2);
+        board.borrow_mut().write(0x6000, 0x10 + bank);
+    }
+    for bank in 0..4 {
+        load_register(&board, 0xA000, bank << 2);
+        assert_eq!(board.borrow_mut().read(0x6000, false), 0x10 + bank);
+    }
+}
+
+#[test]
+fn read_modify_write_reset_trick() {
+    let mut rom = build_mmc1(4, 2, None).build();
+    rom[16] = 0xFF;
+    let board = load_board(&rom);
+    let mut bus = NesBus::new();
+    bus.connect_cartridge(Box::new(board.clone()));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.attach_device(Box::new(board.clone()));
+    cpu.reset();
+    cpu.pc = 0x0200;
+    // the board has to see a cycle go by before it filters writes
+    let program: [Byte; _] = [
+        Opcode::NopIMP.into(),
+        Opcode::IncABS.into(),
+        0x00,
+        0x80,
+        Opcode::LdaIMM.into(),
+        0x01,
+        Opcode::StaABS.into(),
+        0x00,
+        0xE0,
+        Opcode::LsrIMP.into(),
+        Opcode::StaABS.into(),
+        0x00,
+        0xE0,
+        Opcode::StaABS.into(),
+        0x00,
+        0xE0,
+        Opcode::StaABS.into(),
+        0x00,
+        0xE0,
+        Opcode::StaABS.into(),
+        0x00,
+        0xE0,
+    ];
+    for (offset, &byte) in program.iter().enumerate() {
+        cpu.write(0x0200 + offset as Word, byte);
+    }
+    for _ in 0..9 {
+        cpu.execute();
+    }
+    assert_eq!(cpu.read_byte(0x8000), 1);
+}
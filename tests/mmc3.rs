@@ -0,0 +1,213 @@
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{Bus, Byte, Word, clock::Lines},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{
+            Mapper,
+            mmc3::{IrqBehavior, Mmc3},
+        },
+    },
+    ppu::{PRE_RENDER_SCANLINE, Ppu},
+};
+
+/// MMC3 image: PRG 8 KiB banks hold their bank number, CHR 1 KiB banks `0x80 | bank`
+fn build_mmc3(prg_8k: Byte, chr_1k: Byte, flags6: Byte) -> Ines {
+    Ines::new(4, prg_8k / 2, chr_1k / 8)
+        .flags6(flags6)
+        .prg_banks(8 * 1024, |bank, _| bank as Byte)
+        .chr_banks(1024, |bank, _| 0x80 | bank as Byte)
+}
+
+fn set_bank(board: &mut dyn Mapper, select: Byte, value: Byte) {
+    board.write(0x8000, select);
+    board.write(0x8001, value);
+}
+
+/// One scanline worth of A12 activity: background fetches low, sprite fetches high
+fn scanline(board: &mut dyn Mapper) -> bool {
+    let mut lines = Lines::default();
+    board.ppu_read(0x0000);
+    for _ in 0..80 {
+        board.clock(&mut lines);
+    }
+    board.ppu_read(0x1000);
+    for _ in 0..33 {
+        board.clock(&mut lines);
+    }
+    lines.irq
+}
+
+#[test]
+fn prg_banking_modes() {
+    let board = load_board(&build_mmc3(16, 64, 0).build());
+    let mut board = board.borrow_mut();
+    set_bank(&mut *board, 6, 3);
+    set_bank(&mut *board, 7, 5);
+    let banks = |board: &mut dyn Mapper| {
+        [0x8000, 0xA000, 0xC000, 0xE000].map(|addr: Word| board.read(addr, false))
+    };
+    assert_eq!(banks(&mut *board), [3, 5, 14, 15]);
+    board.write(0x8000, 0x46);
+    assert_eq!(banks(&mut *board), [14, 5, 3, 15]);
+}
+
+#[test]
+fn chr_banking_and_inversion() {
+    let board = load_board(&build_mmc3(4, 64, 0).build());
+    let mut board = board.borrow_mut();
+    set_bank(&mut *board, 0, 9);
+    set_bank(&mut *board, 1, 20);
+    set_bank(&mut *board, 5, 33);
+    let banks = |board: &mut dyn Mapper| {
+        [0x0000, 0x0400, 0x0800, 0x0C00, 0x1C00].map(|addr: Word| board.ppu_read(addr))
+    };
+    assert_eq!(banks(&mut *board), [0x88, 0x89, 0x94, 0x95, 0xA1]);
+    board.write(0x8000, 0x80);
+    assert_eq!(board.ppu_read(0x1000), 0x88);
+    assert_eq!(board.ppu_read(0x1C00), 0x95);
+    assert_eq!(board.ppu_read(0x0C00), 0xA1);
+}
+
+#[test]
+fn mirroring_control() {
+    let board = load_board(&build_mmc3(4, 8, 0).build());
+    let mut board = board.borrow_mut();
+    board.write(0xA000, 1);
+    assert_eq!(board.mirroring(), Mirroring::Horizontal);
+    board.write(0xA000, 0);
+    assert_eq!(board.mirroring(), Mirroring::Vertical);
+
+    let board = load_board(&build_mmc3(4, 8, 0x08).build());
+    board.borrow_mut().write(0xA000, 1);
+    assert_eq!(board.borrow().mirroring(), Mirroring::FourScreen);
+}
+
+#[test]
+fn prg_ram_protect() {
+    let board = load_board(&build_mmc3(4, 8, 0).build());
+    let mut board = board.borrow_mut();
+    board.write(0x6000, 0x11);
+    assert_eq!(board.read(0x6000, false), 0x11);
+    board.write(0xA001, 0xC0);
+    board.write(0x6000, 0x22);
+    assert_eq!(board.read(0x6000, false), 0x11);
+    board.write(0xA001, 0x00);
+    assert_eq!(board.driven_bits(0x6000), 0x00);
+}
+
+#[test]
+fn irq_fires_after_latch_scanlines() {
+    let board = load_board(&build_mmc3(4, 8, 0).build());
+    let mut board = board.borrow_mut();
+    board.write(0xC000, 3);
+    board.write(0xC001, 0);
+    board.write(0xE001, 0);
+    let fired: Vec<bool> = (0..5).map(|_| scanline(&mut *board)).collect();
+    assert_eq!(fired, [false, false, false, true, true]);
+
+    board.write(0xE000, 0);
+    assert!(!scanline(&mut *board));
+}
+
+#[test]
+fn a12_filter_ignores_short_dips() {
+    let board = load_board(&build_mmc3(4, 8, 0).build());
+    let mut board = board.borrow_mut();
+    board.write(0xC000, 1);
+    board.write(0xC001, 0);
+    board.write(0xE001, 0);
+    // reload to 1
+    scanline(&mut *board);
+    for _ in 0..8 {
+        board.ppu_address(0x2000);
+        board.ppu_read(0x1000);
+    }
+    board.clock(&mut Lines::default());
+    let mut lines = Lines::default();
+    board.clock(&mut lines);
+    assert!(!lines.irq);
+    assert!(scanline(&mut *board));
+}
+
+/// Whether A12 going high after `cycles` cycles low clocks the counter
+fn clocks_after_low(cycles: usize) -> bool {
+    let board = load_board(&build_mmc3(4, 8, 0).build());
+    let mut board = board.borrow_mut();
+    board.write(0xC000, 0);
+    board.write(0xC001, 0);
+    board.write(0xE001, 0);
+    board.ppu_read(0x0000);
+    for _ in 0..cycles {
+        board.clock(&mut Lines::default());
+    }
+    board.ppu_read(0x1000);
+    let mut lines = Lines::default();
+    board.clock(&mut lines);
+    lines.irq
+}
+
+#[test]
+fn a12_filter_spans_the_gap_between_scanlines() {
+    // With the background at $1000 A12 is low for 9 dots, 3 cycles, between
+    // the prefetches and the first tile of the next line
+    assert!(!clocks_after_low(3));
+    assert!(clocks_after_low(4));
+}
+
+#[test]
+fn tile_fetches_clock_once_per_scanline() {
+    let board = load_board(&build_mmc3(4, 8, 0).build());
+    let mut ppu = Ppu::new();
+    ppu.connect_cartridge(board.clone());
+    {
+        let mut board = board.borrow_mut();
+        board.write(0xC000, 9);
+        board.write(0xC001, 0);
+        board.write(0xE001, 0);
+    }
+    // Background from $1000, the sprite slots keep A12 low
+    ppu.write(0x2000, 0x10);
+    ppu.write(0x2001, 0x08);
+    while ppu.scanline() != PRE_RENDER_SCANLINE {
+        ppu.clock_dot();
+    }
+    let mut fired_on = None;
+    while fired_on.is_none() {
+        for _ in 0..3 {
+            ppu.clock_dot();
+        }
+        let mut lines = Lines::default();
+        board.borrow_mut().clock(&mut lines);
+        if lines.irq {
+            fired_on = Some((ppu.scanline(), ppu.dot()));
+        }
+    }
+    // The pre-render line reloads the counter and lines 0-8 count it down,
+    // each on the first background pattern fetch after the sprite slots
+    let (scanline, dot) = fired_on.unwrap();
+    assert_eq!(scanline, 8);
+    assert!((325..330).contains(&dot));
+}
+
+#[test]
+fn zero_latch_depends_on_revision() {
+    let rom = build_mmc3(4, 8, 0).build();
+    for (behavior, expected) in [
+        (IrqBehavior::Sharp, [true, true, true]),
+        (IrqBehavior::Nec, [true, false, false]),
+    ] {
+        let mut board = Mmc3::new(Cartridge::from_bytes(&rom).unwrap()).with_irq_behavior(behavior);
+        board.write(0xC000, 0);
+        board.write(0xC001, 0);
+        board.write(0xE001, 0);
+        let fired = [(); 3].map(|_| {
+            board.write(0xE000, 0);
+            board.write(0xE001, 0);
+            scanline(&mut board)
+        });
+        assert_eq!(fired, expected, "{behavior:?}");
+    }
+}
@@ -0,0 +1,455 @@
+//! Most of this suite drives the board with synthetic images and the fetch
+//! pattern of a rendering PPU, one test per feature the mmc5test ROMs check.
+//! The ROMs themselves report on screen only, so their runner compares the
+//! PPU framebuffer with a dump of each ROM's passing screen.
+
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{Bus, Byte, Word, clock::Lines},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{self, Mapper, SharedMapper},
+    },
+    cpu::{CPU, Profile},
+    nes::bus::NesBus,
+    ppu::Ppu,
+};
+
+/// MMC5 image with 64 KiB PRG-RAM: PRG 8 KiB banks and CHR 1 KiB banks hold their bank number
+fn load() -> SharedMapper {
+    load_board(
+        &Ines::new(5, 8, 16)
+            .set(8, 8)
+            .prg_banks(8 * 1024, |bank, _| bank as Byte)
+            .chr_banks(1024, |bank, _| bank as Byte)
+            .build(),
+    )
+}
+
+/// What the background fetches of one tile returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tile {
+    name: Option<Byte>,
+    attribute: Option<Byte>,
+    pattern: Byte,
+}
+
+/// Drives the board with the fetch pattern of a rendering PPU,
+/// background patterns at `$0000` and sprite patterns at `$1000`.
+struct Renderer {
+    irq_lines: Vec<usize>,
+    irq: bool,
+    reads: usize,
+}
+
+impl Renderer {
+    fn new() -> Self {
+        Self {
+            irq_lines: Vec::new(),
+            irq: false,
+            reads: 0,
+        }
+    }
+
+    fn name_addr(column: usize, y: usize) -> Word {
+        (0x2000 | ((column / 32) << 10) | ((y / 8) * 32) | (column % 32)) as Word
+    }
+
+    fn read_name(&mut self, board: &mut dyn Mapper, addr: Word, y: usize) -> Option<Byte> {
+        self.tick(board, y);
+        board.nametable_read(addr)
+    }
+
+    fn read_pattern(&mut self, board: &mut dyn Mapper, addr: Word, y: usize) -> Byte {
+        self.tick(board, y);
+        board.ppu_read(addr)
+    }
+
+    /// A CPU cycle every other read, about the real 1.5
+    fn tick(&mut self, board: &mut dyn Mapper, y: usize) {
+        self.reads += 1;
+        if self.reads.is_multiple_of(2) {
+            let mut lines = Lines::default();
+            board.clock(&mut lines);
+            if lines.irq && !self.irq {
+                self.irq_lines.push(y);
+            }
+            self.irq = lines.irq;
+        }
+    }
+
+    fn fetch_tile(&mut self, board: &mut dyn Mapper, column: usize, y: usize) -> Tile {
+        let name = self.read_name(board, Self::name_addr(column, y), y);
+        let attribute_addr = 0x23C0 | ((column / 32) << 10) | ((y / 32) * 8) | ((column % 32) / 4);
+        let attribute = self.read_name(board, attribute_addr as Word, y);
+        let pattern = self.read_pattern(board, (y % 8) as Word, y);
+        self.read_pattern(board, (y % 8 + 8) as Word, y);
+        Tile {
+            name,
+            attribute,
+            pattern,
+        }
+    }
+
+    /// Renders scanline `y` (`None` for the pre-render line),
+    /// returning tiles 2-33 and the sprite pattern bytes.
+    fn scanline(&mut self, board: &mut dyn Mapper, y: Option<usize>) -> (Vec<Tile>, Vec<Byte>) {
+        let line = y.unwrap_or(261);
+        let fetch_y = y.unwrap_or(0);
+        let tiles = (2..34)
+            .map(|c| self.fetch_tile(board, c, fetch_y))
+            .collect();
+        let mut sprites = Vec::new();
+        for _ in 0..8 {
+            let garbage = Self::name_addr(0, fetch_y);
+            self.read_name(board, garbage, line);
+            self.read_name(board, garbage, line);
+            sprites.push(self.read_pattern(board, 0x1000, line));
+            self.read_pattern(board, 0x1008, line);
+        }
+        let next_y = y.map_or(0, |y| y + 1);
+        self.fetch_tile(board, 0, next_y);
+        self.fetch_tile(board, 1, next_y);
+        let dummy = Self::name_addr(2, next_y);
+        self.read_name(board, dummy, line);
+        self.read_name(board, dummy, line);
+        (tiles, sprites)
+    }
+
+    /// Renders a whole frame and idles through vertical blank, returning the visible lines.
+    fn frame(&mut self, board: &mut dyn Mapper) -> Vec<(Vec<Tile>, Vec<Byte>)> {
+        self.scanline(board, None);
+        let lines = (0..240).map(|y| self.scanline(board, Some(y))).collect();
+        for _ in 0..20 * 114 {
+            board.clock(&mut Lines::default());
+        }
+        lines
+    }
+}
+
+#[test]
+fn prg_modes() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    let banks = |board: &mut dyn Mapper| {
+        [0x8000, 0xA000, 0xC000, 0xE000].map(|addr: Word| board.read(addr, false))
+    };
+    // PRG mode 3 with $5114-$5116 cleared maps RAM everywhere but $E000
+    assert_eq!(banks(&mut *board), [0, 0, 0, 15]);
+
+    for (addr, value) in [
+        (0x5114, 0x81),
+        (0x5115, 0x82),
+        (0x5116, 0x83),
+        (0x5117, 0x84),
+    ] {
+        board.write(addr, value);
+    }
+    assert_eq!(banks(&mut *board), [1, 2, 3, 4]);
+    board.write(0x5100, 2);
+    assert_eq!(banks(&mut *board), [2, 3, 3, 4]);
+    board.write(0x5100, 1);
+    assert_eq!(banks(&mut *board), [2, 3, 4, 5]);
+    board.write(0x5100, 0);
+    assert_eq!(banks(&mut *board), [4, 5, 6, 7]);
+}
+
+#[test]
+fn prg_ram_banking_and_protect() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5113, 2);
+    board.write(0x6000, 0x11);
+    assert_eq!(board.read(0x6000, false), 0x00);
+    board.write(0x5102, 0x02);
+    board.write(0x5103, 0x01);
+    board.write(0x6000, 0x11);
+    assert_eq!(board.read(0x6000, false), 0x11);
+
+    // the same RAM bank seen through $A000 in 8 KiB mode
+    board.write(0x5115, 0x02);
+    assert_eq!(board.read(0xA000, false), 0x11);
+    board.write(0xA001, 0x22);
+    board.write(0x5113, 0x0A);
+    assert_eq!(board.read(0x6001, false), 0x22);
+    board.write(0x5115, 0x82);
+    assert_eq!(board.read(0xA001, false), 2);
+}
+
+#[test]
+fn multiplier() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5205, 200);
+    board.write(0x5206, 123);
+    let product = board.read(0x5205, false) as u16 | (board.read(0x5206, false) as u16) << 8;
+    assert_eq!(product, 24600);
+}
+
+#[test]
+fn chr_modes_and_upper_bits() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5101, 3);
+    for i in 0..8 {
+        board.write(0x5120 + i, 10 + i as Byte);
+    }
+    assert_eq!(board.ppu_read(0x1C00), 17);
+    board.write(0x5101, 2);
+    assert_eq!(board.ppu_read(0x0000), 22);
+    assert_eq!(board.ppu_read(0x0400), 23);
+    board.write(0x5101, 1);
+    assert_eq!(board.ppu_read(0x1C00), 17 * 4 + 3);
+    board.write(0x5101, 0);
+    // 8 KiB bank 17 wraps around 128 KiB of CHR
+    assert_eq!(board.ppu_read(0x1C00), 15);
+
+    board.write(0x5130, 0x01);
+    board.write(0x5101, 3);
+    board.write(0x5120, 0x05);
+    // bank $105 wraps around 128 KiB of CHR
+    assert_eq!(board.ppu_read(0x0000), 5);
+}
+
+#[test]
+fn last_written_set_outside_8x16_mode() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5101, 3);
+    board.write(0x5120, 1);
+    board.write(0x5128, 9);
+    assert_eq!(board.ppu_read(0x0000), 9);
+    assert_eq!(board.ppu_read(0x1000), 9);
+    board.write(0x5120, 1);
+    assert_eq!(board.ppu_read(0x0000), 1);
+}
+
+#[test]
+fn sprites_and_background_use_separate_sets_in_8x16_mode() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x2000, 0x20);
+    board.write(0x5127, 3);
+    board.write(0x512B, 5);
+    let lines = Renderer::new().frame(&mut *board);
+    let (tiles, sprites) = &lines[100];
+    assert!(tiles.iter().all(|tile| tile.pattern == 5 * 8));
+    assert!(sprites.iter().all(|&pattern| pattern == 3 * 8 + 4));
+}
+
+#[test]
+fn scanline_irq() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5203, 100);
+    board.write(0x5204, 0x80);
+    let mut renderer = Renderer::new();
+    renderer.frame(&mut *board);
+    assert_eq!(renderer.irq_lines, [100]);
+    assert_eq!(board.read(0x5204, false) & 0xC0, 0x80);
+    assert_eq!(board.read(0x5204, false), 0x00);
+    let mut lines = Lines::default();
+    board.clock(&mut lines);
+    assert!(!lines.irq);
+}
+
+#[test]
+fn new_frame_clears_an_unacknowledged_irq() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5203, 100);
+    board.write(0x5204, 0x80);
+    let mut renderer = Renderer::new();
+    renderer.frame(&mut *board);
+    assert_eq!(board.peek(0x5204) & 0x80, 0x80);
+    renderer.scanline(&mut *board, None);
+    renderer.scanline(&mut *board, Some(0));
+    assert_eq!(board.peek(0x5204), 0x40);
+    // So the next frame's IRQ is a new edge
+    for y in 1..240 {
+        renderer.scanline(&mut *board, Some(y));
+    }
+    assert_eq!(renderer.irq_lines, [100, 100]);
+}
+
+#[test]
+fn in_frame_flag() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    let mut renderer = Renderer::new();
+    renderer.scanline(&mut *board, None);
+    renderer.scanline(&mut *board, Some(0));
+    assert_eq!(board.peek(0x5204), 0x40);
+    board.write(0x2001, 0x00);
+    assert_eq!(board.peek(0x5204), 0x00);
+}
+
+#[test]
+fn nametable_sources() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5104, 0x02);
+    board.write(0x5C05, 0x77);
+    assert_eq!(board.read(0x5C05, false), 0x77);
+    board.write(0x5104, 0x00);
+    assert_eq!(board.driven_bits(0x5C05), 0x00);
+    // outside rendering, nametable mode ExRAM writes store 0
+    board.write(0x5C06, 0x66);
+
+    board.write(0x5105, 0b11_10_01_00);
+    board.write(0x5106, 0x42);
+    board.write(0x5107, 0x02);
+    assert_eq!(board.nametable_read(0x2005), None);
+    assert_eq!(board.ciram_page(0), 0);
+    assert_eq!(board.ciram_page(1), 1);
+    assert_eq!(board.nametable_read(0x2805), Some(0x77));
+    assert_eq!(board.nametable_read(0x2806), Some(0x00));
+    assert_eq!(board.nametable_read(0x2C05), Some(0x42));
+    assert_eq!(board.nametable_read(0x2FC5), Some(0xAA));
+
+    assert!(board.nametable_write(0x2807, 0x12));
+    assert!(!board.nametable_write(0x2407, 0x12));
+    board.write(0x5104, 0x02);
+    assert_eq!(board.read(0x5C07, false), 0x12);
+
+    board.write(0x5105, 0x50);
+    assert_eq!(board.mirroring(), Mirroring::Horizontal);
+}
+
+#[test]
+fn ppu_peeks_see_the_board_nametables() {
+    let board = load();
+    board.borrow_mut().write(0x5104, 0x02);
+    board.borrow_mut().write(0x5C05, 0x77);
+    board.borrow_mut().write(0x5104, 0x00);
+    board.borrow_mut().write(0x5105, 0b11_10_01_00);
+    board.borrow_mut().write(0x5106, 0x42);
+    board.borrow_mut().write(0x5107, 0x02);
+    let mut ppu = Ppu::new();
+    ppu.connect_cartridge(board.clone());
+    ppu.write_memory(0x2005, 0x33);
+    for (addr, value) in [
+        (0x2005, 0x33),
+        (0x2805, 0x77),
+        (0x2C05, 0x42),
+        (0x2FC5, 0xAA),
+    ] {
+        assert_eq!(ppu.peek_memory(addr), value, "{addr:04X}");
+    }
+    // the same address fetched 3 times in a row would start a frame
+    for _ in 0..3 {
+        ppu.peek_memory(0x2805);
+    }
+    assert_eq!(board.borrow_mut().peek(0x5204), 0x00);
+    for addr in [0x2005, 0x2805, 0x2C05, 0x2FC5] {
+        assert_eq!(ppu.read_memory(addr), ppu.peek_memory(addr), "{addr:04X}");
+    }
+}
+
+#[test]
+fn extended_attributes() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5104, 0x02);
+    // tile (5, 10) uses 4 KiB bank 3 and palette 2
+    board.write(0x5C00 + 10 * 32 + 5, 0x83);
+    board.write(0x5104, 0x01);
+    let lines = Renderer::new().frame(&mut *board);
+    let tile = lines[80].0[3];
+    assert_eq!(tile.attribute, Some(0xAA));
+    assert_eq!(tile.pattern, 3 * 4);
+    let plain = lines[80].0[4];
+    assert_eq!(plain.attribute, Some(0x00));
+    assert_eq!(plain.pattern, 0);
+}
+
+#[test]
+fn vertical_split() {
+    let board = load();
+    let mut board = board.borrow_mut();
+    board.write(0x5104, 0x02);
+    for row in 0..30 {
+        board.write(0x5C00 + row * 32 + 3, 0xA0 + row as Byte);
+    }
+    board.write(0x5C00 + 0x3C0, 0xFF);
+    board.write(0x5104, 0x00);
+    board.write(0x5200, 0x80 | 4);
+    board.write(0x5201, 16);
+    board.write(0x5202, 2);
+    let lines = Renderer::new().frame(&mut *board);
+    let (tiles, _) = &lines[0];
+    // columns 2 and 3 are in the split, read at row (0 + 16) / 8
+    assert_eq!(tiles[1].name, Some(0xA2));
+    assert_eq!(tiles[0].pattern, 2 * 4);
+    assert_eq!(tiles[2].name, None);
+    assert_eq!(lines[8].0[1].name, Some(0xA3));
+    assert_eq!(lines[0].0[0].attribute, Some(0xFF));
+}
+
+#[test]
+fn sees_ppu_register_writes_through_the_nes_bus() {
+    let board = load();
+    board.borrow_mut().write(0x5101, 3);
+    board.borrow_mut().write(0x5124, 1);
+    board.borrow_mut().write(0x5128, 2);
+    let mut bus = NesBus::new();
+    bus.connect_cartridge(Box::new(board.clone()));
+    bus.write(0x2000, 0x20);
+    let lines = Renderer::new().frame(&mut *board.borrow_mut());
+    assert!(lines[10].1.iter().all(|&pattern| pattern == 1));
+    assert!(lines[10].0.iter().all(|tile| tile.pattern == 2));
+}
+
+/// Boots a ROM and returns its screen after `frames` frames, as palette indices
+fn run_rom(path: &Path, frames: u64) -> Vec<Byte> {
+    let board = mapper::from_cartridge(Cartridge::load(path).unwrap()).unwrap();
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    ppu.borrow_mut().connect_cartridge(board.clone());
+    let mut bus = NesBus::new();
+    bus.connect_ppu(Box::new(ppu.clone()));
+    bus.connect_cartridge(Box::new(board.clone()));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.attach_device(Box::new(ppu.clone()));
+    cpu.attach_device(Box::new(board));
+    cpu.reset();
+    cpu.pc = Word::from_le_bytes([cpu.peek(0xFFFC), cpu.peek(0xFFFD)]);
+    while ppu.borrow().frame_count() < frames {
+        cpu.execute();
+    }
+    ppu.borrow().frame().to_vec()
+}
+
+/// Each `<name>.nes` in `tests/roms/mmc5test` is checked against `<name>.screen`,
+/// the 256x240 palette indices of its passing screen. Any other screen is written
+/// to `<name>.actual` so it can be looked at, or kept as the new dump.
+#[test]
+#[ignore = "needs the mmc5test ROMs and their passing .screen dumps in tests/roms/mmc5test"]
+fn mmc5test_roms() {
+    const DIR: &str = "tests/roms/mmc5test";
+    common::require_test_files(&[DIR]);
+    let mut roms: Vec<_> = fs::read_dir(DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "nes"))
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty(), "no ROMs in {DIR}");
+    let failed: Vec<_> = roms
+        .iter()
+        .filter(|rom| {
+            let screen = run_rom(rom, 300);
+            if fs::read(rom.with_extension("screen")).is_ok_and(|expected| expected == screen) {
+                return false;
+            }
+            fs::write(rom.with_extension("actual"), &screen).unwrap();
+            true
+        })
+        .map(|rom| rom.file_name().unwrap().to_owned())
+        .collect();
+    assert!(failed.is_empty(), "failed: {failed:?}");
+}
@@ -0,0 +1,145 @@
+use std::{cell::RefCell, rc::Rc};
+
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{Bus, Byte, Word},
+    cpu::{CPU, Profile, instructions::opcode::Opcode},
+    nes::bus::NesBus,
+};
+
+type Accesses = Rc<RefCell<Vec<(Word, Option<Byte>)>>>;
+
+/// Records the addresses it is handed and answers with their low byte
+struct Probe {
+    seen: Accesses,
+}
+
+impl Bus for Probe {
+    fn read(&mut self, addr: Word, _: bool) -> Byte {
+        self.seen.borrow_mut().push((addr, None));
+        addr as Byte
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        self.seen.borrow_mut().push((addr, Some(value)));
+    }
+}
+
+fn probe() -> (Box<dyn Bus>, Accesses) {
+    let seen = Accesses::default();
+    (Box::new(Probe { seen: seen.clone() }), seen)
+}
+
+#[test]
+fn internal_ram_is_mirrored() {
+    let mut bus = NesBus::new();
+    bus.write(0x0001, 0x2A);
+    assert_eq!(bus.read(0x0801, false), 0x2A);
+    assert_eq!(bus.read(0x1001, false), 0x2A);
+    bus.write(0x1FFF, 0x37);
+    assert_eq!(bus.ram[0x07FF], 0x37);
+}
+
+#[test]
+fn ppu_registers_are_mirrored_every_8_bytes() {
+    let mut bus = NesBus::new();
+    let (ppu, seen) = probe();
+    bus.connect_ppu(ppu);
+    assert_eq!(bus.read(0x3FFA, false), 0x02);
+    bus.write(0x2008, 0x80);
+    assert_eq!(*seen.borrow(), vec![(0x2002, None), (0x2000, Some(0x80))]);
+}
+
+#[test]
+fn io_test_and_cartridge_ranges() {
+    let mut bus = NesBus::new();
+    let (apu, apu_seen) = probe();
+    let (test, test_seen) = probe();
+    let (cart, cart_seen) = probe();
+    bus.connect_apu(apu);
+    bus.connect_test_registers(test);
+    bus.connect_cartridge(cart);
+    bus.write(0x4000, 0x01);
+    bus.write(0x4017, 0x02);
+    bus.write(0x4018, 0x03);
+    bus.write(0x401F, 0x04);
+    bus.write(0x4020, 0x05);
+    bus.write(0xFFFF, 0x06);
+    assert_eq!(
+        *apu_seen.borrow(),
+        vec![(0x4000, Some(0x01)), (0x4017, Some(0x02))]
+    );
+    assert_eq!(
+        *test_seen.borrow(),
+        vec![(0x4018, Some(0x03)), (0x401F, Some(0x04))]
+    );
+    assert_eq!(
+        *cart_seen.borrow(),
+        vec![(0x4020, Some(0x05)), (0xFFFF, Some(0x06))]
+    );
+}
+
+#[test]
+fn cartridge_sees_ppu_register_writes() {
+    let mut bus = NesBus::new();
+    let (ppu, ppu_seen) = probe();
+    let (cart, cart_seen) = probe();
+    bus.connect_ppu(ppu);
+    bus.connect_cartridge(cart);
+    bus.write(0x2009, 0x18);
+    bus.read(0x2002, false);
+    assert_eq!(
+        *ppu_seen.borrow(),
+        vec![(0x2001, Some(0x18)), (0x2002, None)]
+    );
+    assert_eq!(*cart_seen.borrow(), vec![(0x2001, Some(0x18))]);
+}
+
+#[test]
+fn missing_components_read_open_bus() {
+    let mut bus = NesBus::new();
+    bus.write(0x0000, 0x5A);
+    assert_eq!(bus.read(0x8000, false), 0x5A);
+    assert_eq!(bus.read(0x2002, false), 0x5A);
+    assert_eq!(bus.open_bus(), 0x5A);
+}
+
+#[test]
+fn undecoded_cartridge_reads_return_open_bus() {
+    let mut bus = NesBus::new();
+    let image = Ines::new(0, 1, 1)
+        .prg_banks(16 * 1024, |_, _| 0xEA)
+        .chr_banks(8 * 1024, |_, _| 0x00);
+    bus.connect_cartridge(Box::new(load_board(&image.build())));
+    bus.write(0x0000, 0x5A);
+    assert_eq!(bus.read(0x4020, false), 0x5A);
+    assert_eq!(bus.read(0x5000, false), 0x5A);
+    assert_eq!(bus.read(0x8000, false), 0xEA);
+    assert_eq!(bus.open_bus(), 0xEA);
+}
+
+#[test]
+fn runs_a_2a03_program_from_cartridge_space() {
+    struct Rom(Vec<Byte>);
+    impl Bus for Rom {
+        fn read(&mut self, addr: Word, _: bool) -> Byte {
+            self.0[(addr as usize - 0x8000) % self.0.len()]
+        }
+        fn write(&mut self, _: Word, _: Byte) {}
+    }
+    let mut rom = vec![0xEA; 0x8000];
+    rom[0x7FFC] = Opcode::LdaIMM.into();
+    rom[0x7FFD] = 0x2A;
+    rom[0x7FFE] = Opcode::StaZPG.into();
+    rom[0x7FFF] = 0x10;
+    let mut bus = NesBus::new();
+    bus.connect_cartridge(Box::new(Rom(rom)));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.reset();
+    cpu.execute();
+    cpu.execute();
+    assert_eq!(cpu.read_byte(0x0810), 0x2A);
+}
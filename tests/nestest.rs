@@ -0,0 +1,99 @@
+use std::fs;
+
+mod common;
+
+use cpu_6502::{
+    bus::{Byte, Word},
+    cartridge::{Cartridge, mapper},
+    cpu::{CPU, Flag, Profile},
+    nes::bus::NesBus,
+};
+
+const ROM: &str = "tests/roms/nestest.nes";
+const LOG: &str = "tests/roms/nestest.log";
+
+/// Registers as printed by a nestest.log line
+#[derive(Debug, PartialEq, Eq)]
+struct State {
+    pc: Word,
+    a: Byte,
+    x: Byte,
+    y: Byte,
+    p: Byte,
+    sp: Byte,
+    /// CPU cycles since power on, the reset sequence's 7 included
+    cyc: u64,
+}
+
+impl State {
+    fn parse(line: &str) -> Self {
+        let field = |name: &str| {
+            let start = line.find(name).unwrap() + name.len();
+            Byte::from_str_radix(&line[start..start + 2], 16).unwrap()
+        };
+        Self {
+            pc: Word::from_str_radix(&line[0..4], 16).unwrap(),
+            a: field(" A:"),
+            x: field(" X:"),
+            y: field(" Y:"),
+            p: field(" P:"),
+            sp: field(" SP:"),
+            cyc: line[line.find(" CYC:").unwrap() + 5..]
+                .trim()
+                .parse()
+                .unwrap(),
+        }
+    }
+
+    fn of(cpu: &CPU) -> Self {
+        Self {
+            pc: cpu.pc,
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            p: cpu.flag.bits(),
+            sp: cpu.sp,
+            cyc: cpu.general_cycles,
+        }
+    }
+
+    /// `B` and the unused bit don't exist in the real status register
+    fn masked(mut self) -> Self {
+        self.p &= !(Flag::BREAK_COMMAND | Flag::UNUSED).bits();
+        self
+    }
+}
+
+/// Boots nestest in automation mode (PC forced to `$C000`) and compares every
+/// official-opcode line against the golden log, cycle counts included.
+#[test]
+#[ignore = "needs tests/roms/nestest.nes and tests/roms/nestest.log, which aren't shipped"]
+fn nestest_matches_golden_log() {
+    common::require_test_files(&[ROM, LOG]);
+    let cartridge = Cartridge::load(ROM).unwrap();
+    let log = fs::read_to_string(LOG).unwrap();
+    let mut bus = NesBus::new();
+    bus.connect_cartridge(Box::new(mapper::from_cartridge(cartridge).unwrap()));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.reset();
+    cpu.pc = 0xC000;
+    cpu.flag = Flag::from_bits_truncate(0x24);
+    // the log counts the reset sequence, which `reset` doesn't run
+    cpu.general_cycles = 7;
+
+    for (number, line) in log.lines().enumerate() {
+        // Unofficial opcodes are marked with a star in the disassembly column
+        if line.as_bytes().get(15) == Some(&b'*') {
+            break;
+        }
+        assert_eq!(
+            State::of(&cpu).masked(),
+            State::parse(line).masked(),
+            "line {}: {line}",
+            number + 1
+        );
+        cpu.execute();
+    }
+    assert_eq!(cpu.read_byte(0x0002), 0x00, "official opcode tests failed");
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::Byte,
+    cartridge::{Cartridge, CartridgeError, Mirroring, mapper},
+    cpu::{CPU, Profile, instructions::opcode::Opcode},
+    nes::bus::NesBus,
+};
+
+/// NROM image whose PRG bytes hold the low byte of their bank number and CHR bytes `0x80 | bank`
+fn build_nrom(prg_banks: Byte, chr_banks: Byte, flags6: Byte) -> Ines {
+    Ines::new(0, prg_banks, chr_banks)
+        .flags6(flags6)
+        .prg_banks(16 * 1024, |bank, _| bank as Byte)
+        .chr_banks(8 * 1024, |bank, _| 0x80 | bank as Byte)
+}
+
+#[test]
+fn nrom_128_mirrors_prg() {
+    let mut rom = build_nrom(1, 1, 0x00).build();
+    rom[16 + 0x1234] = 0x42;
+    let board = load_board(&rom);
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x9234, false), 0x42);
+    assert_eq!(board.read(0xD234, false), 0x42);
+}
+
+#[test]
+fn nrom_256_maps_both_banks() {
+    let board = load_board(&build_nrom(2, 1, 0x01).build());
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0x8000, false), 0x00);
+    assert_eq!(board.read(0xBFFF, false), 0x00);
+    assert_eq!(board.read(0xC000, false), 0x01);
+    assert_eq!(board.read(0xFFFF, false), 0x01);
+    board.write(0xC000, 0x55);
+    assert_eq!(board.read(0xC000, false), 0x01);
+    assert_eq!(board.mirroring(), Mirroring::Vertical);
+}
+
+#[test]
+fn prg_ram_at_6000() {
+    let board = load_board(&build_nrom(1, 1, 0x02).build());
+    let mut board = board.borrow_mut();
+    board.write(0x6000, 0x12);
+    board.write(0x7FFF, 0x34);
+    assert_eq!(board.read(0x6000, false), 0x12);
+    assert_eq!(board.read(0x7FFF, false), 0x34);
+    assert_eq!(board.save_ram().map(|ram| ram.len()), Some(8 * 1024));
+    assert_eq!(board.driven_bits(0x5000), 0x00);
+}
+
+#[test]
+fn chr_rom_is_read_only() {
+    let board = load_board(&build_nrom(1, 1, 0x00).build());
+    let mut board = board.borrow_mut();
+    board.ppu_write(0x0010, 0x00);
+    assert_eq!(board.ppu_read(0x0010), 0x80);
+    assert_eq!(board.ppu_read(0x1FFF), 0x80);
+    assert_eq!(board.mirroring(), Mirroring::Horizontal);
+}
+
+#[test]
+fn chr_ram_when_header_has_no_chr() {
+    let board = load_board(&build_nrom(1, 0, 0x00).build());
+    let mut board = board.borrow_mut();
+    board.ppu_write(0x1ABC, 0x77);
+    assert_eq!(board.ppu_read(0x1ABC), 0x77);
+    assert_eq!(board.ppu_peek(0x1ABC), 0x77);
+}
+
+#[test]
+fn unsupported_mappers_are_rejected() {
+    let rom = build_nrom(1, 1, 0x00).set(7, 0xF0).build();
+    let result = mapper::from_cartridge(Cartridge::from_bytes(&rom).unwrap());
+    assert!(matches!(
+        result,
+        Err(CartridgeError::UnsupportedMapper(0xF0))
+    ));
+}
+
+#[test]
+fn cpu_runs_from_nrom() {
+    let mut rom = build_nrom(1, 1, 0x00).build();
+    let prg = 16;
+    rom[prg + 0x3FFC] = Opcode::LdaABS.into();
+    rom[prg + 0x3FFD] = 0x00;
+    rom[prg + 0x3FFE] = 0x80;
+    rom[prg] = 0x99;
+    let mut bus = NesBus::new();
+    bus.connect_cartridge(Box::new(load_board(&rom)));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.reset();
+    cpu.execute();
+    assert_eq!(cpu.a, 0x99);
+}
@@ -0,0 +1,271 @@
+use std::io::{self, Cursor};
+
+use cpu_6502::{
+    audio::{AudioSink, wav::WavWriter},
+    bus::{Byte, Word},
+    cartridge::header::Region,
+    nsf::{
+        ExpansionChips, Nsf, NsfError,
+        player::{IDLE_LOOP, NsfPlayer},
+    },
+};
+
+const RATE: u32 = 44_100;
+
+/// INIT at `$8000` stores `A` and `X` in `$00` and `$01`, PLAY at `$8010` counts in `$02`
+const COUNTER: [Byte; 0x13] = [
+    0x85, 0x00, 0x86, 0x01, 0x60, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xE6, 0x02, 0x60,
+];
+
+fn nsf_image(load: Word, init: Word, play: Word, banks: [Byte; 8], data: &[Byte]) -> Vec<Byte> {
+    let mut image = b"NESM\x1A\x01".to_vec();
+    image.extend([3, 2]);
+    for addr in [load, init, play] {
+        image.extend(addr.to_le_bytes());
+    }
+    for text in [&b"Title"[..], b"Artist", b"2024 Someone"] {
+        let mut field = text.to_vec();
+        field.resize(32, 0);
+        image.extend(field);
+    }
+    image.extend(16_639u16.to_le_bytes());
+    image.extend(banks);
+    image.extend(19_997u16.to_le_bytes());
+    image.extend([0x02, 0x05, 0, 0, 0, 0]);
+    image.extend_from_slice(data);
+    image
+}
+
+fn chunk(id: &[u8; 4], data: &[Byte]) -> Vec<Byte> {
+    let mut chunk = (data.len() as u32).to_le_bytes().to_vec();
+    chunk.extend(id);
+    chunk.extend_from_slice(data);
+    chunk
+}
+
+fn nsfe_image(chunks: &[Vec<Byte>]) -> Vec<Byte> {
+    let mut image = b"NSFE".to_vec();
+    for chunk in chunks {
+        image.extend(chunk);
+    }
+    image
+}
+
+fn counter_player() -> NsfPlayer {
+    let nsf = Nsf::from_bytes(&nsf_image(0x8000, 0x8000, 0x8010, [0; 8], &COUNTER)).unwrap();
+    NsfPlayer::new(nsf, RATE)
+}
+
+struct Recorder(Vec<f32>);
+
+impl AudioSink for Recorder {
+    fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        self.0.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+#[test]
+fn parses_the_nsf_header() {
+    let nsf = Nsf::from_bytes(&nsf_image(0x8000, 0x8003, 0x8010, [0; 8], &COUNTER)).unwrap();
+    assert_eq!(nsf.song_count, 3);
+    assert_eq!(nsf.starting_song, 1);
+    assert_eq!(
+        (nsf.load_address, nsf.init_address, nsf.play_address),
+        (0x8000, 0x8003, 0x8010)
+    );
+    assert_eq!(nsf.title, "Title");
+    assert_eq!(nsf.artist, "Artist");
+    assert_eq!(nsf.copyright, "2024 Someone");
+    assert_eq!((nsf.ntsc_speed, nsf.pal_speed), (16_639, 19_997));
+    assert_eq!(nsf.banks, None);
+    assert_eq!(nsf.region, Region::MultiRegion);
+    assert_eq!(nsf.chips, ExpansionChips::VRC6 | ExpansionChips::FDS);
+    assert_eq!(nsf.data, COUNTER);
+
+    let banked = Nsf::from_bytes(&nsf_image(
+        0x8000,
+        0x8000,
+        0x8010,
+        [0, 1, 2, 3, 4, 5, 6, 7],
+        &COUNTER,
+    ));
+    assert_eq!(banked.unwrap().banks, Some([0, 1, 2, 3, 4, 5, 6, 7]));
+}
+
+#[test]
+fn rejects_bad_files() {
+    assert!(matches!(
+        Nsf::from_bytes(b"NES\x1A\x01\x01"),
+        Err(NsfError::BadMagic)
+    ));
+    assert!(matches!(
+        Nsf::from_bytes(b"NESM\x1A\x01\x03\x01"),
+        Err(NsfError::Truncated {
+            expected: 0x80,
+            actual: 8
+        })
+    ));
+    let missing_data = nsfe_image(&[chunk(b"INFO", &[0; 10])]);
+    assert!(matches!(
+        Nsf::from_bytes(&missing_data),
+        Err(NsfError::MissingChunk("DATA"))
+    ));
+    // Nothing to map at $8000, bankswitched or not
+    let empty = nsf_image(0x8000, 0x8000, 0x8010, [0, 1, 0, 0, 0, 0, 0, 0], &[]);
+    assert!(matches!(Nsf::from_bytes(&empty), Err(NsfError::NoData)));
+    let empty = nsfe_image(&[chunk(b"INFO", &[0; 10]), chunk(b"DATA", &[])]);
+    assert!(matches!(Nsf::from_bytes(&empty), Err(NsfError::NoData)));
+    let unknown = nsfe_image(&[chunk(b"INFO", &[0; 10]), chunk(b"VRC7", &[1])]);
+    assert!(matches!(
+        Nsf::from_bytes(&unknown),
+        Err(NsfError::UnsupportedChunk(id)) if &id == b"VRC7"
+    ));
+}
+
+#[test]
+fn parses_nsfe_chunks() {
+    let info = [0x00, 0x80, 0x00, 0x80, 0x10, 0x80, 0x01, 0x08, 2, 1];
+    let image = nsfe_image(&[
+        chunk(b"INFO", &info),
+        chunk(b"BANK", &[0, 1]),
+        chunk(b"RATE", &10_000u16.to_le_bytes()),
+        chunk(b"auth", b"Song\0Someone\0\0Ripper\0"),
+        chunk(
+            b"time",
+            &[&1500i32.to_le_bytes()[..], &(-1i32).to_le_bytes()].concat(),
+        ),
+        chunk(b"tlbl", b"Intro\0Boss\0"),
+        // Lowercase chunks are optional and skipped when unknown
+        chunk(b"text", b"liner notes"),
+        chunk(b"DATA", &COUNTER),
+        chunk(b"NEND", &[]),
+    ]);
+    let nsf = Nsf::from_bytes(&image).unwrap();
+    assert_eq!(
+        (nsf.load_address, nsf.init_address, nsf.play_address),
+        (0x8000, 0x8000, 0x8010)
+    );
+    assert_eq!(nsf.region, Region::Pal);
+    assert_eq!(nsf.chips, ExpansionChips::MMC5);
+    assert_eq!((nsf.song_count, nsf.starting_song), (2, 1));
+    assert_eq!(nsf.banks, Some([0, 1, 0, 0, 0, 0, 0, 0]));
+    assert_eq!(nsf.ntsc_speed, 10_000);
+    assert_eq!(nsf.title, "Song");
+    assert_eq!(nsf.artist, "Someone");
+    assert_eq!(nsf.copyright, "");
+    assert_eq!(nsf.track_lengths, [1500, -1]);
+    assert_eq!(nsf.track_names, ["Intro", "Boss"]);
+    assert_eq!(nsf.data, COUNTER);
+}
+
+#[test]
+fn init_gets_the_song_and_region() {
+    let mut player = counter_player();
+    player.start_song(2);
+    assert_eq!(player.peek(0x00), 2);
+    assert_eq!(player.peek(0x01), 0);
+    assert_eq!(player.peek(0x02), 0);
+}
+
+#[test]
+fn play_is_called_at_the_play_rate() {
+    let mut player = counter_player();
+    // 16639 µs at the NTSC clock
+    assert_eq!(player.play_period(), 29_780);
+    player.start_song(0);
+    let mut samples = 0;
+    for _ in 0..60 {
+        samples += player.run_frame().len();
+    }
+    assert_eq!(player.peek(0x02), 60);
+    // 60 periods of 29780 cycles
+    assert!(samples.abs_diff(44_027) <= 1, "{samples}");
+
+    // Starting a song clears the RAM and runs INIT again
+    player.start_song(1);
+    assert_eq!((player.peek(0x00), player.peek(0x02)), (1, 0));
+}
+
+#[test]
+fn routines_return_to_the_idle_loop() {
+    let mut player = counter_player();
+    assert_eq!(player.peek(IDLE_LOOP), 0x4C);
+    assert_eq!(player.peek(IDLE_LOOP + 1), 0xF0);
+    assert_eq!(player.peek(IDLE_LOOP + 2), 0x5F);
+    player.start_song(0);
+    player.run_frame();
+    // The JSR frames are popped, nothing grows on the stack
+    for _ in 0..10 {
+        player.run_frame();
+    }
+    assert_eq!(player.peek(0x02), 11);
+}
+
+#[test]
+fn bank_registers_switch_4k_windows() {
+    // INIT: LDA $9000, STA $00, LDA #2, STA $5FF9, LDA $9000, STA $01,
+    // LDA $6000, STA $02, INC $6000, RTS
+    let init = [
+        0xAD, 0x00, 0x90, 0x85, 0x00, 0xA9, 0x02, 0x8D, 0xF9, 0x5F, 0xAD, 0x00, 0x90, 0x85, 0x01,
+        0xAD, 0x00, 0x60, 0x85, 0x02, 0xEE, 0x00, 0x60, 0x60,
+    ];
+    // Loaded at $8100: the first bank starts with $100 bytes of padding
+    let mut data = init.to_vec();
+    data.resize(0x1000 - 0x100, 0);
+    for bank in 1..=2 {
+        data.extend(std::iter::repeat_n(bank * 0x11, 0x1000));
+    }
+    let image = nsf_image(0x8100, 0x8100, 0x8100, [0, 1, 0, 0, 0, 0, 0, 0], &data);
+    let mut player = NsfPlayer::new(Nsf::from_bytes(&image).unwrap(), RATE);
+    player.start_song(0);
+    assert_eq!(player.peek(0x00), 0x11);
+    assert_eq!(player.peek(0x01), 0x22);
+    assert_eq!(player.peek(0x9000), 0x22);
+
+    // A new song restores the header banks and clears $6000
+    player.start_song(0);
+    assert_eq!(player.peek(0x00), 0x11);
+    assert_eq!(player.peek(0x02), 0x00);
+}
+
+#[test]
+fn renders_a_pulse_tone() {
+    // INIT: $4015 = 1, $4000 = $BF (50% duty, constant volume 15), period $0FD
+    let init = [
+        0xA9, 0x01, 0x8D, 0x15, 0x40, 0xA9, 0xBF, 0x8D, 0x00, 0x40, 0xA9, 0xFD, 0x8D, 0x02, 0x40,
+        0xA9, 0x00, 0x8D, 0x03, 0x40, 0x60,
+    ];
+    let mut data = init.to_vec();
+    data.resize(0x20, 0);
+    data.push(0x60);
+    let nsf = Nsf::from_bytes(&nsf_image(0x8000, 0x8000, 0x8020, [0; 8], &data)).unwrap();
+    let mut player = NsfPlayer::new(nsf, RATE);
+    player.start_song(0);
+    let mut recorder = Recorder(Vec::new());
+    player.render(1.0, &mut recorder).unwrap();
+    assert_eq!(recorder.0.len(), RATE as usize);
+
+    // 1789773 / (16 * 254) = 440.4 Hz
+    let second_half = &recorder.0[RATE as usize / 2..];
+    let mut positive = second_half[0] > 0.0;
+    let mut crossings = 0usize;
+    for &sample in second_half {
+        if positive && sample < -0.02 || !positive && sample > 0.02 {
+            positive = !positive;
+            crossings += 1;
+        }
+    }
+    assert!(crossings.abs_diff(440) <= 2, "{crossings}");
+}
+
+#[test]
+fn renders_to_wav() {
+    let mut player = counter_player();
+    player.start_song(0);
+    let mut wav = WavWriter::new(Cursor::new(Vec::new()), RATE).unwrap();
+    player.render(0.5, &mut wav).unwrap();
+    let bytes = wav.finish().unwrap().into_inner();
+    assert_eq!(bytes.len(), 44 + RATE as usize);
+    assert_eq!(player.peek(0x02), 31);
+}
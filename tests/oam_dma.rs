@@ -0,0 +1,113 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cpu_6502::{
+    bus::Byte,
+    cpu::{Access, CPU, Profile, instructions::opcode::Opcode},
+    nes::bus::NesBus,
+    ppu::Ppu,
+};
+
+/// CPU running `LDA #$02; STA $4014; NOP` from `$0300`, with page `$02`
+/// holding `0xFF - i` at offset `i`
+fn setup(profile: Profile) -> (CPU, Rc<RefCell<Ppu>>) {
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    let mut bus = NesBus::new();
+    bus.connect_ppu(Box::new(ppu.clone()));
+    let mut cpu = CPU::with_profile(profile);
+    cpu.connect_bus(Box::new(bus));
+    cpu.attach_device(Box::new(ppu.clone()));
+    cpu.reset();
+    for i in 0..=0xFF {
+        cpu.write(0x0200 + i, 0xFF - i as Byte);
+    }
+    let program: [Byte; _] = [
+        Opcode::LdaIMM.into(),
+        0x02,
+        Opcode::StaABS.into(),
+        0x14,
+        0x40,
+        Opcode::NopIMP.into(),
+    ];
+    for (i, &byte) in program.iter().enumerate() {
+        cpu.write(0x0300 + i as u16, byte);
+    }
+    cpu.pc = 0x0300;
+    (cpu, ppu)
+}
+
+/// Runs the `LDA` and `STA`, starting the DMA on the given cycle parity
+fn start_dma(cpu: &mut CPU, odd: bool) {
+    cpu.general_cycles = odd as u64;
+    cpu.execute();
+    cpu.execute();
+}
+
+#[test]
+fn copies_the_page_to_oam() {
+    let (mut cpu, ppu) = setup(Profile::Ricoh2A03);
+    start_dma(&mut cpu, false);
+    cpu.execute();
+    let expected: Vec<Byte> = (0..=0xFF).map(|i: Byte| 0xFF - i).collect();
+    assert_eq!(ppu.borrow().oam.to_vec(), expected);
+    assert_eq!(cpu.pc, 0x0306);
+}
+
+#[test]
+fn takes_513_or_514_cycles_depending_on_alignment() {
+    for (odd, dma_cycles) in [(false, 514), (true, 513)] {
+        let (mut cpu, _) = setup(Profile::Ricoh2A03);
+        start_dma(&mut cpu, odd);
+        let start = cpu.general_cycles;
+        // DMA then the 2 cycle NOP
+        assert_eq!(cpu.execute(), dma_cycles + 2);
+        assert_eq!(cpu.general_cycles - start, dma_cycles as u64 + 2);
+    }
+}
+
+#[test]
+fn observers_see_reads_on_get_and_writes_on_put_cycles() {
+    let (mut cpu, _) = setup(Profile::Ricoh2A03);
+    start_dma(&mut cpu, true);
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let sink = events.clone();
+    cpu.add_observer(move |event| sink.borrow_mut().push(*event));
+    cpu.execute();
+    let events = events.borrow();
+    let dma: Vec<_> = events
+        .iter()
+        .filter(|e| matches!(e.access, Access::DmaRead | Access::DmaWrite))
+        .collect();
+    assert_eq!(dma.len(), 512);
+    for pair in dma.chunks(2) {
+        assert_eq!(pair[0].access, Access::DmaRead);
+        assert_eq!(pair[0].cycle % 2, 0);
+        assert_eq!(pair[1].access, Access::DmaWrite);
+        assert_eq!(pair[1].addr, 0x2004);
+        assert_eq!(pair[1].value, pair[0].value);
+        assert_eq!(pair[1].cycle, pair[0].cycle + 1);
+    }
+    assert_eq!((dma[0].addr, dma[510].addr), (0x0200, 0x02FF));
+}
+
+#[test]
+fn single_stepping_runs_the_same_cycles() {
+    for (odd, dma_cycles) in [(false, 514), (true, 513)] {
+        let (mut cpu, ppu) = setup(Profile::Ricoh2A03);
+        start_dma(&mut cpu, odd);
+        let start = cpu.general_cycles;
+        while cpu.pc != 0x0306 {
+            cpu.clock();
+        }
+        // The NOP is fetched on the cycle after the DMA
+        assert_eq!(cpu.general_cycles - start, dma_cycles as u64 + 1);
+        assert_eq!(ppu.borrow().oam[0], 0xFF);
+    }
+}
+
+#[test]
+fn plain_6502_has_no_dma() {
+    let (mut cpu, ppu) = setup(Profile::Nmos6502);
+    start_dma(&mut cpu, false);
+    assert_eq!(cpu.execute(), 2);
+    assert!(ppu.borrow().oam.iter().all(|&byte| byte == 0));
+}
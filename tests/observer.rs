@@ -0,0 +1,334 @@
+mod common;
+use std::{cell::RefCell, rc::Rc};
+
+use common::setup_cpu_bus;
+use cpu_6502::{
+    bus::Word,
+    cpu::{
+        Access, CPU, Flag,
+        instructions::opcode::Opcode,
+        observer::{BusEvent, ObserverId},
+    },
+};
+
+fn record(cpu: &mut CPU) -> (ObserverId, Rc<RefCell<Vec<BusEvent>>>) {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let sink = events.clone();
+    let id = cpu.add_observer(move |event| sink.borrow_mut().push(*event));
+    (id, events)
+}
+
+#[test]
+fn observer_sees_fetch_operand_and_write() {
+    let mut cpu = setup_cpu_bus();
+    cpu.write(0xFFFC, Opcode::StaZPG.into());
+    cpu.write(0xFFFD, 0x42);
+    cpu.a = 0x37;
+    let (_, events) = record(&mut cpu);
+    cpu.execute();
+    let events = events.borrow();
+    let accesses: Vec<_> = events
+        .iter()
+        .map(|e| (e.addr, e.value, e.access, e.cycle))
+        .collect();
+    assert_eq!(
+        accesses,
+        vec![
+            (0xFFFC, Opcode::StaZPG.into(), Access::Fetch, 0),
+            (0xFFFD, 0x42, Access::Read, 1),
+            (0x0042, 0x37, Access::Write, 2),
+        ]
+    );
+}
+
+#[test]
+fn read_modify_write_does_a_dummy_write() {
+    let mut cpu = setup_cpu_bus();
+    cpu.write(0xFFFC, Opcode::IncZPG.into());
+    cpu.write(0xFFFD, 0x42);
+    cpu.write(0x0042, 0x09);
+    let (_, events) = record(&mut cpu);
+    cpu.execute();
+    let writes: Vec<_> = events
+        .borrow()
+        .iter()
+        .filter(|e| e.addr == 0x0042)
+        .map(|e| (e.value, e.access))
+        .collect();
+    assert_eq!(
+        writes,
+        vec![
+            (0x09, Access::Read),
+            (0x09, Access::DummyWrite),
+            (0x0A, Access::Write),
+        ]
+    );
+}
+
+#[test]
+fn page_crossing_does_a_dummy_read() {
+    let mut cpu = setup_cpu_bus();
+    cpu.write(0xFFFC, Opcode::LdaABX.into());
+    cpu.write(0xFFFD, 0xFF);
+    cpu.write(0xFFFE, 0x20);
+    cpu.write(0x2100, 0x55);
+    cpu.x = 0x01;
+    let (_, events) = record(&mut cpu);
+    cpu.execute();
+    let tail: Vec<_> = events
+        .borrow()
+        .iter()
+        .skip(3)
+        .map(|e| (e.addr, e.access))
+        .collect();
+    assert_eq!(
+        tail,
+        vec![(0x2000, Access::DummyRead), (0x2100, Access::Read)]
+    );
+    assert_eq!(cpu.a, 0x55);
+}
+
+#[test]
+fn cycles_keep_counting_across_instructions() {
+    let mut cpu = setup_cpu_bus();
+    cpu.write(0xFFFC, Opcode::NopIMP.into());
+    cpu.write(0xFFFD, Opcode::NopIMP.into());
+    let (_, events) = record(&mut cpu);
+    cpu.execute();
+    cpu.execute();
+    let cycles: Vec<_> = events
+        .borrow()
+        .iter()
+        .filter(|e| e.access == Access::Fetch)
+        .map(|e| e.cycle)
+        .collect();
+    assert_eq!(cycles, vec![0, 2]);
+}
+
+/// Address and kind of every access after the opcode fetch and `operands` bytes
+fn accesses_after(events: &[BusEvent], operands: usize) -> Vec<(Word, Access)> {
+    events
+        .iter()
+        .skip(1 + operands)
+        .map(|e| (e.addr, e.access))
+        .collect()
+}
+
+#[test]
+fn indexed_stores_always_do_a_dummy_read() {
+    for (x, dummy, addr) in [(0x01, 0x20F1, 0x20F1), (0x20, 0x2010, 0x2110)] {
+        let mut cpu = setup_cpu_bus();
+        cpu.write(0xFFFC, Opcode::StaABX.into());
+        cpu.write(0xFFFD, 0xF0);
+        cpu.write(0xFFFE, 0x20);
+        cpu.x = x;
+        let (_, events) = record(&mut cpu);
+        cpu.execute();
+        assert_eq!(
+            accesses_after(&events.borrow(), 2),
+            vec![(dummy, Access::DummyRead), (addr, Access::Write)]
+        );
+    }
+}
+
+#[test]
+fn indexed_read_modify_write_always_does_a_dummy_read() {
+    let mut cpu = setup_cpu_bus();
+    cpu.write(0xFFFC, Opcode::IncABX.into());
+    cpu.write(0xFFFD, 0x00);
+    cpu.write(0xFFFE, 0x20);
+    cpu.x = 0x01;
+    let (_, events) = record(&mut cpu);
+    cpu.execute();
+    assert_eq!(
+        accesses_after(&events.borrow(), 2),
+        vec![
+            (0x2001, Access::DummyRead),
+            (0x2001, Access::Read),
+            (0x2001, Access::DummyWrite),
+            (0x2001, Access::Write),
+        ]
+    );
+}
+
+#[test]
+fn indirect_indexed_stores_always_do_a_dummy_read() {
+    let mut cpu = setup_cpu_bus();
+    cpu.write(0xFFFC, Opcode::StaIDY.into());
+    cpu.write(0xFFFD, 0x10);
+    cpu.write(0x0010, 0x00);
+    cpu.write(0x0011, 0x20);
+    cpu.y = 0x05;
+    let (_, events) = record(&mut cpu);
+    cpu.execute();
+    assert_eq!(
+        accesses_after(&events.borrow(), 3),
+        vec![(0x2005, Access::DummyRead), (0x2005, Access::Write)]
+    );
+}
+
+#[test]
+fn implied_and_accumulator_instructions_read_the_next_byte() {
+    for opcode in [Opcode::NopIMP, Opcode::AslIMP] {
+        let mut cpu = setup_cpu_bus();
+        cpu.write(0xFFFC, opcode.into());
+        let (_, events) = record(&mut cpu);
+        cpu.execute();
+        assert_eq!(
+            accesses_after(&events.borrow(), 0),
+            vec![(0xFFFD, Access::DummyRead)],
+            "{opcode:?}"
+        );
+        assert_eq!(cpu.pc, 0xFFFD);
+    }
+}
+
+#[test]
+fn zero_page_indexing_reads_the_unindexed_address() {
+    for (opcode, reads) in [
+        (
+            Opcode::LdaZPX,
+            vec![(0x0080, Access::DummyRead), (0x0085, Access::Read)],
+        ),
+        (
+            Opcode::LdxZPY,
+            vec![(0x0080, Access::DummyRead), (0x0086, Access::Read)],
+        ),
+        (
+            Opcode::LdaIDX,
+            vec![
+                (0x0080, Access::DummyRead),
+                (0x0085, Access::Read),
+                (0x0086, Access::Read),
+                (0x2000, Access::Read),
+            ],
+        ),
+    ] {
+        let mut cpu = setup_cpu_bus();
+        cpu.write(0xFFFC, opcode.into());
+        cpu.write(0xFFFD, 0x80);
+        cpu.write(0x0086, 0x20);
+        cpu.x = 0x05;
+        cpu.y = 0x06;
+        let (_, events) = record(&mut cpu);
+        cpu.execute();
+        assert_eq!(accesses_after(&events.borrow(), 1), reads, "{opcode:?}");
+    }
+}
+
+#[test]
+fn stack_instructions_do_dummy_reads() {
+    let pushes = vec![(0xFFFD, Access::DummyRead), (0x01FD, Access::Write)];
+    let pulls = vec![
+        (0xFFFD, Access::DummyRead),
+        (0x01FD, Access::DummyRead),
+        (0x01FE, Access::Read),
+    ];
+    for (opcode, operands, accesses) in [
+        (Opcode::PhaIMP, 0, pushes.clone()),
+        (Opcode::PhpIMP, 0, pushes),
+        (Opcode::PlaIMP, 0, pulls.clone()),
+        (Opcode::PlpIMP, 0, pulls),
+        (
+            Opcode::RtsIMP,
+            0,
+            vec![
+                (0xFFFD, Access::DummyRead),
+                (0x01FD, Access::DummyRead),
+                (0x01FE, Access::Read),
+                (0x01FF, Access::Read),
+                (0x1233, Access::DummyRead),
+            ],
+        ),
+        (
+            Opcode::RtiIMP,
+            0,
+            vec![
+                (0xFFFD, Access::DummyRead),
+                (0x01FD, Access::DummyRead),
+                (0x01FE, Access::Read),
+                (0x01FF, Access::Read),
+                (0x0100, Access::Read),
+            ],
+        ),
+        (
+            Opcode::BrkIMP,
+            0,
+            vec![
+                (0xFFFD, Access::DummyRead),
+                (0x01FD, Access::Write),
+                (0x01FC, Access::Write),
+                (0x01FB, Access::Write),
+                (0xFFFE, Access::Read),
+                (0xFFFF, Access::Read),
+            ],
+        ),
+        (
+            Opcode::JsrABS,
+            2,
+            vec![
+                (0x01FD, Access::DummyRead),
+                (0x01FD, Access::Write),
+                (0x01FC, Access::Write),
+            ],
+        ),
+    ] {
+        let mut cpu = setup_cpu_bus();
+        cpu.write(0xFFFC, opcode.into());
+        cpu.write(0x01FE, 0x33);
+        cpu.write(0x01FF, 0x12);
+        let (_, events) = record(&mut cpu);
+        cpu.execute();
+        assert_eq!(
+            accesses_after(&events.borrow(), operands),
+            accesses,
+            "{opcode:?}"
+        );
+    }
+}
+
+#[test]
+fn taken_branches_do_dummy_reads() {
+    // Not taken, taken, taken across a page
+    for (zero, pc, offset, dummies) in [
+        (false, 0x0280, 0x10, vec![]),
+        (true, 0x0280, 0x10, vec![(0x0282, Access::DummyRead)]),
+        (
+            true,
+            0x02F0,
+            0x20,
+            vec![(0x02F2, Access::DummyRead), (0x0212, Access::DummyRead)],
+        ),
+    ] {
+        let mut cpu = setup_cpu_bus();
+        cpu.pc = pc;
+        cpu.write(pc, Opcode::BeqREL.into());
+        cpu.write(pc + 1, offset);
+        cpu.flag.set(Flag::ZERO, zero);
+        let (_, events) = record(&mut cpu);
+        cpu.execute();
+        assert_eq!(accesses_after(&events.borrow(), 1), dummies, "{pc:04X}");
+    }
+}
+
+#[test]
+fn observers_can_be_removed_at_runtime() {
+    let mut cpu = setup_cpu_bus();
+    let (first, first_events) = record(&mut cpu);
+    let (_, second_events) = record(&mut cpu);
+    cpu.read_byte(0x1234);
+    assert!(cpu.remove_observer(first));
+    assert!(!cpu.remove_observer(first));
+    cpu.read_byte(0x1234);
+    assert_eq!(first_events.borrow().len(), 1);
+    assert_eq!(second_events.borrow().len(), 2);
+}
+
+#[test]
+fn peek_is_not_observed() {
+    let mut cpu = setup_cpu_bus();
+    let (_, events) = record(&mut cpu);
+    cpu.peek(0x1234);
+    cpu.disassemble(0x0000, 0x0010);
+    assert!(events.borrow().is_empty());
+}
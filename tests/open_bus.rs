@@ -0,0 +1,109 @@
+mod common;
+use common::setup_cpu_bus;
+use cpu_6502::{
+    bus::{Bus, Byte, Word, memory_map::MemoryMap, open_bus::OpenBus},
+    cpu::{CPU, instructions::opcode::Opcode},
+};
+
+/// Controller port that only drives bit 0
+struct Controller;
+
+impl Bus for Controller {
+    fn read(&mut self, _: Word, _: bool) -> Byte {
+        0x01
+    }
+
+    fn write(&mut self, _: Word, _: Byte) {}
+}
+
+#[test]
+fn cpu_latches_last_transfer() {
+    let mut cpu = setup_cpu_bus();
+    cpu.write(0x0200, 0x5A);
+    assert_eq!(cpu.data_bus, 0x5A);
+    cpu.write(0x0300, 0xA5);
+    cpu.read_byte(0x0200);
+    assert_eq!(cpu.data_bus, 0x5A);
+    cpu.peek(0x0300);
+    assert_eq!(cpu.data_bus, 0x5A);
+}
+
+#[test]
+fn unmapped_reads_return_open_bus() {
+    let mut map = MemoryMap::builder()
+        .ram(0x0000..=0x07FF)
+        .open_bus(OpenBus::new())
+        .build()
+        .unwrap();
+    map.write(0x0010, 0x42);
+    assert_eq!(map.read(0x5000, false), 0x42);
+    map.read(0x0000, false);
+    assert_eq!(map.read(0x5000, false), 0x00);
+}
+
+#[test]
+fn peeks_do_not_update_open_bus() {
+    let mut map = MemoryMap::builder()
+        .ram(0x0000..=0x07FF)
+        .open_bus(OpenBus::new())
+        .build()
+        .unwrap();
+    map.write(0x0010, 0x42);
+    map.write(0x0011, 0x24);
+    map.peek(0x0010);
+    assert_eq!(map.read(0x5000, false), 0x24);
+}
+
+#[test]
+fn unmapped_reads_see_the_operand_high_byte() {
+    let mut rom = vec![0xEA; 0x8000];
+    rom[0x7FFC] = Opcode::LdaABS.into();
+    rom[0x7FFD] = 0x00;
+    rom[0x7FFE] = 0x50;
+    let map = MemoryMap::builder()
+        .ram(0x0000..=0x07FF)
+        .rom(0x8000..=0xFFFF, rom)
+        .open_bus(OpenBus::new())
+        .build()
+        .unwrap();
+    let mut cpu = CPU::new();
+    cpu.connect_bus(Box::new(map));
+    cpu.reset();
+    cpu.execute();
+    assert_eq!(cpu.a, 0x50);
+    assert_eq!(cpu.data_bus, 0x50);
+}
+
+#[test]
+fn partial_devices_mix_in_open_bus() {
+    let mut map = MemoryMap::builder()
+        .ram(0x0000..=0x07FF)
+        .partial_device(0x4016..=0x4017, 0x1F, Box::new(Controller))
+        .open_bus(OpenBus::new())
+        .build()
+        .unwrap();
+    map.write(0x0000, 0x40);
+    assert_eq!(map.read(0x4016, false), 0x41);
+}
+
+#[test]
+fn bits_decay_when_not_refreshed() {
+    let mut open_bus = OpenBus::with_decay(10);
+    open_bus.latch(0xFF);
+    open_bus.tick(5);
+    open_bus.drive(0x0F, 0x0F);
+    assert_eq!(open_bus.value(), 0xFF);
+    open_bus.tick(5);
+    assert_eq!(open_bus.value(), 0x0F);
+    open_bus.tick(5);
+    assert_eq!(open_bus.value(), 0x00);
+}
+
+#[test]
+fn without_decay_the_value_sticks() {
+    let mut open_bus = OpenBus::new();
+    open_bus.latch(0x80);
+    open_bus.tick(1_000_000);
+    assert_eq!(open_bus.value(), 0x80);
+    assert_eq!(open_bus.merge(0x01, 0x01), 0x81);
+}
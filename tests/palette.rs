@@ -0,0 +1,130 @@
+use cpu_6502::{
+    bus::Byte,
+    ppu::{
+        DOTS_PER_SCANLINE, HEIGHT, Mask, Ppu, SCANLINES, WIDTH,
+        palette::{Palette, PaletteError},
+    },
+};
+
+/// `.pal` file where color `i` of emphasis set `e` is `[i, e, 0x80 + i]`
+fn pal_file(sets: usize) -> Vec<Byte> {
+    (0..sets * 64)
+        .flat_map(|entry| {
+            [
+                (entry % 64) as Byte,
+                (entry / 64) as Byte,
+                0x80 + (entry % 64) as Byte,
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn default_palette_is_the_2c02() {
+    let palette = Palette::default();
+    assert_eq!(palette.rgb(0x00, Mask::empty()), [84, 84, 84]);
+    assert_eq!(palette.rgb(0x0F, Mask::empty()), [0, 0, 0]);
+    assert_eq!(palette.rgb(0x21, Mask::empty()), [76, 154, 236]);
+    assert_eq!(palette.rgb(0x30, Mask::empty()), [236, 238, 236]);
+    // The 2 top bits of a palette RAM byte aren't part of the color
+    assert_eq!(
+        palette.rgb(0xE1, Mask::empty()),
+        palette.rgb(0x21, Mask::empty())
+    );
+}
+
+#[test]
+fn greyscale_keeps_the_brightness_row() {
+    let palette = Palette::default();
+    for color in 0..64 {
+        assert_eq!(
+            palette.rgb(color, Mask::GREYSCALE),
+            palette.rgb(color & 0x30, Mask::empty())
+        );
+    }
+}
+
+#[test]
+fn emphasis_dims_the_other_channels() {
+    let palette = Palette::default();
+    assert_eq!(palette.rgb(0x30, Mask::EMPHASIZE_RED), [236, 194, 193]);
+    assert_eq!(palette.rgb(0x30, Mask::EMPHASIZE_GREEN), [193, 238, 193]);
+    assert_eq!(palette.rgb(0x30, Mask::EMPHASIZE_BLUE), [193, 194, 236]);
+    let all = Mask::EMPHASIZE_RED | Mask::EMPHASIZE_GREEN | Mask::EMPHASIZE_BLUE;
+    assert_eq!(palette.rgb(0x30, all), [157, 158, 157]);
+    let red_green = Mask::EMPHASIZE_RED | Mask::EMPHASIZE_GREEN;
+    assert_eq!(palette.rgb(0x30, red_green), [193, 194, 157]);
+    // Rendering enables don't change the color
+    assert_eq!(
+        palette.rgb(0x16, Mask::BACKGROUND | Mask::SPRITES),
+        palette.rgb(0x16, Mask::empty())
+    );
+}
+
+#[test]
+fn loads_64_color_files() {
+    let palette = Palette::from_bytes(&pal_file(1)).unwrap();
+    assert_eq!(palette.rgb(0x12, Mask::empty()), [0x12, 0, 0x92]);
+    assert_eq!(palette.rgb(0x3F, Mask::EMPHASIZE_BLUE), [0x33, 0, 0xBF]);
+}
+
+#[test]
+fn loads_512_color_files() {
+    let palette = Palette::from_bytes(&pal_file(8)).unwrap();
+    assert_eq!(palette.rgb(0x12, Mask::empty()), [0x12, 0, 0x92]);
+    assert_eq!(palette.rgb(0x12, Mask::EMPHASIZE_RED), [0x12, 1, 0x92]);
+    assert_eq!(palette.rgb(0x12, Mask::EMPHASIZE_GREEN), [0x12, 2, 0x92]);
+    assert_eq!(
+        palette.rgb(0x12, Mask::EMPHASIZE_RED | Mask::EMPHASIZE_BLUE),
+        [0x12, 5, 0x92]
+    );
+    assert_eq!(
+        palette.rgb(0x12, Mask::GREYSCALE | Mask::EMPHASIZE_BLUE),
+        [0x10, 4, 0x90]
+    );
+}
+
+#[test]
+fn rejects_other_sizes() {
+    assert!(matches!(
+        Palette::from_bytes(&[0; 200]),
+        Err(PaletteError::BadSize(200))
+    ));
+    assert!(matches!(
+        Palette::from_file("/nonexistent/palette.pal"),
+        Err(PaletteError::Io(_))
+    ));
+}
+
+#[test]
+fn loads_pal_files_from_disk() {
+    let path = std::env::temp_dir().join(format!("cpu_6502_pal_{}.pal", std::process::id()));
+    std::fs::write(&path, pal_file(8)).unwrap();
+    let palette = Palette::from_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(palette.unwrap(), Palette::from_bytes(&pal_file(8)).unwrap());
+}
+
+#[test]
+fn converts_a_ppu_frame_to_rgba() {
+    let mut ppu = Ppu::new();
+    // With rendering off the backdrop fills the screen
+    ppu.write_memory(0x3F00, 0x21);
+    for _ in 0..SCANLINES * DOTS_PER_SCANLINE {
+        ppu.clock_dot();
+    }
+    let palette = Palette::default();
+    let rgba = palette.frame_to_rgba(ppu.frame(), ppu.mask);
+    assert_eq!(rgba.len(), WIDTH * HEIGHT * 4);
+    assert!(
+        rgba.chunks_exact(4)
+            .all(|pixel| pixel == [76, 154, 236, 0xFF])
+    );
+
+    let mut out = vec![0; rgba.len()];
+    palette.to_rgba(ppu.frame(), Mask::GREYSCALE, &mut out);
+    assert!(
+        out.chunks_exact(4)
+            .all(|pixel| pixel == [236, 238, 236, 0xFF])
+    );
+}
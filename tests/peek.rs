@@ -0,0 +1,92 @@
+use std::{cell::Cell, rc::Rc};
+
+use cpu_6502::{
+    bus::{Bus, Byte, Word, memory_map::MemoryMap},
+    cpu::{CPU, instructions::opcode::Opcode},
+};
+
+const STATUS: Word = 0x2002;
+
+/// A status register whose top bit is cleared by reading it, like PPUSTATUS
+struct LatchDevice {
+    latch: Rc<Cell<Byte>>,
+}
+
+impl Bus for LatchDevice {
+    fn read(&mut self, _: Word, read_only: bool) -> Byte {
+        let value = self.latch.get();
+        if !read_only {
+            self.latch.set(value & 0x7F);
+        }
+        value
+    }
+
+    fn write(&mut self, _: Word, value: Byte) {
+        self.latch.set(value);
+    }
+}
+
+fn setup_cpu_latch() -> (CPU, Rc<Cell<Byte>>) {
+    let latch = Rc::new(Cell::new(0x80));
+    let map = MemoryMap::builder()
+        .ram(0x0000..=0x1FFF)
+        .mirrored_device(
+            0x2000..=0x3FFF,
+            8,
+            Box::new(LatchDevice {
+                latch: latch.clone(),
+            }),
+        )
+        .ram(0x4000..=0xFFFF)
+        .build()
+        .unwrap();
+    let mut cpu = CPU::new();
+    cpu.connect_bus(Box::new(map));
+    cpu.reset();
+    (cpu, latch)
+}
+
+#[test]
+fn peek_does_not_clear_the_latch() {
+    let (mut cpu, latch) = setup_cpu_latch();
+    assert_eq!(cpu.peek(STATUS), 0x80);
+    assert_eq!(cpu.peek(STATUS + 8), 0x80);
+    assert_eq!(latch.get(), 0x80);
+}
+
+#[test]
+fn cpu_read_clears_the_latch() {
+    let (mut cpu, latch) = setup_cpu_latch();
+    assert_eq!(cpu.read_byte(STATUS), 0x80);
+    assert_eq!(latch.get(), 0x00);
+    assert_eq!(cpu.peek(STATUS), 0x00);
+}
+
+#[test]
+fn executed_load_clears_the_latch() {
+    let (mut cpu, latch) = setup_cpu_latch();
+    cpu.write(0xFFFC, Opcode::LdaABS.into());
+    cpu.write(0xFFFD, 0x02);
+    cpu.write(0xFFFE, 0x20);
+    cpu.execute();
+    assert_eq!(cpu.a, 0x80);
+    assert_eq!(latch.get(), 0x00);
+}
+
+#[test]
+fn disassemble_does_not_clear_the_latch() {
+    let (mut cpu, latch) = setup_cpu_latch();
+    let lines = cpu.disassemble(0x1FF0, 0x2010);
+    assert!(!lines.is_empty());
+    assert_eq!(latch.get(), 0x80);
+}
+
+#[test]
+fn default_peek_forwards_read_only() {
+    let latch = Rc::new(Cell::new(0x80));
+    let mut device = LatchDevice {
+        latch: latch.clone(),
+    };
+    assert_eq!(device.peek(STATUS), 0x80);
+    assert_eq!(latch.get(), 0x80);
+}
@@ -0,0 +1,277 @@
+use std::{cell::RefCell, rc::Rc};
+
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{
+        Bus, Byte, Word,
+        clock::{Clocked, Lines},
+    },
+    cartridge::mapper::SharedMapper,
+    cpu::{CPU, Profile, instructions::opcode::Opcode},
+    nes::bus::NesBus,
+    ppu::{DOTS_PER_SCANLINE, PRE_RENDER_SCANLINE, Ppu, SCANLINES, Status, VBLANK_SCANLINE},
+};
+
+/// Runs dots until the next one to run is `dot` of `scanline`
+fn run_to(ppu: &mut Ppu, scanline: usize, dot: usize) {
+    while (ppu.scanline(), ppu.dot()) != (scanline, dot) {
+        ppu.clock_dot();
+    }
+}
+
+fn tick(ppu: &mut Ppu) -> bool {
+    let mut lines = Lines::default();
+    ppu.tick(&mut lines);
+    lines.nmi
+}
+
+fn set_address(ppu: &mut Ppu, addr: Word) {
+    ppu.write(0x2006, (addr >> 8) as Byte);
+    ppu.write(0x2006, addr as Byte);
+}
+
+/// NROM with 8 KiB of CHR-RAM and the given flags 6
+fn nrom(flags6: Byte) -> SharedMapper {
+    load_board(
+        &Ines::new(0, 1, 0)
+            .flags6(flags6)
+            .prg(&[0; 16 * 1024])
+            .build(),
+    )
+}
+
+#[test]
+fn vblank_flag_timing() {
+    let mut ppu = Ppu::new();
+    run_to(&mut ppu, VBLANK_SCANLINE, 1);
+    assert!(!ppu.status.contains(Status::VBLANK));
+    ppu.clock_dot();
+    assert!(ppu.status.contains(Status::VBLANK));
+    run_to(&mut ppu, PRE_RENDER_SCANLINE, 1);
+    assert!(ppu.status.contains(Status::VBLANK));
+    ppu.clock_dot();
+    assert!(!ppu.status.contains(Status::VBLANK));
+}
+
+#[test]
+fn status_read_clears_vblank_and_toggle() {
+    let mut ppu = Ppu::new();
+    run_to(&mut ppu, VBLANK_SCANLINE + 1, 0);
+    ppu.write(0x2005, 0x10);
+    assert!(ppu.w);
+    assert_eq!(ppu.peek(0x2002) & 0x80, 0x80);
+    assert!(ppu.w);
+    assert_eq!(ppu.read(0x2002, false) & 0x80, 0x80);
+    assert!(!ppu.w);
+    assert_eq!(ppu.read(0x2002, false) & 0x80, 0x00);
+}
+
+#[test]
+fn status_low_bits_are_the_io_latch() {
+    let mut ppu = Ppu::new();
+    ppu.write(0x2003, 0x1F);
+    assert_eq!(ppu.read(0x2002, false), 0x1F);
+    assert_eq!(ppu.read(0x2000, false), 0x1F);
+}
+
+#[test]
+fn frame_length() {
+    let mut ppu = Ppu::new();
+    let mut dots = 0;
+    while ppu.frame_count() == 0 {
+        ppu.clock_dot();
+        dots += 1;
+    }
+    assert_eq!(dots, SCANLINES * DOTS_PER_SCANLINE);
+
+    // Frame 1 is odd, rendering skips its last pre-render dot
+    ppu.write(0x2001, 0x08);
+    dots = 0;
+    while ppu.frame_count() == 1 {
+        ppu.clock_dot();
+        dots += 1;
+    }
+    assert_eq!(dots, SCANLINES * DOTS_PER_SCANLINE - 1);
+
+    dots = 0;
+    while ppu.frame_count() == 2 {
+        ppu.clock_dot();
+        dots += 1;
+    }
+    assert_eq!(dots, SCANLINES * DOTS_PER_SCANLINE);
+}
+
+#[test]
+fn nmi_follows_vblank_and_enable() {
+    let mut ppu = Ppu::new();
+    run_to(&mut ppu, VBLANK_SCANLINE, 3);
+    assert!(!tick(&mut ppu));
+    ppu.write(0x2000, 0x80);
+    assert!(tick(&mut ppu));
+    ppu.write(0x2000, 0x00);
+    assert!(!tick(&mut ppu));
+    ppu.write(0x2000, 0x80);
+    assert!(tick(&mut ppu));
+    ppu.read(0x2002, false);
+    assert!(!tick(&mut ppu));
+}
+
+#[test]
+fn status_read_racing_vblank() {
+    // One dot early: the flag is never set
+    let mut ppu = Ppu::new();
+    ppu.write(0x2000, 0x80);
+    run_to(&mut ppu, VBLANK_SCANLINE, 1);
+    assert_eq!(ppu.read(0x2002, false) & 0x80, 0x00);
+    assert!(!tick(&mut ppu));
+    assert!(!ppu.status.contains(Status::VBLANK));
+    assert!(!tick(&mut ppu));
+
+    // Right after it was set: the flag reads set, no NMI
+    let mut ppu = Ppu::new();
+    ppu.write(0x2000, 0x80);
+    run_to(&mut ppu, VBLANK_SCANLINE, 2);
+    assert_eq!(ppu.read(0x2002, false) & 0x80, 0x80);
+    assert!(!tick(&mut ppu));
+    assert!(!tick(&mut ppu));
+
+    // Without a read the NMI comes on the next cycle
+    let mut ppu = Ppu::new();
+    ppu.write(0x2000, 0x80);
+    run_to(&mut ppu, VBLANK_SCANLINE, 2);
+    assert!(tick(&mut ppu));
+}
+
+#[test]
+fn scroll_and_address_share_the_toggle() {
+    let mut ppu = Ppu::new();
+    ppu.write(0x2000, 0x03);
+    assert_eq!(ppu.t, 0x0C00);
+    ppu.write(0x2005, 0x7D);
+    assert_eq!((ppu.t, ppu.x), (0x0C0F, 0x05));
+    ppu.write(0x2005, 0x5E);
+    assert_eq!(ppu.t, 0x6D6F);
+    ppu.write(0x2006, 0x3D);
+    assert_eq!(ppu.t, 0x3D6F);
+    ppu.write(0x2006, 0xF0);
+    assert_eq!((ppu.t, ppu.v), (0x3DF0, 0x3DF0));
+    assert!(!ppu.w);
+}
+
+#[test]
+fn data_reads_go_through_the_buffer() {
+    let mut ppu = Ppu::new();
+    set_address(&mut ppu, 0x2400);
+    for value in [0x11, 0x22, 0x33] {
+        ppu.write(0x2007, value);
+    }
+    set_address(&mut ppu, 0x2400);
+    assert_eq!(ppu.read(0x2007, false), 0x00);
+    assert_eq!(ppu.peek(0x2007), 0x11);
+    assert_eq!(ppu.read(0x2007, false), 0x11);
+    assert_eq!(ppu.read(0x2007, false), 0x22);
+    assert_eq!(ppu.v, 0x2403);
+
+    ppu.write(0x2000, 0x04);
+    set_address(&mut ppu, 0x2400);
+    ppu.read(0x2007, false);
+    assert_eq!(ppu.v, 0x2420);
+}
+
+#[test]
+fn palette_reads_are_immediate_and_mirrored() {
+    let mut ppu = Ppu::new();
+    set_address(&mut ppu, 0x2F00);
+    ppu.write(0x2007, 0x5A);
+    set_address(&mut ppu, 0x3F10);
+    ppu.write(0x2007, 0xEC);
+    set_address(&mut ppu, 0x3F00);
+    // The buffer picks up the nametable byte under the palette
+    assert_eq!(ppu.read(0x2007, false) & 0x3F, 0x2C);
+    assert_eq!(ppu.read_memory(0x3F30), 0x2C);
+    set_address(&mut ppu, 0x2000);
+    assert_eq!(ppu.read(0x2007, false), 0x5A);
+}
+
+#[test]
+fn oam_access() {
+    let mut ppu = Ppu::new();
+    ppu.write(0x2003, 0xFC);
+    ppu.write(0x2004, 0x12);
+    ppu.write(0x2004, 0xFD);
+    assert_eq!(ppu.oam_addr, 0xFE);
+    assert_eq!(ppu.oam[0xFC], 0x12);
+    ppu.write(0x2003, 0xFC);
+    assert_eq!(ppu.read(0x2004, false), 0x12);
+    ppu.write(0x2003, 0xFD);
+    assert_eq!(ppu.read(0x2004, false), 0xFD);
+    // Attribute bytes have no bits 2-4
+    ppu.write(0x2003, 0x02);
+    ppu.write(0x2004, 0xFF);
+    ppu.write(0x2003, 0x02);
+    assert_eq!(ppu.read(0x2004, false), 0xE3);
+}
+
+#[test]
+fn memory_comes_from_the_cartridge() {
+    let mut ppu = Ppu::new();
+    ppu.connect_cartridge(nrom(0x00));
+    set_address(&mut ppu, 0x1234);
+    ppu.write(0x2007, 0x77);
+    assert_eq!(ppu.read_memory(0x1234), 0x77);
+    // Horizontal mirroring
+    ppu.write_memory(0x2005, 0x42);
+    assert_eq!(ppu.read_memory(0x2405), 0x42);
+    assert_eq!(ppu.read_memory(0x2805), 0x00);
+    assert_eq!(ppu.read_memory(0x3405), 0x42);
+
+    let mut ppu = Ppu::new();
+    ppu.connect_cartridge(nrom(0x01));
+    ppu.write_memory(0x2005, 0x42);
+    assert_eq!(ppu.read_memory(0x2805), 0x42);
+    assert_eq!(ppu.read_memory(0x2405), 0x00);
+}
+
+#[test]
+fn drives_the_cpu_nmi() {
+    struct Rom(Vec<Byte>);
+    impl Bus for Rom {
+        fn read(&mut self, addr: Word, _: bool) -> Byte {
+            self.0[(addr as usize - 0x8000) % self.0.len()]
+        }
+        fn write(&mut self, _: Word, _: Byte) {}
+    }
+    let mut rom = vec![0xEA; 0x8000];
+    let program: [Byte; _] = [
+        Opcode::LdaIMM.into(),
+        0x80,
+        Opcode::StaABS.into(),
+        0x00,
+        0x20,
+        Opcode::JmpABS.into(),
+        0x05,
+        0x80,
+        // NMI handler
+        Opcode::IncZPG.into(),
+        0x10,
+        Opcode::RtiIMP.into(),
+    ];
+    rom[..program.len()].copy_from_slice(&program);
+    rom[0x7FFA..].copy_from_slice(&[0x08, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    let mut bus = NesBus::new();
+    bus.connect_ppu(Box::new(ppu.clone()));
+    bus.connect_cartridge(Box::new(Rom(rom)));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.attach_device(Box::new(ppu.clone()));
+    cpu.reset();
+    cpu.pc = 0x8000;
+    while ppu.borrow().frame_count() < 3 {
+        cpu.execute();
+    }
+    assert_eq!(cpu.peek(0x0010), 3);
+}
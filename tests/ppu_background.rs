@@ -0,0 +1,194 @@
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{Bus, Byte, Word},
+    cartridge::mapper::SharedMapper,
+    ppu::{HEIGHT, PRE_RENDER_SCANLINE, Ppu, VISIBLE_SCANLINES, WIDTH},
+};
+
+/// Tile 1 is solid color 1, tile 2 has only its left column set,
+/// tile 3 only its top row, tile 4 is solid color 3
+fn chr() -> Vec<Byte> {
+    let mut chr = vec![0; 8 * 1024];
+    let mut tile = |index: usize, planes: [[Byte; 8]; 2]| {
+        chr[index * 16..index * 16 + 8].copy_from_slice(&planes[0]);
+        chr[index * 16 + 8..index * 16 + 16].copy_from_slice(&planes[1]);
+    };
+    tile(1, [[0xFF; 8], [0x00; 8]]);
+    tile(2, [[0x80; 8], [0x00; 8]]);
+    tile(3, [[0xFF, 0, 0, 0, 0, 0, 0, 0], [0x00; 8]]);
+    tile(4, [[0xFF; 8], [0xFF; 8]]);
+    chr
+}
+
+/// 32 KiB PRG board holding the tiles above
+fn board(flags6: Byte) -> SharedMapper {
+    load_board(
+        &Ines::new(0, 2, 1)
+            .flags6(flags6)
+            .prg(&[0; 32 * 1024])
+            .chr(&chr())
+            .build(),
+    )
+}
+
+/// NROM PPU with palette 0 set to `$0F $01 $02 $03` and palette 1 to `$0F $11 $12 $13`
+fn ppu(flags6: Byte) -> Ppu {
+    ppu_on(board(flags6))
+}
+
+fn ppu_on(board: SharedMapper) -> Ppu {
+    let mut ppu = Ppu::new();
+    ppu.connect_cartridge(board);
+    set_address(&mut ppu, 0x3F00);
+    for color in [0x0F, 0x01, 0x02, 0x03, 0x0F, 0x11, 0x12, 0x13] {
+        ppu.write(0x2007, color);
+    }
+    ppu
+}
+
+fn set_address(ppu: &mut Ppu, addr: Word) {
+    ppu.write(0x2006, (addr >> 8) as Byte);
+    ppu.write(0x2006, addr as Byte);
+}
+
+fn fill_nametable(ppu: &mut Ppu, base: Word, tile: Byte) {
+    set_address(ppu, base);
+    for _ in 0..0x3C0 {
+        ppu.write(0x2007, tile);
+    }
+}
+
+fn scroll(ppu: &mut Ppu, x: Byte, y: Byte) {
+    ppu.write(0x2005, x);
+    ppu.write(0x2005, y);
+}
+
+/// Finishes the current frame and renders the next one
+fn render(ppu: &mut Ppu) -> Vec<Byte> {
+    while ppu.scanline() != PRE_RENDER_SCANLINE {
+        ppu.clock_dot();
+    }
+    while ppu.scanline() != VISIBLE_SCANLINES {
+        ppu.clock_dot();
+    }
+    ppu.frame().to_vec()
+}
+
+fn row(frame: &[Byte], y: usize) -> &[Byte] {
+    &frame[y * WIDTH..(y + 1) * WIDTH]
+}
+
+#[test]
+fn renders_a_solid_screen() {
+    let mut ppu = ppu(0x00);
+    fill_nametable(&mut ppu, 0x2000, 1);
+    scroll(&mut ppu, 0, 0);
+    ppu.write(0x2001, 0x0A);
+    let frame = render(&mut ppu);
+    assert_eq!(frame.len(), WIDTH * HEIGHT);
+    assert!(frame.iter().all(|&color| color == 0x01));
+}
+
+#[test]
+fn attributes_pick_the_palette() {
+    let mut ppu = ppu(0x00);
+    fill_nametable(&mut ppu, 0x2000, 4);
+    // Top right 16x16 quadrant of the first attribute byte uses palette 1
+    set_address(&mut ppu, 0x23C0);
+    ppu.write(0x2007, 0b0000_0100);
+    scroll(&mut ppu, 0, 0);
+    ppu.write(0x2001, 0x0A);
+    let frame = render(&mut ppu);
+    assert_eq!(&row(&frame, 0)[..16], [0x03; 16]);
+    assert_eq!(&row(&frame, 0)[16..32], [0x13; 16]);
+    assert_eq!(&row(&frame, 15)[16..32], [0x13; 16]);
+    assert_eq!(&row(&frame, 16)[16..32], [0x03; 16]);
+}
+
+#[test]
+fn fine_x_scroll_shifts_the_picture() {
+    let mut ppu = ppu(0x00);
+    fill_nametable(&mut ppu, 0x2000, 2);
+    scroll(&mut ppu, 3, 0);
+    ppu.write(0x2001, 0x0A);
+    let frame = render(&mut ppu);
+    let lit: Vec<usize> = (0..32).filter(|&x| row(&frame, 100)[x] != 0x0F).collect();
+    assert_eq!(lit, [5, 13, 21, 29]);
+}
+
+#[test]
+fn vertical_scroll_wraps_within_the_nametable() {
+    let mut ppu = ppu(0x00);
+    fill_nametable(&mut ppu, 0x2000, 3);
+    scroll(&mut ppu, 0, 2);
+    ppu.write(0x2001, 0x0A);
+    let frame = render(&mut ppu);
+    let lit: Vec<usize> = (0..HEIGHT).filter(|&y| row(&frame, y)[0] != 0x0F).collect();
+    // Past row 29 the picture continues with the nametable below, blank here
+    let expected: Vec<usize> = (6..HEIGHT - 2).step_by(8).collect();
+    assert_eq!(lit, expected);
+}
+
+#[test]
+fn left_column_can_be_masked() {
+    let mut ppu = ppu(0x00);
+    fill_nametable(&mut ppu, 0x2000, 1);
+    scroll(&mut ppu, 0, 0);
+    ppu.write(0x2001, 0x08);
+    let frame = render(&mut ppu);
+    assert_eq!(&row(&frame, 50)[..8], [0x0F; 8]);
+    assert_eq!(&row(&frame, 50)[8..16], [0x01; 8]);
+}
+
+#[test]
+fn nametables_follow_the_cartridge_mirroring() {
+    for (flags6, expected) in [(0x00, 0x01), (0x01, 0x0F)] {
+        let mut ppu = ppu(flags6);
+        fill_nametable(&mut ppu, 0x2000, 1);
+        // Show the nametable at $2400
+        ppu.write(0x2000, 0x01);
+        scroll(&mut ppu, 0, 0);
+        ppu.write(0x2001, 0x0A);
+        let frame = render(&mut ppu);
+        assert_eq!(row(&frame, 120)[128], expected);
+    }
+}
+
+#[test]
+fn scrolling_crosses_into_the_next_nametable() {
+    let mut ppu = ppu(0x01);
+    fill_nametable(&mut ppu, 0x2400, 1);
+    scroll(&mut ppu, 128, 0);
+    ppu.write(0x2001, 0x0A);
+    let frame = render(&mut ppu);
+    assert_eq!(row(&frame, 10)[127], 0x01);
+    assert_eq!(row(&frame, 10)[128], 0x0F);
+}
+
+#[test]
+fn rendering_off_shows_the_backdrop() {
+    let mut ppu = ppu(0x00);
+    fill_nametable(&mut ppu, 0x2000, 1);
+    scroll(&mut ppu, 0, 0);
+    set_address(&mut ppu, 0x2000);
+    let frame = render(&mut ppu);
+    assert!(frame.iter().all(|&color| color == 0x0F));
+    // ...unless `v` points into the palette
+    set_address(&mut ppu, 0x3F02);
+    let frame = render(&mut ppu);
+    assert!(frame.iter().all(|&color| color == 0x02));
+}
+
+#[test]
+fn data_access_while_rendering_bumps_both_scroll_counters() {
+    let mut ppu = ppu(0x00);
+    ppu.write(0x2001, 0x08);
+    while ppu.scanline() != 10 {
+        ppu.clock_dot();
+    }
+    ppu.v = 0x0000;
+    ppu.read(0x2007, false);
+    assert_eq!(ppu.v, 0x1001);
+}
@@ -0,0 +1,369 @@
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{Bus, Byte, Word, clock::Lines},
+    cartridge::{
+        Cartridge,
+        mapper::{self, SharedMapper},
+    },
+    cpu::{CPU, Profile},
+    nes::bus::NesBus,
+    ppu::{PRE_RENDER_SCANLINE, Ppu, Status, VISIBLE_SCANLINES, WIDTH},
+};
+
+/// Tile 1 is solid color 1, tile 2 has only its left column set, tile 3 only its top row
+fn chr() -> Vec<Byte> {
+    let mut chr = vec![0; 8 * 1024];
+    let mut tile = |index: usize, planes: [[Byte; 8]; 2]| {
+        chr[index * 16..index * 16 + 8].copy_from_slice(&planes[0]);
+        chr[index * 16 + 8..index * 16 + 16].copy_from_slice(&planes[1]);
+    };
+    tile(1, [[0xFF; 8], [0x00; 8]]);
+    tile(2, [[0x80; 8], [0x00; 8]]);
+    tile(3, [[0xFF, 0, 0, 0, 0, 0, 0, 0], [0x00; 8]]);
+    chr
+}
+
+fn board(flags6: Byte) -> SharedMapper {
+    load_board(
+        &Ines::new(0, 2, 1)
+            .flags6(flags6)
+            .prg(&[0; 32 * 1024])
+            .chr(&chr())
+            .build(),
+    )
+}
+
+/// Background colors are `$01-$03`, sprite palette 0 `$21-$23` and palette 1 `$25-$27`.
+/// Every sprite starts off screen.
+fn ppu_on(board: SharedMapper) -> Ppu {
+    let mut ppu = Ppu::new();
+    ppu.connect_cartridge(board);
+    set_address(&mut ppu, 0x3F00);
+    for palette in 0..8 {
+        let base = if palette < 4 { 0x00 } else { 0x20 } + (palette % 4) * 4;
+        for color in [0x0F, base + 1, base + 2, base + 3] {
+            ppu.write(0x2007, color);
+        }
+    }
+    ppu.oam = [0xF0; 256];
+    ppu
+}
+
+fn nrom_ppu() -> Ppu {
+    ppu_on(board(0x00))
+}
+
+fn set_address(ppu: &mut Ppu, addr: Word) {
+    ppu.write(0x2006, (addr >> 8) as Byte);
+    ppu.write(0x2006, addr as Byte);
+}
+
+fn fill_background(ppu: &mut Ppu, tile: Byte) {
+    set_address(ppu, 0x2000);
+    for _ in 0..0x3C0 {
+        ppu.write(0x2007, tile);
+    }
+    ppu.write(0x2005, 0);
+    ppu.write(0x2005, 0);
+}
+
+fn sprite(ppu: &mut Ppu, n: usize, y: Byte, tile: Byte, attributes: Byte, x: Byte) {
+    ppu.oam[n * 4..n * 4 + 4].copy_from_slice(&[y, tile, attributes, x]);
+}
+
+fn run_to(ppu: &mut Ppu, scanline: usize, dot: usize) {
+    while (ppu.scanline(), ppu.dot()) != (scanline, dot) {
+        ppu.clock_dot();
+    }
+}
+
+/// Finishes the current frame and renders the next one
+fn render(ppu: &mut Ppu) -> Vec<Byte> {
+    run_to(ppu, PRE_RENDER_SCANLINE, 0);
+    run_to(ppu, VISIBLE_SCANLINES, 0);
+    ppu.frame().to_vec()
+}
+
+fn pixel(frame: &[Byte], x: usize, y: usize) -> Byte {
+    frame[y * WIDTH + x]
+}
+
+/// Rows of column `x` and columns of row `y` that aren't the backdrop
+fn lit_rows(frame: &[Byte], x: usize) -> Vec<usize> {
+    (0..VISIBLE_SCANLINES)
+        .filter(|&y| pixel(frame, x, y) != 0x0F)
+        .collect()
+}
+
+fn lit_columns(frame: &[Byte], y: usize) -> Vec<usize> {
+    (0..WIDTH).filter(|&x| pixel(frame, x, y) != 0x0F).collect()
+}
+
+#[test]
+fn sprites_are_drawn_one_line_below_their_y() {
+    let mut ppu = nrom_ppu();
+    sprite(&mut ppu, 5, 20, 1, 0x01, 40);
+    ppu.write(0x2001, 0x14);
+    let frame = render(&mut ppu);
+    assert_eq!(lit_rows(&frame, 40), (21..29).collect::<Vec<_>>());
+    assert_eq!(lit_columns(&frame, 21), (40..48).collect::<Vec<_>>());
+    assert_eq!(pixel(&frame, 40, 21), 0x25);
+}
+
+#[test]
+fn sprites_can_be_flipped() {
+    let mut ppu = nrom_ppu();
+    sprite(&mut ppu, 0, 20, 2, 0x40, 40);
+    sprite(&mut ppu, 1, 100, 3, 0x80, 40);
+    ppu.write(0x2001, 0x14);
+    let frame = render(&mut ppu);
+    assert_eq!(lit_columns(&frame, 25), [47]);
+    assert_eq!(lit_rows(&frame, 42), [108]);
+}
+
+#[test]
+fn tall_sprites_take_two_tiles() {
+    let mut ppu = nrom_ppu();
+    ppu.write(0x2000, 0x20);
+    sprite(&mut ppu, 0, 20, 2, 0x00, 40);
+    sprite(&mut ppu, 1, 100, 2, 0x80, 40);
+    ppu.write(0x2001, 0x14);
+    let frame = render(&mut ppu);
+    // Tile 2 on top, tile 3 below
+    assert_eq!(
+        lit_rows(&frame, 40)[..9],
+        [21, 22, 23, 24, 25, 26, 27, 28, 29]
+    );
+    assert_eq!(lit_columns(&frame, 29), (40..48).collect::<Vec<_>>());
+    // Flipped vertically tile 3 comes first, upside down
+    assert_eq!(lit_columns(&frame, 108), (40..48).collect::<Vec<_>>());
+    assert_eq!(lit_rows(&frame, 41), [29, 108]);
+    assert_eq!(lit_rows(&frame, 40)[9..], (108..117).collect::<Vec<_>>());
+}
+
+#[test]
+fn priority_against_the_background() {
+    let mut ppu = nrom_ppu();
+    fill_background(&mut ppu, 1);
+    sprite(&mut ppu, 0, 20, 1, 0x00, 40);
+    sprite(&mut ppu, 1, 50, 1, 0x20, 40);
+    // A sprite behind the background still hides the sprites after it
+    sprite(&mut ppu, 2, 80, 1, 0x20, 40);
+    sprite(&mut ppu, 3, 80, 1, 0x01, 44);
+    ppu.write(0x2001, 0x1E);
+    let frame = render(&mut ppu);
+    assert_eq!(pixel(&frame, 40, 21), 0x21);
+    assert_eq!(pixel(&frame, 40, 51), 0x01);
+    assert_eq!(pixel(&frame, 44, 81), 0x01);
+    assert_eq!(pixel(&frame, 48, 81), 0x25);
+}
+
+#[test]
+fn left_column_hides_sprites() {
+    let mut ppu = nrom_ppu();
+    sprite(&mut ppu, 0, 20, 1, 0x00, 4);
+    ppu.write(0x2001, 0x10);
+    let frame = render(&mut ppu);
+    assert_eq!(lit_columns(&frame, 21), [8, 9, 10, 11]);
+}
+
+#[test]
+fn sprite_0_hit_is_set_on_the_first_overlapping_pixel() {
+    let mut ppu = nrom_ppu();
+    fill_background(&mut ppu, 1);
+    sprite(&mut ppu, 0, 50, 1, 0x00, 40);
+    ppu.write(0x2001, 0x1E);
+    render(&mut ppu);
+    run_to(&mut ppu, PRE_RENDER_SCANLINE, 2);
+    run_to(&mut ppu, 51, 41);
+    assert!(!ppu.status.contains(Status::SPRITE_0_HIT));
+    ppu.clock_dot();
+    assert!(ppu.status.contains(Status::SPRITE_0_HIT));
+    run_to(&mut ppu, PRE_RENDER_SCANLINE, 2);
+    assert!(!ppu.status.contains(Status::SPRITE_0_HIT));
+}
+
+#[test]
+fn sprite_0_hit_edge_cases() {
+    let hit = |setup: fn(&mut Ppu), mask: Byte| {
+        let mut ppu = nrom_ppu();
+        setup(&mut ppu);
+        ppu.write(0x2001, mask);
+        render(&mut ppu);
+        ppu.status.contains(Status::SPRITE_0_HIT)
+    };
+    assert!(hit(
+        |ppu| {
+            fill_background(ppu, 1);
+            sprite(ppu, 0, 50, 1, 0x00, 254);
+        },
+        0x1E
+    ));
+    // Column 255 never hits
+    assert!(!hit(
+        |ppu| {
+            fill_background(ppu, 1);
+            sprite(ppu, 0, 50, 2, 0x40, 248);
+        },
+        0x1E
+    ));
+    // Nor does the clipped left column
+    let left = |ppu: &mut Ppu| {
+        fill_background(ppu, 1);
+        sprite(ppu, 0, 50, 2, 0x00, 0);
+    };
+    assert!(hit(left, 0x1E));
+    assert!(!hit(left, 0x1A));
+    assert!(!hit(left, 0x1C));
+    // A transparent background, or an opaque one behind the sprite
+    assert!(!hit(|ppu| sprite(ppu, 0, 50, 1, 0x00, 40), 0x1E));
+    assert!(hit(
+        |ppu| {
+            fill_background(ppu, 1);
+            sprite(ppu, 0, 50, 1, 0x20, 40);
+        },
+        0x1E
+    ));
+    // Background rendering off
+    assert!(!hit(
+        |ppu| {
+            fill_background(ppu, 1);
+            sprite(ppu, 0, 50, 1, 0x00, 40);
+        },
+        0x16
+    ));
+}
+
+#[test]
+fn eight_sprites_per_scanline() {
+    let mut ppu = nrom_ppu();
+    for n in 0..9 {
+        sprite(&mut ppu, n, 100, 1, 0x00, n as Byte * 16);
+    }
+    ppu.write(0x2001, 0x14);
+    let frame = render(&mut ppu);
+    assert_eq!(lit_columns(&frame, 101).len(), 64);
+    assert!(ppu.status.contains(Status::SPRITE_OVERFLOW));
+
+    ppu.unlimited_sprites = true;
+    let frame = render(&mut ppu);
+    assert_eq!(lit_columns(&frame, 101).len(), 72);
+    assert!(ppu.status.contains(Status::SPRITE_OVERFLOW));
+}
+
+#[test]
+fn overflow_check_reads_the_wrong_bytes() {
+    // A false positive: sprite 9's tile number looks like a Y in range
+    let mut ppu = nrom_ppu();
+    for n in 0..8 {
+        sprite(&mut ppu, n, 100, 1, 0x00, 0);
+    }
+    sprite(&mut ppu, 9, 0xF0, 100, 0x00, 0);
+    ppu.write(0x2001, 0x14);
+    render(&mut ppu);
+    assert!(ppu.status.contains(Status::SPRITE_OVERFLOW));
+
+    // A false negative: sprite 9 is in range but its tile number is checked
+    let mut ppu = nrom_ppu();
+    for n in 0..8 {
+        sprite(&mut ppu, n, 100, 1, 0x00, 0);
+    }
+    sprite(&mut ppu, 9, 100, 0xF0, 0xF0, 0xF0);
+    ppu.write(0x2001, 0x14);
+    render(&mut ppu);
+    assert!(!ppu.status.contains(Status::SPRITE_OVERFLOW));
+}
+
+#[test]
+fn oam_access_during_rendering() {
+    let mut ppu = nrom_ppu();
+    ppu.write(0x2001, 0x10);
+    run_to(&mut ppu, 10, 30);
+    assert_eq!(ppu.read(0x2004, false), 0xFF);
+    ppu.write(0x2003, 0x10);
+    run_to(&mut ppu, 10, 300);
+    assert_eq!(ppu.oam_addr, 0);
+}
+
+#[test]
+fn sprite_fetches_clock_the_mmc3() {
+    let board = board(0x40);
+    let mut ppu = ppu_on(board.clone());
+    {
+        let mut board = board.borrow_mut();
+        board.write(0xC000, 9);
+        board.write(0xC001, 0);
+        board.write(0xE001, 0);
+    }
+    // Background from $0000, sprites from $1000
+    ppu.write(0x2000, 0x08);
+    ppu.write(0x2001, 0x18);
+    run_to(&mut ppu, PRE_RENDER_SCANLINE, 0);
+    let fired_on = loop {
+        for _ in 0..3 {
+            ppu.clock_dot();
+        }
+        let mut lines = Lines::default();
+        board.borrow_mut().clock(&mut lines);
+        if lines.irq {
+            break (ppu.scanline(), ppu.dot());
+        }
+    };
+    // The first sprite pattern fetch of line 8
+    assert_eq!(fired_on.0, 8);
+    assert!((261..266).contains(&fired_on.1));
+}
+
+/// Runs a blargg test ROM until it stores its result code at `$F8`, 1 meaning passed
+fn run_blargg(path: &Path) -> Byte {
+    let board = mapper::from_cartridge(Cartridge::load(path).unwrap()).unwrap();
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    ppu.borrow_mut().connect_cartridge(board.clone());
+    let mut bus = NesBus::new();
+    bus.connect_ppu(Box::new(ppu.clone()));
+    bus.connect_cartridge(Box::new(board.clone()));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.attach_device(Box::new(ppu.clone()));
+    cpu.attach_device(Box::new(board));
+    cpu.reset();
+    cpu.pc = Word::from_le_bytes([cpu.peek(0xFFFC), cpu.peek(0xFFFD)]);
+    while ppu.borrow().frame_count() < 600 && cpu.peek(0x00F8) == 0 {
+        cpu.execute();
+    }
+    cpu.peek(0x00F8)
+}
+
+/// Runs every ROM in `dir`
+fn run_blargg_dir(dir: &str) {
+    common::require_test_files(&[dir]);
+    let mut roms: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "nes"))
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty(), "no ROMs in {dir}");
+    let failed: Vec<_> = roms
+        .iter()
+        .map(|rom| (rom.file_name().unwrap().to_owned(), run_blargg(rom)))
+        .filter(|&(_, result)| result != 1)
+        .collect();
+    assert!(failed.is_empty(), "failed: {failed:?}");
+}
+
+#[test]
+#[ignore = "needs blargg's sprite_hit_tests ROMs in tests/roms/sprite_hit_tests"]
+fn blargg_sprite_hit_tests() {
+    run_blargg_dir("tests/roms/sprite_hit_tests");
+}
+
+#[test]
+#[ignore = "needs blargg's sprite_overflow_tests ROMs in tests/roms/sprite_overflow_tests"]
+fn blargg_sprite_overflow_tests() {
+    run_blargg_dir("tests/roms/sprite_overflow_tests");
+}
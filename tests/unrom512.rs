@@ -0,0 +1,151 @@
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{Bus, Byte, Word},
+    cartridge::{
+        Cartridge, Mirroring,
+        mapper::{Mapper, unrom512::Unrom512},
+    },
+};
+
+/// UNROM-512 image with 32 PRG banks holding their bank number
+fn build_rom(flags6: Byte) -> Vec<Byte> {
+    Ines::new(30, 32, 0)
+        .flags6(flags6)
+        .prg_banks(16 * 1024, |bank, _| bank as Byte)
+        .build()
+}
+
+fn flashable() -> Unrom512 {
+    Unrom512::new(Cartridge::from_bytes(&build_rom(0x0A)).unwrap())
+}
+
+/// Sends a flash command the way games do, through `$9555` in bank 1 and `$AAAA` in bank 0
+fn command(board: &mut dyn Mapper, command: Byte) {
+    board.write(0xC000, 1);
+    board.write(0x9555, 0xAA);
+    board.write(0xC000, 0);
+    board.write(0xAAAA, 0x55);
+    board.write(0xC000, 1);
+    board.write(0x9555, command);
+}
+
+fn program(board: &mut dyn Mapper, bank: Byte, addr: Word, value: Byte) {
+    command(board, 0xA0);
+    board.write(0xC000, bank);
+    board.write(addr, value);
+}
+
+#[test]
+fn banks_prg_and_chr() {
+    let board = load_board(&build_rom(0x01));
+    let mut board = board.borrow_mut();
+    assert_eq!(board.read(0xC000, false), 31);
+    board.ppu_write(0x0000, 0x11);
+    // ROM drives 0x1F at $FFFF, the CHR bits are lost to the bus conflict
+    board.write(0xFFFF, 0x25);
+    assert_eq!(board.read(0x8000, false), 5);
+    assert_eq!(board.ppu_read(0x0000), 0x11);
+    assert_eq!(board.mirroring(), Mirroring::Vertical);
+
+    let mut board = flashable();
+    board.ppu_write(0x0000, 0x11);
+    board.write(0xFFFF, 0x25);
+    assert_eq!(board.read(0x8000, false), 5);
+    assert_eq!(board.ppu_read(0x0000), 0x00);
+    board.write(0xC000, 0x05);
+    assert_eq!(board.ppu_read(0x0000), 0x11);
+}
+
+#[test]
+fn one_screen_select() {
+    let mut board = flashable();
+    assert_eq!(board.mirroring(), Mirroring::SingleScreenLower);
+    board.write(0xC000, 0x80);
+    assert_eq!(board.mirroring(), Mirroring::SingleScreenUpper);
+
+    let board = Unrom512::new(Cartridge::from_bytes(&build_rom(0x0B)).unwrap());
+    assert_eq!(board.mirroring(), Mirroring::FourScreen);
+}
+
+#[test]
+fn programs_bytes() {
+    let mut board = flashable();
+    program(&mut board, 3, 0x8010, 0x3C);
+    board.write(0xC000, 3);
+    // 0x03 & 0x3C, programming only clears bits
+    assert_eq!(board.read(0x8010, false), 0x00);
+    assert_eq!(board.read(0x8011, false), 3);
+    assert!(board.is_dirty());
+
+    // without the unlock sequence writes are ignored
+    board.write(0x8020, 0x00);
+    assert_eq!(board.read(0x8020, false), 3);
+}
+
+#[test]
+fn erases_sectors_and_chip() {
+    let mut board = flashable();
+    command(&mut board, 0x80);
+    board.write(0x9555, 0xAA);
+    board.write(0xC000, 0);
+    board.write(0xAAAA, 0x55);
+    board.write(0xC000, 2);
+    board.write(0x9000, 0x30);
+    assert_eq!(board.read(0x9000, false), 0xFF);
+    assert_eq!(board.read(0x9FFF, false), 0xFF);
+    assert_eq!(board.read(0x8FFF, false), 2);
+    assert_eq!(board.read(0xA000, false), 2);
+
+    command(&mut board, 0x80);
+    board.write(0x9555, 0xAA);
+    board.write(0xC000, 0);
+    board.write(0xAAAA, 0x55);
+    board.write(0xC000, 1);
+    board.write(0x9555, 0x10);
+    assert_eq!(board.read(0xC000, false), 0xFF);
+}
+
+#[test]
+fn software_id_mode() {
+    let mut board = flashable();
+    command(&mut board, 0x90);
+    assert_eq!(board.read(0x8000, false), 0xBF);
+    assert_eq!(board.read(0x8001, false), 0xB7);
+    board.write(0x8000, 0xF0);
+    assert_eq!(board.read(0x8000, false), 1);
+}
+
+#[test]
+fn sidecar_round_trip() {
+    let path = std::env::temp_dir().join(format!("unrom512-{}.flash", std::process::id()));
+    let mut board = flashable();
+    board.load_sidecar(&path).unwrap();
+    program(&mut board, 7, 0xBFFF, 0x42);
+    board.save_sidecar(&path).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 2 + 4096);
+
+    let mut fresh = flashable();
+    fresh.load_sidecar(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    fresh.write(0xC000, 7);
+    assert_eq!(fresh.read(0xBFFF, false), 0x02);
+    assert_eq!(fresh.read(0xBFFE, false), 7);
+}
+
+#[test]
+fn sidecar_through_the_mapper_trait() {
+    let path = std::env::temp_dir().join(format!("unrom512-dyn-{}.flash", std::process::id()));
+    let board = load_board(&build_rom(0x0A));
+    let mut board = board.borrow_mut();
+    program(&mut *board, 3, 0x8000, 0x42);
+    board.save_sidecar(&path).unwrap();
+
+    let fresh = load_board(&build_rom(0x0A));
+    let mut fresh = fresh.borrow_mut();
+    fresh.load_sidecar(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    fresh.write(0xC000, 3);
+    assert_eq!(fresh.read(0x8000, false), 0x02);
+}
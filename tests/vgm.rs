@@ -0,0 +1,263 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cpu_6502::{
+    apu::Apu,
+    audio::{
+        NTSC_CPU_CLOCK,
+        vgm::{ApuWrite, VGM_SAMPLE_RATE, VgmLogger},
+    },
+    bus::{Bus, Byte, Word},
+    cpu::{Access, CPU, Profile, instructions::opcode::Opcode, observer::BusEvent},
+    nes::bus::NesBus,
+};
+
+/// 32 KiB at `$8000`
+struct Rom(Vec<Byte>);
+
+impl Bus for Rom {
+    fn read(&mut self, addr: Word, _read_only: bool) -> Byte {
+        match addr {
+            0x8000..=0xFFFF => self.0[addr as usize - 0x8000],
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, _addr: Word, _value: Byte) {}
+}
+
+/// Sets up the DMC on a 17 byte sample at `$C000` and a pulse, does an OAM DMA
+/// and reads a controller, then spins
+const PROGRAM: [Byte; 33] = [
+    0xA9, 0x0F, 0x8D, 0x10, 0x40, // LDA #$0F, STA $4010
+    0xA9, 0x00, 0x8D, 0x12, 0x40, // LDA #$00, STA $4012
+    0xA9, 0x01, 0x8D, 0x13, 0x40, // LDA #$01, STA $4013
+    0xA9, 0xBF, 0x8D, 0x00, 0x40, // LDA #$BF, STA $4000
+    0xA9, 0x10, 0x8D, 0x15, 0x40, // LDA #$10, STA $4015
+    0xA9, 0x02, 0x8D, 0x14, 0x40, // LDA #$02, STA $4014
+    0x4C, 0x1E, 0x80, // JMP $801E
+];
+
+fn nes() -> CPU {
+    let mut rom = vec![0; 0x8000];
+    rom[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+    for i in 0..17 {
+        rom[0x4000 + i] = 0xA0 + i as Byte;
+    }
+    let apu = Rc::new(RefCell::new(Apu::new()));
+    let mut bus = NesBus::new();
+    bus.connect_apu(Box::new(apu.clone()));
+    bus.connect_cartridge(Box::new(Rom(rom)));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.attach_device(Box::new(apu));
+    cpu.reset();
+    cpu.pc = 0x8000;
+    cpu
+}
+
+fn run(cpu: &mut CPU, cycles: u64) {
+    let end = cpu.general_cycles + cycles;
+    while cpu.general_cycles < end {
+        cpu.execute();
+    }
+}
+
+fn write_at(logger: &mut VgmLogger, cycle: u64, addr: Word, value: Byte) {
+    logger.observe(&BusEvent {
+        addr,
+        value,
+        access: Access::Write,
+        cycle,
+    });
+}
+
+fn u32_at(bytes: &[Byte], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+/// Commands after the header, with waits folded into sample counts
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Block(Byte, Vec<Byte>),
+    Write(Byte, Byte),
+    Wait(u64),
+}
+
+fn commands(vgm: &[Byte]) -> Vec<Command> {
+    let mut at = 0x34 + u32_at(vgm, 0x34) as usize;
+    let mut commands = Vec::new();
+    loop {
+        let command = match vgm[at] {
+            0x66 => break,
+            0x67 => {
+                let size = u32_at(vgm, at + 3) as usize;
+                at += 7 + size;
+                Command::Block(vgm[at - size - 7 + 2], vgm[at - size..at].to_vec())
+            }
+            0xB4 => {
+                at += 3;
+                Command::Write(vgm[at - 2], vgm[at - 1])
+            }
+            0x61 => {
+                at += 3;
+                Command::Wait(u16::from_le_bytes([vgm[at - 2], vgm[at - 1]]) as u64)
+            }
+            0x62 => {
+                at += 1;
+                Command::Wait(735)
+            }
+            0x63 => {
+                at += 1;
+                Command::Wait(882)
+            }
+            cmd @ 0x70..=0x7F => {
+                at += 1;
+                Command::Wait((cmd - 0x6F) as u64)
+            }
+            cmd => panic!("unexpected command {cmd:#04X}"),
+        };
+        commands.push(command);
+    }
+    assert_eq!(at, vgm.len() - 1);
+    commands
+}
+
+#[test]
+fn logs_apu_writes_and_dmc_fetches() {
+    let mut cpu = nes();
+    run(&mut cpu, 6);
+    let logger = VgmLogger::attach(&mut cpu);
+    let start = cpu.general_cycles;
+    run(&mut cpu, 10_000);
+
+    let logger = logger.borrow();
+    let writes: Vec<(Word, Byte)> = logger.writes().iter().map(|w| (w.addr, w.value)).collect();
+    // The first write ran before the logger, `$4014` isn't an APU register
+    assert_eq!(
+        writes,
+        [
+            (0x4012, 0x00),
+            (0x4013, 0x01),
+            (0x4000, 0xBF),
+            (0x4015, 0x10)
+        ]
+    );
+    assert!(logger.writes().windows(2).all(|w| w[0].cycle < w[1].cycle));
+    assert!(logger.writes()[0].cycle < 20);
+    assert!(cpu.general_cycles - start >= 10_000);
+
+    // The whole sample, but none of the OAM DMA reads from page $02
+    let samples: Vec<(Word, Byte)> = logger.samples().iter().map(|(&a, &v)| (a, v)).collect();
+    let expected: Vec<(Word, Byte)> = (0..17).map(|i| (0xC000 + i, 0xA0 + i as Byte)).collect();
+    assert_eq!(samples, expected);
+}
+
+#[test]
+fn logs_the_dummy_write_of_read_modify_writes() {
+    let mut bus = NesBus::new();
+    bus.connect_apu(Box::new(Apu::new()));
+    let mut cpu = CPU::with_profile(Profile::Ricoh2A03);
+    cpu.connect_bus(Box::new(bus));
+    cpu.reset();
+    cpu.write(0x0000, Opcode::IncABS.into());
+    cpu.write(0x0001, 0x00);
+    cpu.write(0x0002, 0x40);
+    cpu.pc = 0x0000;
+    let logger = VgmLogger::attach(&mut cpu);
+    cpu.execute();
+
+    // `$4000` reads back open bus, the operand's high byte
+    let writes: Vec<(u64, Word, Byte)> = logger
+        .borrow()
+        .writes()
+        .iter()
+        .map(|w| (w.cycle, w.addr, w.value))
+        .collect();
+    assert_eq!(writes, [(4, 0x4000, 0x40), (5, 0x4000, 0x41)]);
+}
+
+#[test]
+fn writes_a_vgm_file() {
+    let mut cpu = nes();
+    let logger = VgmLogger::attach(&mut cpu);
+    run(&mut cpu, 10_000);
+    let logger = logger.borrow();
+    let end_cycle = NTSC_CPU_CLOCK as u64;
+    let vgm = logger.to_vgm(end_cycle);
+
+    assert_eq!(&vgm[0..4], b"Vgm ");
+    assert_eq!(u32_at(&vgm, 0x04) as usize, vgm.len() - 4);
+    assert_eq!(u32_at(&vgm, 0x08), 0x161);
+    assert_eq!(u32_at(&vgm, 0x18), 44_100);
+    assert_eq!(u32_at(&vgm, 0x34), 0xC0 - 0x34);
+    assert_eq!(u32_at(&vgm, 0x84), 1_789_773);
+
+    let commands = commands(&vgm);
+    let mut block = vec![0x00, 0xC0];
+    block.extend((0..17).map(|i| 0xA0 + i as Byte));
+    assert_eq!(commands[0], Command::Block(0xC2, block));
+
+    let registers: Vec<&Command> = commands
+        .iter()
+        .filter(|c| matches!(c, Command::Write(..)))
+        .collect();
+    assert_eq!(
+        registers,
+        [
+            &Command::Write(0x10, 0x0F),
+            &Command::Write(0x12, 0x00),
+            &Command::Write(0x13, 0x01),
+            &Command::Write(0x00, 0xBF),
+            &Command::Write(0x15, 0x10),
+        ]
+    );
+    let waited: u64 = commands
+        .iter()
+        .map(|c| match c {
+            Command::Wait(samples) => *samples,
+            _ => 0,
+        })
+        .sum();
+    assert_eq!(waited, 44_100);
+
+    let path = std::env::temp_dir().join(format!("cpu_6502_vgm_{}.vgm", std::process::id()));
+    logger.save(&path, end_cycle).unwrap();
+    let saved = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(saved, vgm);
+}
+
+#[test]
+fn waits_use_the_shortest_commands() {
+    let mut logger = VgmLogger::new(1000);
+    // Cycles are counted from the start given to the logger
+    for (cycle, value) in [(1000, 0), (1203, 1), (31_033, 2), (66_829, 3)] {
+        write_at(&mut logger, cycle, 0x4011, value);
+    }
+    assert_eq!(
+        logger.writes()[1],
+        ApuWrite {
+            cycle: 203,
+            addr: 0x4011,
+            value: 1
+        }
+    );
+    let to_samples = |cycle: f64| (cycle * VGM_SAMPLE_RATE as f64 / NTSC_CPU_CLOCK) as u64;
+    let end = 66_829.0 - 1000.0 + 2.0 * NTSC_CPU_CLOCK;
+    let commands = commands(&logger.to_vgm(end as u64));
+    assert_eq!(
+        commands,
+        [
+            Command::Write(0x11, 0),
+            Command::Wait(5),
+            Command::Write(0x11, 1),
+            Command::Wait(735),
+            Command::Write(0x11, 2),
+            Command::Wait(882),
+            Command::Write(0x11, 3),
+            Command::Wait(65_535),
+            Command::Wait(to_samples(end) - 1622 - 65_535),
+        ]
+    );
+    assert!(logger.samples().is_empty());
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{Ines, load_board};
+use cpu_6502::{
+    bus::{Byte, Word, clock::Lines},
+    cartridge::{
+        Mirroring,
+        mapper::{Mapper, SharedMapper},
+    },
+};
+
+/// VRC6 image: PRG 8 KiB banks hold their bank number, CHR 1 KiB banks `0x80 | bank`
+fn load(mapper: u16) -> SharedMapper {
+    load_board(
+        &Ines::new(mapper, 16, 16)
+            .prg_banks(8 * 1024, |bank, _| bank as Byte)
+            .chr_banks(1024, |bank, _| 0x80 | bank as Byte)
+            .build(),
+    )
+}
+
+fn run(board: &mut dyn Mapper, cycles: usize) -> bool {
+    let mut irq = false;
+    for _ in 0..cycles {
+        let mut lines = Lines::default();
+        board.clock(&mut lines);
+        irq |= lines.irq;
+    }
+    irq
+}
+
+#[test]
+fn prg_and_chr_banking() {
+    let board = load(24);
+    let mut board = board.borrow_mut();
+    board.write(0x8000, 3);
+    board.write(0xC000, 9);
+    let banks = [0x8000, 0xA000, 0xC000, 0xE000].map(|addr: Word| board.read(addr, false));
+    assert_eq!(banks, [6, 7, 9, 31]);
+    board.write(0xD001, 0x11);
+    board.write(0xE003, 0x7F);
+    assert_eq!(board.ppu_read(0x0400), 0x91);
+    assert_eq!(board.ppu_read(0x1FFF), 0xFF);
+}
+
+#[test]
+fn vrc6b_swaps_a0_and_a1() {
+    let board = load(26);
+    let mut board = board.borrow_mut();
+    // $D002 on VRC6b is register 1
+    board.write(0xD002, 0x22);
+    board.write(0xD001, 0x33);
+    assert_eq!(board.ppu_read(0x0400), 0xA2);
+    assert_eq!(board.ppu_read(0x0800), 0xB3);
+    // $B003 stays $B003
+    board.write(0xB003, 0x84);
+    assert_eq!(board.mirroring(), Mirroring::Horizontal);
+}
+
+#[test]
+fn mirroring_and_prg_ram() {
+    let board = load(24);
+    let mut board = board.borrow_mut();
+    board.write(0x6000, 0x55);
+    assert_eq!(board.driven_bits(0x6000), 0x00);
+    board.write(0xB003, 0x88);
+    assert_eq!(board.mirroring(), Mirroring::SingleScreenLower);
+    board.write(0x6000, 0x55);
+    assert_eq!(board.read(0x6000, false), 0x55);
+}
+
+#[test]
+fn cycle_mode_irq() {
+    let board = load(24);
+    let mut board = board.borrow_mut();
+    board.write(0xF000, 0xFC);
+    board.write(0xF001, 0x07);
+    assert!(!run(&mut *board, 3));
+    assert!(run(&mut *board, 1));
+    // acknowledging keeps counting since A was set
+    board.write(0xF002, 0);
+    assert!(!run(&mut *board, 3));
+    assert!(run(&mut *board, 1));
+}
+
+#[test]
+fn scanline_mode_irq() {
+    let board = load(24);
+    let mut board = board.borrow_mut();
+    board.write(0xF000, 0xFE);
+    board.write(0xF001, 0x02);
+    // two scanlines of 113.67 cycles
+    assert!(!run(&mut *board, 227));
+    assert!(run(&mut *board, 1));
+    board.write(0xF002, 0);
+    assert!(!run(&mut *board, 1000));
+}
+
+#[test]
+fn pulse_duty_and_sawtooth() {
+    let board = load(24);
+    let mut board = board.borrow_mut();
+    let level = |board: &dyn Mapper| board.sample_source().unwrap().sample();
+    assert_eq!(level(&*board), 0.0);
+
+    // duty 8/16 at volume 15, period 0 steps every cycle
+    board.write(0x9000, 0x7F);
+    board.write(0x9001, 0x00);
+    board.write(0x9002, 0x80);
+    let mut highs = 0;
+    for _ in 0..16 {
+        run(&mut *board, 1);
+        if level(&*board) > 0.0 {
+            highs += 1;
+        }
+    }
+    assert_eq!(highs, 8);
+
+    board.write(0x9002, 0x00);
+    board.write(0xB000, 0x08);
+    board.write(0xB002, 0x80);
+    run(&mut *board, 12);
+    // 6 accumulations of 8 give 48, output 48 >> 3
+    let saw = level(&*board);
+    run(&mut *board, 2);
+    assert_eq!(level(&*board), 0.0);
+    assert!((saw - 6.0 * 0.1494 / 15.0).abs() < 1e-6);
+}